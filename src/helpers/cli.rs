@@ -0,0 +1,603 @@
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::helpers::config::{Config, QueuePolicy, StatsFormat};
+use crate::helpers::error::CustomError;
+use crate::helpers::file_reader;
+use crate::helpers::logger::{LogFilter, LOG_ENV};
+use crate::structs::dashboard;
+use crate::structs::event_bus::{EventSubscriber, FileSubscriber};
+use crate::structs::menu::Menu;
+use crate::structs::order::OrderFormat;
+use crate::structs::stats_renderer::{self, StatsRenderer};
+
+/// Texto que se imprime con `--help`.
+pub const HELP: &str = "\
+CoffeeGPT: simulador de cafetera concurrente.
+
+Uso: coffee-gpt [OPCIONES] <archivo_pedidos>
+     coffee-gpt [OPCIONES] --serve <direccion>
+
+Argumentos:
+  <archivo_pedidos>          Archivo con un pedido por línea, o - para la entrada estándar.
+
+Opciones:
+  -c, --config <archivo>     Archivo de configuración de la cafetera.
+  -m, --menu <archivo>       Archivo con las bebidas que se pueden pedir por nombre.
+      --time-scale <factor>  Multiplica todos los tiempos de acción. 0.1 corre 10 veces más rápido.
+      --stats-interval <ms>  Cada cuántos milisegundos se imprimen las estadísticas.
+      --stats-format <fmt>   Formato de las estadísticas: text, json o csv.
+      --stats-file <archivo> Escribe las estadísticas en el archivo en lugar de la salida estándar.
+      --events <archivo>     Escribe los eventos de la cafetera en el archivo, además de imprimirlos.
+      --trace <archivo>      Escribe la actividad de cada hilo en el archivo, en el formato Trace Event de Chrome.
+      --log-level <filtro>   Nivel de los mensajes: error, warn, info o debug, para todos o por módulo
+                             (coffee, water, cocoa, milk, orders, stats, general), por ejemplo warn,orders=debug.
+                             Por defecto el de la variable COFFEE_GPT_LOG o debug.
+      --dashboard            Muestra un tablero que se redibuja en la terminal en lugar de las estadísticas y los eventos.
+                             Los mensajes van a la salida de error, por ejemplo para guardarlos con 2> cafetera.log.
+      --dry-run              Solo valida el archivo de pedidos, sin prepararlos.
+      --seed <n>             Semilla de las variaciones de tiempo para repetir una corrida. Solo tiene efecto si
+                             VARIACION_TIEMPOS no es 0 en la configuración, por defecto es 0.
+      --simulated            Usa un reloj virtual: la corrida termina en segundos y es reproducible.
+                             No se puede usar con `-` ni con un pipe con nombre.
+      --baristas <n>         Cantidad de baristas que preparan pedidos en simultáneo.
+      --queue-capacity <n>   Cantidad máxima de pedidos esperando a un barista.
+      --queue-policy <pol>   Qué hacer con la cola llena: block (esperar) o reject (rechazar).
+      --order-format <fmt>   Formato del archivo de pedidos: csv o jsonl. Por defecto según la extensión.
+      --fifo                 Atiende los dispensadores en orden de llegada en lugar de por prioridad.
+      --metrics <direccion>  Publica métricas para Prometheus en http://<direccion>/metrics. Solo direcciones locales.
+      --serve <direccion>    Recibe los pedidos por TCP en lugar de un archivo, por ejemplo 127.0.0.1:7878.
+  -h, --help                 Muestra esta ayuda.
+";
+
+/// Argumentos con los que se ejecuta el programa.
+#[derive(Debug, PartialEq)]
+pub struct CliArgs {
+    /// Path del archivo de pedidos. Vacío si se reciben por TCP.
+    pub orders_path: String,
+    /// Path del archivo de configuración, si se indicó.
+    pub config_path: Option<String>,
+    /// Path del archivo de menú, si se indicó.
+    pub menu_path: Option<String>,
+    /// Factor por el que se multiplican los tiempos de acción.
+    pub time_scale: Option<f64>,
+    /// Milisegundos entre cada impresión de estadísticas.
+    pub stats_interval: Option<u64>,
+    /// Formato de las estadísticas.
+    pub stats_format: Option<StatsFormat>,
+    /// Path del archivo en el que se escriben las estadísticas, si se indicó.
+    pub stats_file: Option<String>,
+    /// Path del archivo en el que se escriben los eventos, si se indicó.
+    pub events_file: Option<String>,
+    /// Path del archivo en el que se escribe la línea de tiempo, si se indicó.
+    pub trace_file: Option<String>,
+    /// Nivel máximo de los mensajes que se imprimen de cada módulo, si se indicó.
+    pub log_filter: Option<LogFilter>,
+    /// Si se muestra el tablero en lugar de las estadísticas y los eventos.
+    pub dashboard: bool,
+    /// Si solo se debe validar el archivo de pedidos.
+    pub dry_run: bool,
+    /// Semilla de las variaciones de tiempo.
+    pub seed: Option<u64>,
+    /// Si se debe usar el reloj simulado en lugar del real.
+    pub simulated: bool,
+    /// Cantidad de baristas.
+    pub baristas: Option<u32>,
+    /// Cantidad máxima de pedidos en la cola.
+    pub queue_capacity: Option<u32>,
+    /// Política de la cola llena.
+    pub queue_policy: Option<QueuePolicy>,
+    /// Dirección en la que se reciben pedidos por TCP, si se indicó.
+    pub serve: Option<String>,
+    /// Dirección local en la que se publican las métricas, si se indicó.
+    pub metrics: Option<String>,
+    /// Formato del archivo de pedidos.
+    pub order_format: Option<OrderFormat>,
+    /// Si los dispensadores se atienden en orden de llegada.
+    pub fifo: bool,
+}
+
+/// Resultado de interpretar la línea de comandos.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Se pidió la ayuda.
+    Help,
+    /// Se debe ejecutar la cafetera con estos argumentos.
+    Run(Box<CliArgs>),
+}
+
+impl CliArgs {
+    /// Interpreta los argumentos, sin incluir el nombre del programa.
+    /// Errores:
+    /// * Si alguna opción es desconocida o su valor es inválido devuelve [`CustomError::InvalidArguments`]
+    /// * Si no se indicó el archivo de pedidos ni `--serve` devuelve [`CustomError::InvalidOrderFilePath`]
+    pub fn parse(args: &[String]) -> Result<Command, CustomError> {
+        let mut orders_path = None;
+        let mut cli_args = CliArgs {
+            orders_path: String::new(),
+            config_path: None,
+            menu_path: None,
+            time_scale: None,
+            stats_interval: None,
+            stats_format: None,
+            stats_file: None,
+            events_file: None,
+            trace_file: None,
+            log_filter: None,
+            dashboard: false,
+            dry_run: false,
+            seed: None,
+            simulated: false,
+            baristas: None,
+            queue_capacity: None,
+            queue_policy: None,
+            serve: None,
+            metrics: None,
+            order_format: None,
+            fifo: false,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || match inline_value.clone().or_else(|| args.next().cloned()) {
+                Some(value) => Ok(value),
+                None => {
+                    error!("Falta el valor de la opción {}.", flag);
+                    Err(CustomError::InvalidArguments)
+                }
+            };
+            match flag {
+                "-h" | "--help" => return Ok(Command::Help),
+                "-c" | "--config" => cli_args.config_path = Some(value()?),
+                "-m" | "--menu" => cli_args.menu_path = Some(value()?),
+                "--time-scale" => {
+                    cli_args.time_scale = Some(parse_value(flag, &value()?, |scale: &f64| {
+                        scale.is_finite() && *scale > 0.0
+                    })?)
+                }
+                "--stats-interval" => {
+                    cli_args.stats_interval =
+                        Some(parse_value(flag, &value()?, |interval: &u64| {
+                            *interval > 0
+                        })?)
+                }
+                "--stats-format" => {
+                    let format = value()?;
+                    cli_args.stats_format = Some(StatsFormat::parse(&format).ok_or_else(|| {
+                        error!("Formato de estadísticas inválido: {:?}.", format);
+                        CustomError::InvalidArguments
+                    })?)
+                }
+                "--stats-file" => cli_args.stats_file = Some(value()?),
+                "--events" => cli_args.events_file = Some(value()?),
+                "--trace" => cli_args.trace_file = Some(value()?),
+                "--log-level" => cli_args.log_filter = Some(parse_log_filter(&value()?)?),
+                "--baristas" => {
+                    cli_args.baristas = Some(parse_value(flag, &value()?, |baristas: &u32| {
+                        *baristas > 0
+                    })?)
+                }
+                "--queue-capacity" => {
+                    cli_args.queue_capacity =
+                        Some(parse_value(flag, &value()?, |capacity: &u32| {
+                            *capacity > 0
+                        })?)
+                }
+                "--queue-policy" => {
+                    let policy = value()?;
+                    cli_args.queue_policy = Some(QueuePolicy::parse(&policy).ok_or_else(|| {
+                        error!("Política de cola inválida: {:?}.", policy);
+                        CustomError::InvalidArguments
+                    })?)
+                }
+                "--order-format" => {
+                    let format = value()?;
+                    cli_args.order_format = Some(OrderFormat::parse(&format).ok_or_else(|| {
+                        error!("Formato de pedidos inválido: {:?}.", format);
+                        CustomError::InvalidArguments
+                    })?)
+                }
+                "--serve" => cli_args.serve = Some(value()?),
+                "--metrics" => cli_args.metrics = Some(value()?),
+                "--dashboard" => cli_args.dashboard = true,
+                "--dry-run" => cli_args.dry_run = true,
+                "--simulated" => cli_args.simulated = true,
+                "--fifo" => cli_args.fifo = true,
+                "--seed" => cli_args.seed = Some(parse_value(flag, &value()?, |_: &u64| true)?),
+                _ if flag.starts_with('-') && flag.len() > 1 => {
+                    error!(
+                        "Opción desconocida {}. Usar --help para ver las opciones.",
+                        flag
+                    );
+                    return Err(CustomError::InvalidArguments);
+                }
+                _ => {
+                    if orders_path.is_some() {
+                        error!("Se indicó más de un archivo de pedidos.");
+                        return Err(CustomError::InvalidArguments);
+                    }
+                    orders_path = Some(arg.clone());
+                }
+            }
+        }
+
+        if cli_args.dashboard && cli_args.stats_file.is_some() {
+            error!("Con --dashboard no se puede usar --stats-file: el tablero reemplaza a las estadísticas.");
+            return Err(CustomError::InvalidArguments);
+        }
+        if cli_args.serve.is_some() {
+            if orders_path.is_some() || cli_args.dry_run || cli_args.simulated {
+                error!("Con --serve no se indica archivo de pedidos ni se puede usar --dry-run o --simulated.");
+                return Err(CustomError::InvalidArguments);
+            }
+            return Ok(Command::Run(Box::new(cli_args)));
+        }
+        match orders_path {
+            Some(orders_path) => {
+                if cli_args.simulated && file_reader::is_stream(&orders_path) {
+                    error!("Con --simulated los pedidos no se pueden leer de la entrada estándar ni de un pipe: el reloj virtual se detendría esperando cada línea.");
+                    return Err(CustomError::InvalidArguments);
+                }
+                cli_args.orders_path = orders_path;
+                Ok(Command::Run(Box::new(cli_args)))
+            }
+            None => {
+                error!(
+                    "No se específico el archivo de pedidos. Usar --help para ver las opciones."
+                );
+                Err(CustomError::InvalidOrderFilePath)
+            }
+        }
+    }
+
+    /// Carga la configuración del archivo indicado, o la por defecto, y le aplica las opciones de la línea de comandos.
+    /// Con `--dashboard` y sin `--stats-interval` las estadísticas se toman cada [`dashboard::REFRESH_TIME`] milisegundos.
+    /// Devuelve los errores de [`Config::from_file`] o [`CustomError::InvalidConfigValue`] si el resultado es inválido.
+    pub fn load_config(&self) -> Result<Config, CustomError> {
+        let mut config = match &self.config_path {
+            Some(config_path) => Config::from_file(config_path)?,
+            None => Config::default(),
+        };
+        if let Some(time_scale) = self.time_scale {
+            config.scale_times(time_scale);
+        }
+        if let Some(stats_interval) = self.stats_interval {
+            config.time_to_stats = stats_interval;
+        } else if self.dashboard {
+            config.time_to_stats = dashboard::REFRESH_TIME;
+        }
+        if let Some(stats_format) = self.stats_format {
+            config.stats_format = stats_format;
+        }
+        if let Some(seed) = self.seed {
+            config.seed = seed;
+            if config.time_variation == 0 {
+                warn!("--seed no tiene efecto: VARIACION_TIEMPOS es 0, los tiempos de acción no varían.");
+            }
+        }
+        if let Some(baristas) = self.baristas {
+            config.baristas = baristas;
+        }
+        if let Some(queue_capacity) = self.queue_capacity {
+            config.queue_capacity = queue_capacity;
+        }
+        if let Some(queue_policy) = self.queue_policy {
+            config.queue_policy = queue_policy;
+        }
+        if let Some(order_format) = self.order_format {
+            config.order_format = Some(order_format);
+        }
+        if self.fifo {
+            config.set_fifo_dispensers();
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Carga el menú del archivo indicado, o el por defecto.
+    /// Devuelve los errores de [`Menu::from_file`].
+    pub fn load_menu(&self) -> Result<Menu, CustomError> {
+        match &self.menu_path {
+            Some(menu_path) => Menu::from_file(menu_path),
+            None => Ok(Menu::default()),
+        }
+    }
+
+    /// Filtro de los mensajes: el de `--log-level`, o si no se indicó el de la variable de entorno [`LOG_ENV`],
+    /// o si tampoco está definida el que imprime todo.
+    /// Si el filtro de la variable de entorno es inválido devuelve [`CustomError::InvalidArguments`]
+    pub fn load_log_filter(&self) -> Result<LogFilter, CustomError> {
+        match (self.log_filter, env::var(LOG_ENV)) {
+            (Some(filter), _) => Ok(filter),
+            (None, Ok(spec)) => parse_log_filter(&spec),
+            (None, Err(_)) => Ok(LogFilter::default()),
+        }
+    }
+
+    /// Si se indicó `--stats-file`, crea el archivo y devuelve el renderizador que escribe las estadísticas
+    /// en él, en el formato `format`.
+    /// Si el archivo no se puede crear devuelve [`CustomError::CantOpenStatsFile`]
+    pub fn load_stats_renderer(
+        &self,
+        format: StatsFormat,
+    ) -> Result<Option<Box<dyn StatsRenderer>>, CustomError> {
+        let Some(stats_file) = &self.stats_file else {
+            return Ok(None);
+        };
+        match File::create(stats_file) {
+            Ok(file) => Ok(Some(stats_renderer::for_format(
+                format,
+                Box::new(BufWriter::new(file)),
+            ))),
+            Err(e) => {
+                error!(
+                    "No se pudo crear el archivo de estadísticas {:?}: {:?}",
+                    stats_file, e
+                );
+                Err(CustomError::CantOpenStatsFile)
+            }
+        }
+    }
+
+    /// Si se indicó `--trace`, crea el archivo en el que se escribirá la línea de tiempo al terminar.
+    /// Se crea antes de empezar para no preparar todos los pedidos y perder la línea de tiempo.
+    /// Si el archivo no se puede crear devuelve [`CustomError::CantOpenTraceFile`]
+    pub fn load_trace_output(&self) -> Result<Option<BufWriter<File>>, CustomError> {
+        let Some(trace_file) = &self.trace_file else {
+            return Ok(None);
+        };
+        match File::create(trace_file) {
+            Ok(file) => Ok(Some(BufWriter::new(file))),
+            Err(e) => {
+                error!(
+                    "No se pudo crear el archivo de la línea de tiempo {:?}: {:?}",
+                    trace_file, e
+                );
+                Err(CustomError::CantOpenTraceFile)
+            }
+        }
+    }
+
+    /// Si se indicó `--events`, crea el archivo y devuelve el suscriptor que escribe los eventos en él.
+    /// Si el archivo no se puede crear devuelve [`CustomError::CantOpenEventsFile`]
+    pub fn load_event_subscriber(&self) -> Result<Option<Box<dyn EventSubscriber>>, CustomError> {
+        let Some(events_file) = &self.events_file else {
+            return Ok(None);
+        };
+        match File::create(events_file) {
+            Ok(file) => Ok(Some(Box::new(FileSubscriber::new(BufWriter::new(file))))),
+            Err(e) => {
+                error!(
+                    "No se pudo crear el archivo de eventos {:?}: {:?}",
+                    events_file, e
+                );
+                Err(CustomError::CantOpenEventsFile)
+            }
+        }
+    }
+}
+
+/// Interpreta un filtro de mensajes con [`LogFilter::parse`].
+/// Si es inválido devuelve [`CustomError::InvalidArguments`]
+fn parse_log_filter(spec: &str) -> Result<LogFilter, CustomError> {
+    LogFilter::parse(spec).ok_or_else(|| {
+        error!("Filtro de log inválido: {:?}.", spec);
+        CustomError::InvalidArguments
+    })
+}
+
+/// Interpreta el valor de una opción y verifica que sea válido.
+fn parse_value<T: std::str::FromStr>(
+    flag: &str,
+    value: &str,
+    is_valid: impl Fn(&T) -> bool,
+) -> Result<T, CustomError> {
+    match value.parse() {
+        Ok(parsed) if is_valid(&parsed) => Ok(parsed),
+        _ => {
+            error!("Valor inválido para {}: {:?}.", flag, value);
+            Err(CustomError::InvalidArguments)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_only_orders_path() {
+        match CliArgs::parse(&args(&["pedidos.txt"])) {
+            Ok(Command::Run(cli_args)) => {
+                assert_eq!(cli_args.orders_path, "pedidos.txt");
+                assert_eq!(cli_args.config_path, None);
+                assert_eq!(cli_args.log_filter, None);
+                assert!(!cli_args.dry_run);
+                assert!(!cli_args.simulated);
+            }
+            other => panic!("Resultado inesperado: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_all_options() {
+        let parsed = CliArgs::parse(&args(&[
+            "--config",
+            "config.ini",
+            "--menu=menu.ini",
+            "--time-scale=0.1",
+            "--stats-interval",
+            "200",
+            "--stats-format",
+            "csv",
+            "--stats-file=stats.csv",
+            "--events",
+            "eventos.log",
+            "--trace=traza.json",
+            "--log-level",
+            "warn,orders=debug",
+            "--dry-run",
+            "--seed",
+            "42",
+            "--simulated",
+            "--baristas",
+            "4",
+            "--queue-capacity=10",
+            "--queue-policy",
+            "reject",
+            "--order-format",
+            "jsonl",
+            "--fifo",
+            "--metrics",
+            "127.0.0.1:9898",
+            "pedidos.txt",
+        ]));
+        assert_eq!(
+            parsed,
+            Ok(Command::Run(Box::new(CliArgs {
+                orders_path: "pedidos.txt".to_string(),
+                config_path: Some("config.ini".to_string()),
+                menu_path: Some("menu.ini".to_string()),
+                time_scale: Some(0.1),
+                stats_interval: Some(200),
+                stats_format: Some(StatsFormat::Csv),
+                stats_file: Some("stats.csv".to_string()),
+                events_file: Some("eventos.log".to_string()),
+                trace_file: Some("traza.json".to_string()),
+                log_filter: LogFilter::parse("warn,orders=debug"),
+                dashboard: false,
+                dry_run: true,
+                seed: Some(42),
+                simulated: true,
+                baristas: Some(4),
+                queue_capacity: Some(10),
+                queue_policy: Some(QueuePolicy::Reject),
+                serve: None,
+                metrics: Some("127.0.0.1:9898".to_string()),
+                order_format: Some(OrderFormat::Jsonl),
+                fifo: true,
+            })))
+        );
+    }
+
+    #[test]
+    fn test_parse_serve() {
+        match CliArgs::parse(&args(&["--serve", "127.0.0.1:7878"])) {
+            Ok(Command::Run(cli_args)) => {
+                assert_eq!(cli_args.serve, Some("127.0.0.1:7878".to_string()));
+                assert!(cli_args.orders_path.is_empty());
+            }
+            other => panic!("Resultado inesperado: {:?}", other),
+        }
+        assert_eq!(
+            CliArgs::parse(&args(&["--serve", "127.0.0.1:7878", "pedidos.txt"])),
+            Err(CustomError::InvalidArguments)
+        );
+        assert_eq!(
+            CliArgs::parse(&args(&["--serve", "127.0.0.1:7878", "--simulated"])),
+            Err(CustomError::InvalidArguments)
+        );
+        assert_eq!(
+            CliArgs::parse(&args(&["-", "--simulated"])),
+            Err(CustomError::InvalidArguments)
+        );
+    }
+
+    #[test]
+    fn test_parse_dashboard() {
+        let cli_args = match CliArgs::parse(&args(&["--dashboard", "pedidos.txt"])) {
+            Ok(Command::Run(cli_args)) => cli_args,
+            other => panic!("Resultado inesperado: {:?}", other),
+        };
+        assert!(cli_args.dashboard);
+        assert_eq!(
+            cli_args.load_config().unwrap().time_to_stats,
+            dashboard::REFRESH_TIME
+        );
+        assert_eq!(
+            CliArgs::parse(&args(&["pedidos.txt", "--dashboard", "--stats-file=s.csv"])),
+            Err(CustomError::InvalidArguments)
+        );
+    }
+
+    #[test]
+    fn test_parse_help() {
+        assert_eq!(
+            CliArgs::parse(&args(&["pedidos.txt", "--help"])),
+            Ok(Command::Help)
+        );
+        assert_eq!(CliArgs::parse(&args(&["-h"])), Ok(Command::Help));
+    }
+
+    #[test]
+    fn test_parse_invalid_arguments() {
+        assert_eq!(
+            CliArgs::parse(&args(&[])),
+            Err(CustomError::InvalidOrderFilePath)
+        );
+        assert_eq!(
+            CliArgs::parse(&args(&["--verbose", "pedidos.txt"])),
+            Err(CustomError::InvalidArguments)
+        );
+        assert_eq!(
+            CliArgs::parse(&args(&["pedidos.txt", "--time-scale", "-1"])),
+            Err(CustomError::InvalidArguments)
+        );
+        assert_eq!(
+            CliArgs::parse(&args(&["pedidos.txt", "--seed"])),
+            Err(CustomError::InvalidArguments)
+        );
+        assert_eq!(
+            CliArgs::parse(&args(&["pedidos.txt", "--baristas", "0"])),
+            Err(CustomError::InvalidArguments)
+        );
+        assert_eq!(
+            CliArgs::parse(&args(&["pedidos.txt", "otros.txt"])),
+            Err(CustomError::InvalidArguments)
+        );
+    }
+
+    #[test]
+    fn test_load_config_applies_options() {
+        let cli_args = match CliArgs::parse(&args(&[
+            "-c",
+            "src/tests/config.ini",
+            "--time-scale",
+            "2",
+            "--stats-interval",
+            "50",
+            "--seed",
+            "3",
+            "pedidos.txt",
+        ])) {
+            Ok(Command::Run(cli_args)) => cli_args,
+            other => panic!("Resultado inesperado: {:?}", other),
+        };
+        let config = cli_args.load_config().unwrap();
+        assert_eq!(config.dispensers, 2);
+        assert_eq!(config.serve_coffee_time, 200);
+        assert_eq!(config.time_to_stats, 50);
+        assert_eq!(config.seed, 3);
+        assert!(cli_args.load_menu().unwrap().recipe("mocha").is_some());
+    }
+
+    #[test]
+    fn test_load_menu_from_file() {
+        let cli_args = match CliArgs::parse(&args(&["-m", "src/tests/menu.ini", "pedidos.txt"])) {
+            Ok(Command::Run(cli_args)) => cli_args,
+            other => panic!("Resultado inesperado: {:?}", other),
+        };
+        let menu = cli_args.load_menu().unwrap();
+        assert_eq!(menu.recipe("lagrima").unwrap().milk_foam, 20);
+        assert!(menu.recipe("mocha").is_none());
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle, ThreadId};
+use std::time::{Duration, Instant};
+
+use crate::helpers::error::CustomError;
+
+/// Cada cuántos milisegundos virtuales se vuelve a chequear una condición con el reloj simulado.
+pub const POLL_TIME: u64 = 10;
+
+/// Reloj que utilizan la cafetera, los contenedores y las estadísticas para todas las esperas.
+///
+/// * `Real`: las esperas duermen el hilo el tiempo indicado.
+/// * `Simulated`: el tiempo es virtual. Solo un hilo participante corre a la vez y el tiempo avanza
+///   únicamente cuando todos están durmiendo, saltando directamente al próximo en despertarse.
+///   Así una corrida de horas termina en segundos y, al no depender del planificador del sistema,
+///   repite exactamente la misma línea de tiempo en cada ejecución.
+///
+/// Con el reloj simulado todos los hilos deben crearse con [`Clock::spawn_named`] y ningún hilo puede
+/// esperar mientras tiene tomado un lock, porque el resto no podría correr.
+#[derive(Clone)]
+pub enum Clock {
+    Real(Instant),
+    Simulated(Arc<Scheduler>),
+}
+
+impl Clock {
+    pub fn real() -> Clock {
+        Clock::Real(Instant::now())
+    }
+
+    pub fn simulated() -> Clock {
+        Clock::Simulated(Arc::new(Scheduler::new()))
+    }
+
+    /// Milisegundos transcurridos desde que se creó el reloj.
+    pub fn now(&self) -> u64 {
+        match self {
+            Clock::Real(start) => start.elapsed().as_millis() as u64,
+            Clock::Simulated(scheduler) => scheduler.now(),
+        }
+    }
+
+    /// Espera la duración indicada.
+    /// Con el reloj simulado cede el turno hasta que el tiempo virtual alcance el momento de despertarse.
+    pub fn sleep(&self, duration: Duration) {
+        match self {
+            Clock::Real(_) => thread::sleep(duration),
+            Clock::Simulated(scheduler) => scheduler.sleep(duration.as_millis() as u64),
+        }
+    }
+
+    /// Toma el lock y espera mientras se cumpla la condición, como [`Condvar::wait_while`].
+    /// Con el reloj simulado la condición se vuelve a chequear cada [`POLL_TIME`] milisegundos virtuales.
+    ///
+    /// Si el lock está envenenado devuelve [`CustomError::PoisonedLock`]
+    pub fn wait_while<'a, T, F>(
+        &self,
+        lock: &'a Mutex<T>,
+        cvar: &Condvar,
+        mut condition: F,
+    ) -> Result<MutexGuard<'a, T>, CustomError>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        match self {
+            Clock::Real(_) => Ok(cvar.wait_while(lock.lock()?, condition)?),
+            Clock::Simulated(scheduler) => {
+                let mut guard = lock.lock()?;
+                while condition(&mut guard) {
+                    drop(guard);
+                    scheduler.sleep(POLL_TIME);
+                    guard = lock.lock()?;
+                }
+                Ok(guard)
+            }
+        }
+    }
+
+    /// Crea un hilo con nombre que participa del reloj. El nombre se ve en los mensajes del logger, en los de pánico y en `/proc`.
+    pub fn spawn_named<F, T>(&self, name: String, f: F) -> Handle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let builder = thread::Builder::new().name(name);
+        match self {
+            Clock::Real(_) => Handle {
+                handle: spawn_thread(builder, f),
+                participant: None,
+            },
+            Clock::Simulated(scheduler) => {
+                let id = scheduler.register();
+                let scheduler_clone = scheduler.clone();
+                let handle = spawn_thread(builder, move || {
+                    let _participant = scheduler_clone.enter(id);
+                    f()
+                });
+                Handle {
+                    handle,
+                    participant: Some((scheduler.clone(), id)),
+                }
+            }
+        }
+    }
+
+    /// Hace que el hilo actual participe del reloj mientras viva el valor devuelto.
+    /// Con el reloj real no hace nada.
+    pub fn participate(&self) -> Option<Participant> {
+        match self {
+            Clock::Real(_) => None,
+            Clock::Simulated(scheduler) => {
+                let id = scheduler.register();
+                Some(scheduler.enter(id))
+            }
+        }
+    }
+}
+
+/// Crea el hilo. Igual que [`thread::spawn`], entra en pánico si el sistema operativo no lo puede crear.
+fn spawn_thread<F, T>(builder: thread::Builder, f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match builder.spawn(f) {
+        Ok(handle) => handle,
+        Err(e) => panic!("No se pudo crear el hilo: {:?}", e),
+    }
+}
+
+/// Hilo creado con [`Clock::spawn_named`].
+pub struct Handle<T> {
+    handle: JoinHandle<T>,
+    participant: Option<(Arc<Scheduler>, usize)>,
+}
+
+impl<T> Handle<T> {
+    /// Espera a que termine el hilo.
+    /// Con el reloj simulado cede el turno mientras tanto.
+    pub fn join(self) -> thread::Result<T> {
+        if let Some((scheduler, id)) = self.participant {
+            scheduler.wait_exit(id);
+        }
+        self.handle.join()
+    }
+}
+
+/// Marca a un hilo como participante del reloj simulado. Al liberarse el hilo deja de participar.
+pub struct Participant {
+    scheduler: Arc<Scheduler>,
+    id: usize,
+}
+
+impl Drop for Participant {
+    fn drop(&mut self) {
+        self.scheduler.exit(self.id);
+    }
+}
+
+/// Estado del planificador del reloj simulado.
+struct SchedulerState {
+    /// Tiempo virtual en milisegundos.
+    now: u64,
+    /// Identificador del próximo participante.
+    next_id: usize,
+    /// Participante que tiene el turno.
+    running: Option<usize>,
+    /// Participantes esperando su turno, ordenados por momento en que se despiertan y por identificador.
+    ready: BTreeSet<(u64, usize)>,
+    /// Participantes que ya terminaron.
+    finished: HashSet<usize>,
+    /// Participantes esperando que termine otro.
+    joiners: HashMap<usize, Vec<usize>>,
+    /// Participante asociado a cada hilo.
+    threads: HashMap<ThreadId, usize>,
+}
+
+/// Planificador de eventos discretos del reloj simulado.
+pub struct Scheduler {
+    state: Mutex<SchedulerState>,
+    cvar: Condvar,
+}
+
+impl Scheduler {
+    fn new() -> Scheduler {
+        Scheduler {
+            state: Mutex::new(SchedulerState {
+                now: 0,
+                next_id: 0,
+                running: None,
+                ready: BTreeSet::new(),
+                finished: HashSet::new(),
+                joiners: HashMap::new(),
+                threads: HashMap::new(),
+            }),
+            cvar: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SchedulerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn now(&self) -> u64 {
+        self.lock().now
+    }
+
+    /// Agrega un participante listo para correr en el tiempo actual.
+    fn register(&self) -> usize {
+        let mut state = self.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        let now = state.now;
+        state.ready.insert((now, id));
+        self.dispatch(&mut state);
+        id
+    }
+
+    /// Asocia el hilo actual al participante y espera su turno.
+    fn enter(self: &Arc<Self>, id: usize) -> Participant {
+        let mut state = self.lock();
+        state.threads.insert(thread::current().id(), id);
+        self.wait_turn(state, id);
+        Participant {
+            scheduler: self.clone(),
+            id,
+        }
+    }
+
+    fn exit(&self, id: usize) {
+        let mut state = self.lock();
+        state.finished.insert(id);
+        state.threads.remove(&thread::current().id());
+        let now = state.now;
+        for joiner in state.joiners.remove(&id).unwrap_or_default() {
+            state.ready.insert((now, joiner));
+        }
+        if state.running == Some(id) {
+            state.running = None;
+        }
+        self.dispatch(&mut state);
+    }
+
+    fn sleep(&self, millis: u64) {
+        let mut state = self.lock();
+        let id = match state.threads.get(&thread::current().id()) {
+            Some(id) => *id,
+            None => return,
+        };
+        let wake_time = state.now + millis;
+        state.ready.insert((wake_time, id));
+        state.running = None;
+        self.dispatch(&mut state);
+        self.wait_turn(state, id);
+    }
+
+    /// Cede el turno hasta que termine el participante indicado.
+    fn wait_exit(&self, target: usize) {
+        let mut state = self.lock();
+        if state.finished.contains(&target) {
+            return;
+        }
+        let id = match state.threads.get(&thread::current().id()) {
+            Some(id) => *id,
+            None => {
+                drop(
+                    self.cvar
+                        .wait_while(state, |state| !state.finished.contains(&target)),
+                );
+                return;
+            }
+        };
+        state.joiners.entry(target).or_default().push(id);
+        state.running = None;
+        self.dispatch(&mut state);
+        self.wait_turn(state, id);
+    }
+
+    /// Si nadie tiene el turno se lo da al próximo participante, avanzando el tiempo si hace falta.
+    fn dispatch(&self, state: &mut SchedulerState) {
+        if state.running.is_some() {
+            return;
+        }
+        if let Some((wake_time, id)) = state.ready.pop_first() {
+            state.now = state.now.max(wake_time);
+            state.running = Some(id);
+        }
+        self.cvar.notify_all();
+    }
+
+    fn wait_turn(&self, state: MutexGuard<SchedulerState>, id: usize) {
+        drop(
+            self.cvar
+                .wait_while(state, |state| state.running != Some(id))
+                .unwrap_or_else(|e| e.into_inner()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulated_sleep_does_not_wait() {
+        let clock = Clock::simulated();
+        let start = Instant::now();
+        let clock_clone = clock.clone();
+        let handle = clock.spawn_named("dormir".to_string(), move || {
+            clock_clone.sleep(Duration::from_secs(3600));
+            clock_clone.now()
+        });
+        assert_eq!(handle.join().unwrap(), 3600 * 1000);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(clock.now(), 3600 * 1000);
+    }
+
+    #[test]
+    fn test_simulated_timeline_is_deterministic() {
+        let timeline = || {
+            let clock = Clock::simulated();
+            let events = Arc::new(Mutex::new(Vec::new()));
+            let _participant = clock.participate();
+            let handles: Vec<Handle<()>> = (0..5)
+                .map(|id| {
+                    let clock_clone = clock.clone();
+                    let events_clone = events.clone();
+                    clock.spawn_named(format!("participante-{}", id), move || {
+                        for step in 0..3 {
+                            clock_clone.sleep(Duration::from_millis(7 * (id + 1) + step));
+                            events_clone
+                                .lock()
+                                .unwrap()
+                                .push((clock_clone.now(), id, step));
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+            let events = events.lock().unwrap().clone();
+            events
+        };
+        let first = timeline();
+        assert_eq!(first.len(), 15);
+        assert!(first.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert_eq!(first, timeline());
+    }
+
+    #[test]
+    fn test_simulated_wait_while() {
+        let clock = Clock::simulated();
+        let _participant = clock.participate();
+        let state = Arc::new((Mutex::new(0), Condvar::new()));
+        let state_clone = state.clone();
+        let clock_clone = clock.clone();
+        let handle = clock.spawn_named("despertar".to_string(), move || {
+            clock_clone.sleep(Duration::from_millis(500));
+            *state_clone.0.lock().unwrap() = 1;
+        });
+        let value = clock
+            .wait_while(&state.0, &state.1, |value| *value == 0)
+            .unwrap();
+        assert_eq!(*value, 1);
+        assert!(clock.now() >= 500);
+        drop(value);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_real_clock() {
+        let clock = Clock::real();
+        clock.sleep(Duration::from_millis(20));
+        assert!(clock.now() >= 20);
+        assert_eq!(
+            clock
+                .spawn_named("hilo".to_string(), || thread::current()
+                    .name()
+                    .map(String::from))
+                .join()
+                .unwrap(),
+            Some("hilo".to_string())
+        );
+    }
+}
@@ -0,0 +1,523 @@
+use std::fs;
+use std::time::Duration;
+
+use crate::helpers::constants::{
+    A, BARISTAS, C, CANTIDAD_RELLENO, CAPACIDAD_COLA, E, ENVEJECIMIENTO_PRIORIDAD, G, L, M, N,
+    REFILL_COFFEE_TIME, REFILL_MILK_TIME, REFILL_WATER_TIME, SERVE_COCOA_TIME, SERVE_COFFEE_TIME,
+    SERVE_HOT_WATER_TIME, SERVE_MILK_FOAM_TIME, TAKE_ORDER_TIME, TIME_TO_STATS, VARIACION_TIEMPOS,
+    X,
+};
+use crate::helpers::error::CustomError;
+use crate::helpers::random;
+use crate::structs::order::OrderFormat;
+
+/// Formato en el que se imprimen las estadísticas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsFormat {
+    /// Bloque de texto legible.
+    Text,
+    /// Un objeto JSON por línea.
+    Json,
+    /// Una fila CSV por impresión, con encabezado en la primera.
+    Csv,
+}
+
+impl StatsFormat {
+    /// Interpreta el nombre de un formato (`text`, `json` o `csv`).
+    pub fn parse(name: &str) -> Option<StatsFormat> {
+        match name.to_lowercase().as_str() {
+            "text" => Some(StatsFormat::Text),
+            "json" => Some(StatsFormat::Json),
+            "csv" => Some(StatsFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Qué hacer con un pedido nuevo cuando la cola de pedidos está llena.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueuePolicy {
+    /// Se deja de tomar pedidos hasta que un barista libere un lugar.
+    Block,
+    /// Se rechaza el pedido con [`CustomError::QueueFull`].
+    Reject,
+}
+
+impl QueuePolicy {
+    /// Interpreta el nombre de una política (`block` o `reject`).
+    pub fn parse(name: &str) -> Option<QueuePolicy> {
+        match name.to_lowercase().as_str() {
+            "block" => Some(QueuePolicy::Block),
+            "reject" => Some(QueuePolicy::Reject),
+            _ => None,
+        }
+    }
+}
+
+/// Orden en que los pedidos que esperan un contenedor toman sus dispensadores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispenserPolicy {
+    /// Primero los más urgentes según su prioridad y lo que llevan esperando.
+    Priority,
+    /// En el orden en que los pedidos llegaron al contenedor, sin importar su prioridad.
+    Fifo,
+}
+
+impl DispenserPolicy {
+    /// Interpreta el nombre de una política (`priority` o `fifo`).
+    pub fn parse(name: &str) -> Option<DispenserPolicy> {
+        match name.to_lowercase().as_str() {
+            "priority" => Some(DispenserPolicy::Priority),
+            "fifo" => Some(DispenserPolicy::Fifo),
+            _ => None,
+        }
+    }
+}
+
+/// Parámetros del modelo de cafetera que se simula.
+/// Se leen de un archivo de configuración y los que no se especifiquen toman los valores de [`crate::helpers::constants`].
+///
+/// El archivo tiene un formato estilo INI/TOML, una clave por línea:
+/// ```text
+/// # Comentario
+/// [capacidades]
+/// G = 500
+/// M = 100
+/// [tiempos]
+/// SERVE_COFFEE_TIME = 5000
+/// ```
+/// Las claves son los nombres de las constantes (sin importar mayúsculas) y las secciones son solo para agrupar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Capacidad de contenedor de granos de café para moler. Clave `G`.
+    pub coffee_grains_capacity: u32,
+    /// Capacidad de contenedor de granos de café molidos. Clave `M`.
+    pub ground_coffee_capacity: u32,
+    /// Capacidad de contenedor de leche fría para hacer espuma. Clave `L`.
+    pub cold_milk_capacity: u32,
+    /// Capacidad de contenedor de espuma de leche. Clave `E`.
+    pub milk_foam_capacity: u32,
+    /// Capacidad de contenedor de cacao. Clave `C`.
+    pub cocoa_capacity: u32,
+    /// Capacidad de contenedor de agua. Clave `A`.
+    pub hot_water_capacity: u32,
+    /// Cantidad de dispensadores por ingrediente. Clave `N`.
+    pub dispensers: u32,
+    /// Porcentaje de capacidad de los contenedores donde se debe alertar. Clave `X`.
+    pub alert_threshold: u32,
+    /// Cantidad de ingredientes en contenedor para que deba ser rellenado. Clave `CANTIDAD_RELLENO`.
+    pub refill_threshold: u32,
+    /// Tiempo en milisegundos para que se impriman las estadísticas. Clave `TIME_TO_STATS`.
+    pub time_to_stats: u64,
+    /// Tiempo de acción de recibir pedido. Clave `TAKE_ORDER_TIME`.
+    pub take_order_time: u64,
+    /// Tiempo de acción de servir cafe. Clave `SERVE_COFFEE_TIME`.
+    pub serve_coffee_time: u64,
+    /// Tiempo de acción de servir espuma de leche. Clave `SERVE_MILK_FOAM_TIME`.
+    pub serve_milk_foam_time: u64,
+    /// Tiempo de acción de servir agua caliente. Clave `SERVE_HOT_WATER_TIME`.
+    pub serve_hot_water_time: u64,
+    /// Tiempo de acción de servir cacao. Clave `SERVE_COCOA_TIME`.
+    pub serve_cocoa_time: u64,
+    /// Tiempo de acción del molinillo automatico de granos. Clave `REFILL_COFFEE_TIME`.
+    pub refill_coffee_time: u64,
+    /// Tiempo de acción de calentar leche. Clave `REFILL_MILK_TIME`.
+    pub refill_milk_time: u64,
+    /// Tiempo de acción de tomar agua de red y calentarla. Clave `REFILL_WATER_TIME`.
+    pub refill_water_time: u64,
+    /// Porcentaje máximo de variación aleatoria de los tiempos de acción. Clave `VARIACION_TIEMPOS`.
+    pub time_variation: u32,
+    /// Semilla de las variaciones de tiempo, para poder repetir una corrida. Clave `SEED`.
+    pub seed: u64,
+    /// Formato en el que se imprimen las estadísticas. Clave `STATS_FORMAT`.
+    pub stats_format: StatsFormat,
+    /// Cantidad de baristas que preparan pedidos en simultáneo. Clave `BARISTAS`.
+    pub baristas: u32,
+    /// Cantidad máxima de pedidos esperando a un barista. Clave `CAPACIDAD_COLA`.
+    pub queue_capacity: u32,
+    /// Qué hacer con los pedidos nuevos cuando la cola está llena. Clave `POLITICA_COLA`.
+    pub queue_policy: QueuePolicy,
+    /// Milisegundos de espera que equivalen a subir una clase de prioridad. Clave `ENVEJECIMIENTO_PRIORIDAD`.
+    pub aging_time: u64,
+    /// Orden en que se atienden los pedidos que esperan un dispensador de café. Clave `ATENCION_CAFE`.
+    pub coffee_dispenser_policy: DispenserPolicy,
+    /// Orden en que se atienden los pedidos que esperan un dispensador de agua caliente. Clave `ATENCION_AGUA`.
+    pub hot_water_dispenser_policy: DispenserPolicy,
+    /// Orden en que se atienden los pedidos que esperan un dispensador de cacao. Clave `ATENCION_CACAO`.
+    pub cocoa_dispenser_policy: DispenserPolicy,
+    /// Orden en que se atienden los pedidos que esperan un dispensador de espuma de leche. Clave `ATENCION_LECHE`.
+    pub milk_dispenser_policy: DispenserPolicy,
+    /// Formato de los archivos de pedidos. Si no se indica se deduce de la extensión. Clave `FORMATO_PEDIDOS`.
+    pub order_format: Option<OrderFormat>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            coffee_grains_capacity: G,
+            ground_coffee_capacity: M,
+            cold_milk_capacity: L,
+            milk_foam_capacity: E,
+            cocoa_capacity: C,
+            hot_water_capacity: A,
+            dispensers: N,
+            alert_threshold: X,
+            refill_threshold: CANTIDAD_RELLENO,
+            time_to_stats: TIME_TO_STATS,
+            take_order_time: TAKE_ORDER_TIME,
+            serve_coffee_time: SERVE_COFFEE_TIME,
+            serve_milk_foam_time: SERVE_MILK_FOAM_TIME,
+            serve_hot_water_time: SERVE_HOT_WATER_TIME,
+            serve_cocoa_time: SERVE_COCOA_TIME,
+            refill_coffee_time: REFILL_COFFEE_TIME,
+            refill_milk_time: REFILL_MILK_TIME,
+            refill_water_time: REFILL_WATER_TIME,
+            time_variation: VARIACION_TIEMPOS,
+            seed: 0,
+            stats_format: StatsFormat::Text,
+            baristas: BARISTAS,
+            queue_capacity: CAPACIDAD_COLA,
+            queue_policy: QueuePolicy::Block,
+            aging_time: ENVEJECIMIENTO_PRIORIDAD,
+            coffee_dispenser_policy: DispenserPolicy::Priority,
+            hot_water_dispenser_policy: DispenserPolicy::Priority,
+            cocoa_dispenser_policy: DispenserPolicy::Priority,
+            milk_dispenser_policy: DispenserPolicy::Priority,
+            order_format: None,
+        }
+    }
+}
+
+impl Config {
+    /// Lee la configuración del archivo indicado.
+    /// Errores:
+    /// * Si no se puede leer el archivo devuelve [`CustomError::CantOpenConfigFile`]
+    /// * Si alguna línea o valor es inválido devuelve [`CustomError::InvalidConfigValue`]
+    pub fn from_file(path: &str) -> Result<Config, CustomError> {
+        match fs::read_to_string(path) {
+            Ok(content) => Config::parse(&content),
+            Err(e) => {
+                error!(
+                    "No se pudo abrir el archivo de configuración {:?}: {:?}",
+                    path, e
+                );
+                Err(CustomError::CantOpenConfigFile)
+            }
+        }
+    }
+
+    /// Interpreta el contenido de un archivo de configuración partiendo de los valores por defecto.
+    /// Si alguna línea es inválida devuelve [`CustomError::InvalidConfigValue`]
+    pub fn parse(content: &str) -> Result<Config, CustomError> {
+        let mut config = Config::default();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => {
+                    error!(
+                        "Configuración inválida en línea {:?}: se esperaba <clave> = <valor>.",
+                        index + 1
+                    );
+                    return Err(CustomError::InvalidConfigValue);
+                }
+            };
+            if let Err(e) = config.set(key, value) {
+                error!("Configuración inválida en línea {:?}: {}", index + 1, e);
+                return Err(CustomError::InvalidConfigValue);
+            }
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Asigna el valor a la clave correspondiente.
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = value.trim_matches('"');
+        if key.eq_ignore_ascii_case("STATS_FORMAT") {
+            self.stats_format = StatsFormat::parse(value)
+                .ok_or(format!("Formato de estadísticas inválido: {:?}.", value))?;
+            return Ok(());
+        }
+        if key.eq_ignore_ascii_case("FORMATO_PEDIDOS") {
+            self.order_format = Some(
+                OrderFormat::parse(value)
+                    .ok_or(format!("Formato de pedidos inválido: {:?}.", value))?,
+            );
+            return Ok(());
+        }
+        if key.eq_ignore_ascii_case("POLITICA_COLA") {
+            self.queue_policy = QueuePolicy::parse(value)
+                .ok_or(format!("Política de cola inválida: {:?}.", value))?;
+            return Ok(());
+        }
+        let dispenser_policy = match key.to_uppercase().as_str() {
+            "ATENCION_CAFE" => Some(&mut self.coffee_dispenser_policy),
+            "ATENCION_AGUA" => Some(&mut self.hot_water_dispenser_policy),
+            "ATENCION_CACAO" => Some(&mut self.cocoa_dispenser_policy),
+            "ATENCION_LECHE" => Some(&mut self.milk_dispenser_policy),
+            _ => None,
+        };
+        if let Some(dispenser_policy) = dispenser_policy {
+            *dispenser_policy = DispenserPolicy::parse(value)
+                .ok_or(format!("Política de dispensadores inválida: {:?}.", value))?;
+            return Ok(());
+        }
+        let number: u64 = value
+            .parse()
+            .map_err(|_| format!("Valor inválido para {}: {:?}.", key, value))?;
+        let small_number =
+            || u32::try_from(number).map_err(|_| format!("Valor demasiado grande para {}.", key));
+        match key.to_uppercase().as_str() {
+            "G" => self.coffee_grains_capacity = small_number()?,
+            "M" => self.ground_coffee_capacity = small_number()?,
+            "L" => self.cold_milk_capacity = small_number()?,
+            "E" => self.milk_foam_capacity = small_number()?,
+            "C" => self.cocoa_capacity = small_number()?,
+            "A" => self.hot_water_capacity = small_number()?,
+            "N" => self.dispensers = small_number()?,
+            "X" => self.alert_threshold = small_number()?,
+            "CANTIDAD_RELLENO" => self.refill_threshold = small_number()?,
+            "TIME_TO_STATS" => self.time_to_stats = number,
+            "TAKE_ORDER_TIME" => self.take_order_time = number,
+            "SERVE_COFFEE_TIME" => self.serve_coffee_time = number,
+            "SERVE_MILK_FOAM_TIME" => self.serve_milk_foam_time = number,
+            "SERVE_HOT_WATER_TIME" => self.serve_hot_water_time = number,
+            "SERVE_COCOA_TIME" => self.serve_cocoa_time = number,
+            "REFILL_COFFEE_TIME" => self.refill_coffee_time = number,
+            "REFILL_MILK_TIME" => self.refill_milk_time = number,
+            "REFILL_WATER_TIME" => self.refill_water_time = number,
+            "VARIACION_TIEMPOS" => self.time_variation = small_number()?,
+            "SEED" => self.seed = number,
+            "BARISTAS" => self.baristas = small_number()?,
+            "CAPACIDAD_COLA" => self.queue_capacity = small_number()?,
+            "ENVEJECIMIENTO_PRIORIDAD" => self.aging_time = number,
+            _ => return Err(format!("Clave desconocida {:?}.", key)),
+        }
+        Ok(())
+    }
+
+    /// Verifica que los valores permitan que la cafetera funcione.
+    /// Si alguno no lo permite devuelve [`CustomError::InvalidConfigValue`]
+    pub fn validate(&self) -> Result<(), CustomError> {
+        let error = if self.coffee_grains_capacity == 0
+            || self.ground_coffee_capacity == 0
+            || self.cold_milk_capacity == 0
+            || self.milk_foam_capacity == 0
+            || self.cocoa_capacity == 0
+            || self.hot_water_capacity == 0
+        {
+            Some("Las capacidades de los contenedores deben ser mayores a cero.")
+        } else if self.dispensers == 0 {
+            Some("Debe haber al menos un dispensador por ingrediente.")
+        } else if self.alert_threshold > 100 {
+            Some("El porcentaje de alerta X debe estar entre 0 y 100.")
+        } else if self.time_variation > 100 {
+            Some("VARIACION_TIEMPOS debe estar entre 0 y 100.")
+        } else if self.refill_threshold >= self.ground_coffee_capacity
+            || self.refill_threshold >= self.milk_foam_capacity
+            || self.refill_threshold >= self.hot_water_capacity
+        {
+            Some("CANTIDAD_RELLENO debe ser menor a las capacidades de M, E y A.")
+        } else if self.time_to_stats == 0 {
+            Some("TIME_TO_STATS debe ser mayor a cero.")
+        } else if self.baristas == 0 {
+            Some("Debe haber al menos un barista.")
+        } else if self.queue_capacity == 0 {
+            Some("CAPACIDAD_COLA debe ser mayor a cero.")
+        } else if self.aging_time == 0 {
+            Some("ENVEJECIMIENTO_PRIORIDAD debe ser mayor a cero.")
+        } else {
+            None
+        };
+        match error {
+            Some(e) => {
+                error!("Configuración inválida: {}", e);
+                Err(CustomError::InvalidConfigValue)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Atiende a los pedidos que esperan cualquier contenedor en orden de llegada, ver [`DispenserPolicy::Fifo`].
+    pub fn set_fifo_dispensers(&mut self) {
+        self.coffee_dispenser_policy = DispenserPolicy::Fifo;
+        self.hot_water_dispenser_policy = DispenserPolicy::Fifo;
+        self.cocoa_dispenser_policy = DispenserPolicy::Fifo;
+        self.milk_dispenser_policy = DispenserPolicy::Fifo;
+    }
+
+    /// Multiplica todos los tiempos de acción y de estadísticas por el factor.
+    /// Un factor de `0.1` hace que la simulación corra 10 veces más rápido.
+    pub fn scale_times(&mut self, factor: f64) {
+        let scale = |time: u64| (time as f64 * factor).round() as u64;
+        self.time_to_stats = scale(self.time_to_stats).max(1);
+        self.take_order_time = scale(self.take_order_time);
+        self.serve_coffee_time = scale(self.serve_coffee_time);
+        self.serve_milk_foam_time = scale(self.serve_milk_foam_time);
+        self.serve_hot_water_time = scale(self.serve_hot_water_time);
+        self.serve_cocoa_time = scale(self.serve_cocoa_time);
+        self.refill_coffee_time = scale(self.refill_coffee_time);
+        self.refill_milk_time = scale(self.refill_milk_time);
+        self.refill_water_time = scale(self.refill_water_time);
+        self.aging_time = scale(self.aging_time).max(1);
+    }
+
+    /// Duración de una acción aplicandole la variación aleatoria de [`Config::time_variation`].
+    /// `action` e `id` identifican la acción (por ejemplo servir café del pedido 3) para que
+    /// con la misma semilla siempre tarde lo mismo.
+    pub fn action_time(&self, time: u64, action: &str, id: u64) -> Duration {
+        Duration::from_millis(random::vary(
+            time,
+            self.time_variation,
+            self.seed,
+            action,
+            id,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_uses_constants() {
+        let config = Config::default();
+        assert_eq!(config.coffee_grains_capacity, G);
+        assert_eq!(config.ground_coffee_capacity, M);
+        assert_eq!(config.hot_water_capacity, A);
+        assert_eq!(config.dispensers, N);
+        assert_eq!(config.refill_threshold, CANTIDAD_RELLENO);
+        assert_eq!(config.serve_coffee_time, SERVE_COFFEE_TIME);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let content =
+            "# Cafetera chica\n[capacidades]\nM = 50\nn=1\n\n[tiempos]\nSERVE_COFFEE_TIME = 10\n";
+        let config = Config::parse(content).unwrap();
+        assert_eq!(config.ground_coffee_capacity, 50);
+        assert_eq!(config.dispensers, 1);
+        assert_eq!(config.serve_coffee_time, 10);
+        assert_eq!(config.coffee_grains_capacity, G);
+    }
+
+    #[test]
+    fn test_parse_config_invalid_lines() {
+        assert_eq!(
+            Config::parse("M = mucho"),
+            Err(CustomError::InvalidConfigValue)
+        );
+        assert_eq!(
+            Config::parse("DESCONOCIDA = 1"),
+            Err(CustomError::InvalidConfigValue)
+        );
+        assert_eq!(Config::parse("M 10"), Err(CustomError::InvalidConfigValue));
+    }
+
+    #[test]
+    fn test_parse_config_invalid_values() {
+        assert_eq!(Config::parse("N = 0"), Err(CustomError::InvalidConfigValue));
+        assert_eq!(
+            Config::parse("X = 101"),
+            Err(CustomError::InvalidConfigValue)
+        );
+        assert_eq!(
+            Config::parse("CANTIDAD_RELLENO = 100"),
+            Err(CustomError::InvalidConfigValue)
+        );
+    }
+
+    #[test]
+    fn test_parse_stats_format_and_seed() {
+        let config =
+            Config::parse("STATS_FORMAT = json\nSEED = 7\nVARIACION_TIEMPOS = 10").unwrap();
+        assert_eq!(config.stats_format, StatsFormat::Json);
+        assert_eq!(config.seed, 7);
+        assert_eq!(config.time_variation, 10);
+        assert_eq!(
+            Config::parse("STATS_FORMAT = xml"),
+            Err(CustomError::InvalidConfigValue)
+        );
+    }
+
+    #[test]
+    fn test_parse_worker_pool() {
+        let config = Config::parse(
+            "BARISTAS = 2\nCAPACIDAD_COLA = 4\nPOLITICA_COLA = reject\nFORMATO_PEDIDOS = jsonl",
+        )
+        .unwrap();
+        assert_eq!(config.order_format, Some(OrderFormat::Jsonl));
+        assert_eq!(config.baristas, 2);
+        assert_eq!(config.queue_capacity, 4);
+        assert_eq!(config.queue_policy, QueuePolicy::Reject);
+        assert_eq!(
+            Config::parse("BARISTAS = 0"),
+            Err(CustomError::InvalidConfigValue)
+        );
+        assert_eq!(
+            Config::parse("POLITICA_COLA = esperar"),
+            Err(CustomError::InvalidConfigValue)
+        );
+    }
+
+    #[test]
+    fn test_parse_dispenser_policies() {
+        let config = Config::parse("ATENCION_CAFE = fifo\natencion_leche = FIFO").unwrap();
+        assert_eq!(config.coffee_dispenser_policy, DispenserPolicy::Fifo);
+        assert_eq!(config.hot_water_dispenser_policy, DispenserPolicy::Priority);
+        assert_eq!(config.cocoa_dispenser_policy, DispenserPolicy::Priority);
+        assert_eq!(config.milk_dispenser_policy, DispenserPolicy::Fifo);
+        assert_eq!(
+            Config::parse("ATENCION_AGUA = turnos"),
+            Err(CustomError::InvalidConfigValue)
+        );
+
+        let mut config = Config::default();
+        config.set_fifo_dispensers();
+        assert_eq!(config.cocoa_dispenser_policy, DispenserPolicy::Fifo);
+    }
+
+    #[test]
+    fn test_parse_aging_time() {
+        assert_eq!(Config::default().aging_time, ENVEJECIMIENTO_PRIORIDAD);
+        assert_eq!(
+            Config::parse("ENVEJECIMIENTO_PRIORIDAD = 2000")
+                .unwrap()
+                .aging_time,
+            2000
+        );
+        assert_eq!(
+            Config::parse("ENVEJECIMIENTO_PRIORIDAD = 0"),
+            Err(CustomError::InvalidConfigValue)
+        );
+    }
+
+    #[test]
+    fn test_scale_times() {
+        let mut config = Config::default();
+        config.scale_times(0.5);
+        assert_eq!(config.serve_coffee_time, SERVE_COFFEE_TIME / 2);
+        assert_eq!(config.time_to_stats, TIME_TO_STATS / 2);
+        assert_eq!(config.ground_coffee_capacity, M);
+    }
+
+    #[test]
+    fn test_from_file() {
+        let config = Config::from_file("src/tests/config.ini").unwrap();
+        assert_eq!(config.dispensers, 2);
+        assert_eq!(config.serve_coffee_time, 100);
+        assert_eq!(
+            Config::from_file("src/tests/invalid.ini"),
+            Err(CustomError::CantOpenConfigFile)
+        );
+    }
+}
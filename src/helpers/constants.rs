@@ -11,12 +11,24 @@ pub const C: u32 = 100;
 /// Capacidad de contenedor de agua
 pub const A: u32 = 100;
 
+/// Cantidad de dispensadores por ingrediente que pueden servir en simultáneo
+pub const N: u32 = 3;
+
 /// Capacidad de contenedores de granosde café, leche y cacao donde se debe alertar
 pub const X: u32 = 25;
 /// Tiempo en milisengudos para que se impriman las estadistincas
 pub const TIME_TO_STATS: u64 = 5000;
 /// Cantidad de ingredientes en contenedor para que deba ser rellenado.
 pub const CANTIDAD_RELLENO: u32 = 30;
+/// Porcentaje máximo de variación aleatoria de los tiempos de acción
+pub const VARIACION_TIEMPOS: u32 = 0;
+
+/// Cantidad de baristas que preparan pedidos en simultáneo
+pub const BARISTAS: u32 = 8;
+/// Cantidad máxima de pedidos esperando a un barista
+pub const CAPACIDAD_COLA: u32 = 32;
+/// Milisegundos de espera que equivalen a subir una clase de prioridad
+pub const ENVEJECIMIENTO_PRIORIDAD: u64 = 10000;
 
 /// Tiempo de acción de recibir pedido
 pub const TAKE_ORDER_TIME: u64 = 500;
@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 /// Errores del programa. Necesito el [`allow(dead_code)`] porque el  último error lo estoy utilizando en los tests.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CustomError {
     /// El archivo de pedidos no se pudo abrir.
     CantOpenOrderFile,
@@ -14,10 +14,59 @@ pub enum CustomError {
     InvalidShutDown,
     /// No hay suficiente cantidad de algún ingrediente para satisfacer un pedido.
     InsufficientIngredients,
+    /// El pedido pide más de algún ingrediente que lo que entra en su contenedor, por lo que nunca podría servirse.
+    OrderExceedsCapacity,
+    /// La cola de pedidos está llena y la política es rechazar los pedidos nuevos.
+    QueueFull,
+    /// No se pudo abrir el puerto del servidor de pedidos.
+    CantStartServer,
+    /// El archivo de configuración no se pudo abrir.
+    CantOpenConfigFile,
+    /// Algún valor del archivo de configuración es inválido.
+    InvalidConfigValue,
+    /// Los argumentos de la línea de comandos son inválidos.
+    InvalidArguments,
+    /// El archivo de menú no se pudo abrir.
+    CantOpenMenuFile,
+    /// Alguna bebida del archivo de menú es inválida.
+    InvalidMenuValue,
+    /// El archivo de estadísticas no se pudo crear.
+    CantOpenStatsFile,
+    /// El archivo de eventos no se pudo crear.
+    CantOpenEventsFile,
+    /// El archivo de la línea de tiempo no se pudo crear.
+    CantOpenTraceFile,
     /// Fallo el test por un error de ejecucion.
     TestFailing,
 }
 
+impl CustomError {
+    /// Código de salida del programa cuando termina con este error.
+    /// El `1` queda para errores no identificados y el `2` para el uso incorrecto de la línea de comandos.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            CustomError::TestFailing => 1,
+            CustomError::InvalidArguments => 2,
+            CustomError::InvalidOrderFilePath => 3,
+            CustomError::CantOpenOrderFile => 4,
+            CustomError::InvalidOrderValue => 5,
+            CustomError::CantOpenConfigFile => 6,
+            CustomError::InvalidConfigValue => 7,
+            CustomError::InsufficientIngredients => 8,
+            CustomError::PoisonedLock => 9,
+            CustomError::InvalidShutDown => 10,
+            CustomError::CantOpenMenuFile => 11,
+            CustomError::InvalidMenuValue => 12,
+            CustomError::OrderExceedsCapacity => 13,
+            CustomError::QueueFull => 14,
+            CustomError::CantStartServer => 15,
+            CustomError::CantOpenStatsFile => 16,
+            CustomError::CantOpenEventsFile => 17,
+            CustomError::CantOpenTraceFile => 18,
+        }
+    }
+}
+
 impl From<std::num::ParseIntError> for CustomError {
     fn from(_: std::num::ParseIntError) -> Self {
         CustomError::InvalidOrderValue
@@ -2,19 +2,50 @@ use std::fs::File;
 use std::io::{self, BufRead};
 use std::path::Path;
 
+/// Path con el que se indica que las líneas se leen de la entrada estándar.
+pub const STDIN_PATH: &str = "-";
+
 /// Obtuve este método eficiente para leer líneas de https://doc.rust-lang.org/rust-by-example/std_misc/file/read_lines.html
 /// The output is wrapped in a Result to allow matching on errors
 /// Returns an Iterator to the Reader of the lines of the file.
-pub fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
+///
+/// Si el path es [`STDIN_PATH`] lee la entrada estándar. El path también puede ser un pipe con nombre (FIFO):
+/// en ambos casos cada línea se devuelve apenas llega y el iterador termina cuando se cierra la escritura.
+pub fn read_lines<P>(filename: P) -> io::Result<io::Lines<Box<dyn BufRead + Send>>>
 where
     P: AsRef<Path>,
 {
+    if filename.as_ref() == Path::new(STDIN_PATH) {
+        let reader: Box<dyn BufRead + Send> = Box::new(io::BufReader::new(io::stdin()));
+        return Ok(reader.lines());
+    }
     match File::open(filename) {
-        Ok(file) => Ok(io::BufReader::new(file).lines()),
+        Ok(file) => {
+            let reader: Box<dyn BufRead + Send> = Box::new(io::BufReader::new(file));
+            Ok(reader.lines())
+        }
         Err(error) => Err(error),
     }
 }
 
+/// Indica si el path es la entrada estándar o un pipe con nombre, de los que las líneas llegan a medida que se escriben.
+pub fn is_stream<P>(filename: P) -> bool
+where
+    P: AsRef<Path>,
+{
+    if filename.as_ref() == Path::new(STDIN_PATH) {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if let Ok(metadata) = std::fs::metadata(filename) {
+            return metadata.file_type().is_fifo();
+        }
+    }
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -43,4 +74,42 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_read_lines_from_fifo() {
+        use std::io::Write;
+
+        let path = std::env::temp_dir().join(format!("coffee-gpt-fifo-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let created = std::process::Command::new("mkfifo").arg(&path).status();
+        assert!(created.map(|status| status.success()).unwrap_or(false));
+
+        let writer_path = path.clone();
+        let writer = std::thread::spawn(move || {
+            let mut fifo = std::fs::OpenOptions::new()
+                .write(true)
+                .open(writer_path)
+                .unwrap();
+            writeln!(fifo, "1,1,1,1").unwrap();
+            fifo.flush().unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            writeln!(fifo, "2,2,2,2").unwrap();
+        });
+        let lines: Vec<String> = read_lines(&path)
+            .unwrap()
+            .map(|line| line.unwrap())
+            .collect();
+        writer.join().unwrap();
+        assert!(is_stream(&path));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(lines, vec!["1,1,1,1", "2,2,2,2"]);
+    }
+
+    #[test]
+    fn test_is_stream() {
+        assert!(is_stream(STDIN_PATH));
+        assert!(!is_stream("src/tests/two_equal_orders.txt"));
+        assert!(!is_stream("src/tests/no_existe.txt"));
+    }
 }
@@ -0,0 +1,272 @@
+/// Valor de un campo de un objeto JSON plano.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    /// Número tal como está escrito, para poder interpretarlo como entero sin perder precisión.
+    Number(String),
+    String(String),
+}
+
+impl JsonValue {
+    /// Describe el valor como texto: los strings sin comillas y el resto como están escritos.
+    pub fn as_text(&self) -> String {
+        match self {
+            JsonValue::Null => "null".to_string(),
+            JsonValue::Bool(value) => value.to_string(),
+            JsonValue::Number(number) => number.clone(),
+            JsonValue::String(text) => text.clone(),
+        }
+    }
+}
+
+/// Interpreta una línea con un objeto JSON plano, sin objetos ni arreglos anidados, como los de un archivo JSON Lines:
+/// ```text
+/// {"id":"A12","customer":"Ana","coffee":10}
+/// ```
+/// Devuelve los campos en el orden en que aparecen, o el motivo por el que la línea es inválida.
+pub fn parse_object(line: &str) -> Result<Vec<(String, JsonValue)>, String> {
+    let mut parser = Parser {
+        chars: line.chars().collect(),
+        position: 0,
+    };
+    let mut fields: Vec<(String, JsonValue)> = Vec::new();
+    parser.expect('{')?;
+    if !parser.consume('}') {
+        loop {
+            let key = parser.string()?;
+            if fields.iter().any(|(existing, _)| *existing == key) {
+                return Err(format!("Campo repetido {:?}.", key));
+            }
+            parser.expect(':')?;
+            let value = parser.value()?;
+            fields.push((key, value));
+            if parser.consume('}') {
+                break;
+            }
+            parser.expect(',')?;
+        }
+    }
+    parser.skip_whitespace();
+    if parser.position < parser.chars.len() {
+        return Err(format!(
+            "Contenido inesperado después del objeto en la posición {}.",
+            parser.position + 1
+        ));
+    }
+    Ok(fields)
+}
+
+/// Recorre los caracteres de la línea.
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while self
+            .chars
+            .get(self.position)
+            .is_some_and(|c| c.is_whitespace())
+        {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.position).copied()
+    }
+
+    /// Avanza si el próximo caracter es el indicado.
+    fn consume(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.consume(expected) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(found) => Err(format!(
+                "Se esperaba {:?} y se encontró {:?} en la posición {}.",
+                expected,
+                found,
+                self.position + 1
+            )),
+            None => Err(format!("Se esperaba {:?} y terminó la línea.", expected)),
+        }
+    }
+
+    fn value(&mut self) -> Result<JsonValue, String> {
+        match self.peek() {
+            Some('"') => Ok(JsonValue::String(self.string()?)),
+            Some('{') | Some('[') => Err("No se admiten objetos ni arreglos anidados.".to_string()),
+            Some(c) if c == '-' || c.is_ascii_digit() => Ok(JsonValue::Number(self.number()?)),
+            Some(_) => {
+                for (word, value) in [
+                    ("true", JsonValue::Bool(true)),
+                    ("false", JsonValue::Bool(false)),
+                    ("null", JsonValue::Null),
+                ] {
+                    if self.chars[self.position..].starts_with(&word.chars().collect::<Vec<_>>()) {
+                        self.position += word.len();
+                        return Ok(value);
+                    }
+                }
+                Err(format!(
+                    "Valor inválido en la posición {}.",
+                    self.position + 1
+                ))
+            }
+            None => Err("Se esperaba un valor y terminó la línea.".to_string()),
+        }
+    }
+
+    fn number(&mut self) -> Result<String, String> {
+        let start = self.position;
+        while self
+            .chars
+            .get(self.position)
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            self.position += 1;
+        }
+        let number: String = self.chars[start..self.position].iter().collect();
+        match number.parse::<f64>() {
+            Ok(_) if !number.starts_with('+') => Ok(number),
+            _ => Err(format!("Número inválido {:?}.", number)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            let c = match self.chars.get(self.position) {
+                Some(c) => *c,
+                None => return Err("Texto sin cerrar.".to_string()),
+            };
+            self.position += 1;
+            match c {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escaped = self.chars.get(self.position).copied();
+                    self.position += 1;
+                    match escaped {
+                        Some('"') => text.push('"'),
+                        Some('\\') => text.push('\\'),
+                        Some('/') => text.push('/'),
+                        Some('b') => text.push('\u{8}'),
+                        Some('f') => text.push('\u{c}'),
+                        Some('n') => text.push('\n'),
+                        Some('r') => text.push('\r'),
+                        Some('t') => text.push('\t'),
+                        Some('u') => text.push(self.unicode_escape()?),
+                        _ => {
+                            return Err(format!(
+                                "Escape inválido en la posición {}.",
+                                self.position
+                            ))
+                        }
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+    }
+
+    /// Interpreta los 4 dígitos hexadecimales de un `\u`. Los pares sustitutos se reemplazan por `�`.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let digits: String = self
+            .chars
+            .get(self.position..self.position + 4)
+            .unwrap_or_default()
+            .iter()
+            .collect();
+        let code = u32::from_str_radix(&digits, 16)
+            .map_err(|_| format!("Escape unicode inválido {:?}.", digits))?;
+        self.position += 4;
+        Ok(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+}
+
+/// Escribe el texto como un string de JSON, entre comillas y con los caracteres especiales escapados.
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("café"), "\"café\"");
+        assert_eq!(quote("a\"b\\c\n"), "\"a\\\"b\\\\c\\n\"");
+        assert_eq!(
+            parse_object(&format!("{{\"texto\":{}}}", quote("x\"y"))),
+            Ok(vec![(
+                "texto".to_string(),
+                JsonValue::String("x\"y".to_string())
+            )])
+        );
+    }
+
+    #[test]
+    fn test_parse_object() {
+        let fields =
+            parse_object(r#" {"id": "A12", "cafe":10, "nota":"sin \"azúcar\"\n", "vip":true, "x":null, "t":-1.5e3} "#)
+                .unwrap();
+        assert_eq!(
+            fields,
+            vec![
+                ("id".to_string(), JsonValue::String("A12".to_string())),
+                ("cafe".to_string(), JsonValue::Number("10".to_string())),
+                (
+                    "nota".to_string(),
+                    JsonValue::String("sin \"azúcar\"\n".to_string())
+                ),
+                ("vip".to_string(), JsonValue::Bool(true)),
+                ("x".to_string(), JsonValue::Null),
+                ("t".to_string(), JsonValue::Number("-1.5e3".to_string())),
+            ]
+        );
+        assert_eq!(parse_object("{}").unwrap(), vec![]);
+        assert_eq!(
+            parse_object(r#"{"a":"á"}"#).unwrap()[0].1,
+            JsonValue::String("á".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_object() {
+        assert!(parse_object("").is_err());
+        assert!(parse_object("10,0,0,0").is_err());
+        assert!(parse_object(r#"{"cafe":10"#).is_err());
+        assert!(parse_object(r#"{"cafe":10,}"#).is_err());
+        assert!(parse_object(r#"{"cafe":10} extra"#).is_err());
+        assert!(parse_object(r#"{"cafe":[10]}"#).is_err());
+        assert!(parse_object(r#"{"cafe":1,"cafe":2}"#).is_err());
+        assert!(parse_object(r#"{"cafe":"10}"#).is_err());
+        assert!(parse_object(r#"{"cafe":verdadero}"#).is_err());
+    }
+}
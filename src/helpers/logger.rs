@@ -0,0 +1,413 @@
+use std::cell::Cell;
+use std::fmt::Arguments;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+use crate::helpers::clock::Clock;
+
+/// Niveles de los mensajes que imprime el programa, de menor a mayor verbosidad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+}
+
+impl LogLevel {
+    /// Interpreta el nombre de un nivel (`error`, `warn`, `info` o `debug`).
+    pub fn parse(name: &str) -> Option<LogLevel> {
+        match name.to_lowercase().as_str() {
+            "error" => Some(LogLevel::Error),
+            "warn" => Some(LogLevel::Warn),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+
+    /// Etiqueta con la que se imprimen los mensajes del nivel.
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN",
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+        }
+    }
+}
+
+/// Partes del programa cuyos mensajes se pueden filtrar por separado.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogModule {
+    /// Contenedor de café: molienda y alertas de granos.
+    Coffee,
+    /// Contenedor de agua caliente.
+    Water,
+    /// Contenedor de cacao.
+    Cocoa,
+    /// Contenedor de leche: espuma y alertas de leche fría.
+    Milk,
+    /// Recepción, preparación y estado de los pedidos.
+    Orders,
+    /// Estadísticas, métricas y resumen de la corrida.
+    Stats,
+    /// Todo lo demás: línea de comandos, configuración y arranque.
+    General,
+}
+
+impl LogModule {
+    /// Todos los módulos, en el orden de [`LogFilter`].
+    pub const ALL: [LogModule; 7] = [
+        LogModule::Coffee,
+        LogModule::Water,
+        LogModule::Cocoa,
+        LogModule::Milk,
+        LogModule::Orders,
+        LogModule::Stats,
+        LogModule::General,
+    ];
+
+    /// Nombre del módulo en los filtros y en los mensajes.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogModule::Coffee => "coffee",
+            LogModule::Water => "water",
+            LogModule::Cocoa => "cocoa",
+            LogModule::Milk => "milk",
+            LogModule::Orders => "orders",
+            LogModule::Stats => "stats",
+            LogModule::General => "general",
+        }
+    }
+
+    /// Interpreta el nombre de un módulo.
+    pub fn parse(name: &str) -> Option<LogModule> {
+        LogModule::ALL
+            .into_iter()
+            .find(|module| module.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Módulo al que pertenece el código de `module_path`, el `module_path!()` desde donde se imprime el mensaje.
+    pub fn from_path(module_path: &str) -> LogModule {
+        match module_path.rsplit("::").next().unwrap_or_default() {
+            "coffee_container" => LogModule::Coffee,
+            "hot_water_container" => LogModule::Water,
+            "cocoa_container" => LogModule::Cocoa,
+            "milk_container" => LogModule::Milk,
+            "coffee_maker" | "order" | "order_queue" | "order_server" | "dispenser_queue"
+            | "menu" => LogModule::Orders,
+            "statistics_values" | "stats_snapshot" | "stats_renderer" | "run_report"
+            | "metrics_server" => LogModule::Stats,
+            _ => LogModule::General,
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// Nivel máximo de los mensajes que se imprimen de cada módulo.
+/// Se escribe como `<nivel>,<modulo>=<nivel>,...`, por ejemplo `warn,orders=debug`: el nivel suelto vale para los
+/// módulos que no se indican. Ver [`LogFilter::parse`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogFilter {
+    levels: [LogLevel; 7],
+}
+
+impl LogFilter {
+    /// Filtro con el mismo nivel para todos los módulos.
+    pub const fn new(level: LogLevel) -> LogFilter {
+        LogFilter { levels: [level; 7] }
+    }
+
+    /// Interpreta un filtro como `info` o `warn,coffee=debug,stats=error`.
+    /// Devuelve `None` si algún módulo o nivel es desconocido.
+    pub fn parse(spec: &str) -> Option<LogFilter> {
+        let directives: Vec<&str> = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|directive| !directive.is_empty())
+            .collect();
+        if directives.is_empty() {
+            return None;
+        }
+        let mut filter = LogFilter::new(LogLevel::Debug);
+        let mut overrides = Vec::new();
+        for directive in directives {
+            match directive.split_once('=') {
+                Some((module, level)) => overrides.push((
+                    LogModule::parse(module.trim())?,
+                    LogLevel::parse(level.trim())?,
+                )),
+                None => filter = LogFilter::new(LogLevel::parse(directive)?),
+            }
+        }
+        for (module, level) in overrides {
+            filter.levels[module.index()] = level;
+        }
+        Some(filter)
+    }
+
+    /// Nivel máximo de los mensajes del módulo.
+    pub fn level(&self, module: LogModule) -> LogLevel {
+        self.levels[module.index()]
+    }
+}
+
+impl Default for LogFilter {
+    /// Por defecto se imprime todo.
+    fn default() -> Self {
+        LogFilter::new(LogLevel::Debug)
+    }
+}
+
+/// Variable de entorno con el filtro de mensajes, si no se indica `--log-level`.
+pub const LOG_ENV: &str = "COFFEE_GPT_LOG";
+
+/// Filtro con el que se imprimen los mensajes.
+static FILTER: Mutex<LogFilter> = Mutex::new(LogFilter::new(LogLevel::Debug));
+
+/// Flag que indica que los mensajes se imprimen por la salida de error en lugar de la estándar.
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Reloj del que se toma el momento de cada mensaje. Si no se indicó ninguno se usa uno real, desde el primer mensaje.
+static CLOCK: Mutex<Option<Clock>> = Mutex::new(None);
+
+thread_local! {
+    /// Pedido que está procesando el hilo, ver [`order_scope`].
+    static CURRENT_ORDER: Cell<Option<u32>> = const { Cell::new(None) };
+}
+
+/// Cambia el filtro de los mensajes que se imprimen.
+pub fn set_filter(filter: LogFilter) {
+    *FILTER.lock().unwrap_or_else(PoisonError::into_inner) = filter;
+}
+
+/// Imprime los mensajes por la salida de error, por ejemplo para que no se mezclen con el tablero que se dibuja en la estándar.
+pub fn use_stderr() {
+    TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+/// Indica el reloj con el que se marca el momento de cada mensaje, el mismo que usa la cafetera.
+pub fn set_clock(clock: Clock) {
+    *CLOCK.lock().unwrap_or_else(PoisonError::into_inner) = Some(clock);
+}
+
+/// Indica si los mensajes del nivel y módulo deben imprimirse.
+pub fn enabled(module: LogModule, level: LogLevel) -> bool {
+    level
+        <= FILTER
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .level(module)
+}
+
+/// Momento actual en milisegundos del reloj de los mensajes.
+fn now() -> u64 {
+    CLOCK
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get_or_insert_with(Clock::real)
+        .now()
+}
+
+/// Nombre del hilo actual, o su identificador si no tiene nombre.
+pub fn thread_name() -> String {
+    let current = thread::current();
+    match current.name() {
+        Some(name) => name.to_string(),
+        None => format!("{:?}", current.id()),
+    }
+}
+
+/// Marca al hilo actual como procesando el pedido `id` hasta que se descarte el [`OrderScope`] devuelto,
+/// así los mensajes que imprima mientras tanto indican el pedido.
+pub fn order_scope(id: u32) -> OrderScope {
+    OrderScope {
+        previous: CURRENT_ORDER.with(|current| current.replace(Some(id))),
+    }
+}
+
+/// Pedido que está procesando el hilo actual, si está dentro de un [`order_scope`].
+pub fn current_order() -> Option<u32> {
+    CURRENT_ORDER.with(Cell::get)
+}
+
+/// Ver [`order_scope`]. Al descartarse vuelve al pedido que tenía el hilo antes.
+pub struct OrderScope {
+    previous: Option<u32>,
+}
+
+impl Drop for OrderScope {
+    fn drop(&mut self) {
+        CURRENT_ORDER.with(|current| current.set(self.previous));
+    }
+}
+
+/// Un mensaje con su contexto: momento del reloj, nivel, módulo, hilo y pedido.
+pub struct LogRecord<'a> {
+    pub time: u64,
+    pub level: LogLevel,
+    pub module: LogModule,
+    pub thread: &'a str,
+    pub order: Option<u32>,
+    pub message: &'a str,
+}
+
+impl LogRecord<'_> {
+    /// Línea con el formato `[<tiempo> ms] [NIVEL] [modulo] [hilo] [pedido <id>] mensaje`.
+    /// El pedido solo aparece si el mensaje corresponde a uno.
+    pub fn format(&self) -> String {
+        let order = match self.order {
+            Some(id) => format!(" [pedido {}]", id),
+            None => String::new(),
+        };
+        format!(
+            "[{:>6} ms] [{}] [{}] [{}]{} {}",
+            self.time,
+            self.level.label(),
+            self.module.name(),
+            self.thread,
+            order,
+            self.message
+        )
+    }
+}
+
+/// Imprime el mensaje si su nivel está habilitado para su módulo, por la salida estándar o, después de [`use_stderr`], por la de error.
+pub fn write(record: &LogRecord) {
+    if !enabled(record.module, record.level) {
+        return;
+    }
+    if TO_STDERR.load(Ordering::Relaxed) {
+        eprintln!("{}", record.format());
+    } else {
+        println!("{}", record.format());
+    }
+}
+
+/// Imprime el mensaje con el momento actual, el hilo actual y el pedido que está procesando, si su nivel está habilitado.
+/// Se usa a través de las macros `error!`, `warn!`, `info!` y `debug!`, disponibles en todo el crate,
+/// que indican el módulo con `module_path!()` (ver [`LogModule::from_path`]).
+pub fn log(module_path: &str, level: LogLevel, message: Arguments) {
+    let module = LogModule::from_path(module_path);
+    if !enabled(module, level) {
+        return;
+    }
+    write(&LogRecord {
+        time: now(),
+        level,
+        module,
+        thread: &thread_name(),
+        order: current_order(),
+        message: &message.to_string(),
+    });
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::helpers::logger::log(
+            module_path!(),
+            $crate::helpers::logger::LogLevel::Error,
+            format_args!($($arg)*),
+        )
+    };
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::helpers::logger::log(
+            module_path!(),
+            $crate::helpers::logger::LogLevel::Warn,
+            format_args!($($arg)*),
+        )
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::helpers::logger::log(
+            module_path!(),
+            $crate::helpers::logger::LogLevel::Info,
+            format_args!($($arg)*),
+        )
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::helpers::logger::log(
+            module_path!(),
+            $crate::helpers::logger::LogLevel::Debug,
+            format_args!($($arg)*),
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_level() {
+        assert_eq!(LogLevel::parse("debug"), Some(LogLevel::Debug));
+        assert_eq!(LogLevel::parse("WARN"), Some(LogLevel::Warn));
+        assert_eq!(LogLevel::parse("verbose"), None);
+        assert!(LogLevel::Error < LogLevel::Debug);
+    }
+
+    #[test]
+    fn test_parse_log_filter() {
+        let filter = LogFilter::parse("warn, orders=debug,STATS=error").unwrap();
+        assert_eq!(filter.level(LogModule::Orders), LogLevel::Debug);
+        assert_eq!(filter.level(LogModule::Stats), LogLevel::Error);
+        assert_eq!(filter.level(LogModule::Milk), LogLevel::Warn);
+        // El nivel suelto no pisa los módulos indicados antes.
+        let filter = LogFilter::parse("coffee=info,error").unwrap();
+        assert_eq!(filter.level(LogModule::Coffee), LogLevel::Info);
+        assert_eq!(filter.level(LogModule::General), LogLevel::Error);
+        assert_eq!(LogFilter::parse("milk=debug"), Some(LogFilter::default()));
+        assert_eq!(LogFilter::parse("tea=debug"), None);
+        assert_eq!(LogFilter::parse("orders=verbose"), None);
+        assert_eq!(LogFilter::parse(""), None);
+    }
+
+    #[test]
+    fn test_log_module_from_path() {
+        assert_eq!(
+            LogModule::from_path("coffee_gpt::structs::milk_container"),
+            LogModule::Milk
+        );
+        assert_eq!(
+            LogModule::from_path("coffee_gpt::structs::coffee_maker"),
+            LogModule::Orders
+        );
+        assert_eq!(LogModule::from_path("coffee_gpt"), LogModule::General);
+    }
+
+    #[test]
+    fn test_format_log_record() {
+        let record = LogRecord {
+            time: 510,
+            level: LogLevel::Warn,
+            module: LogModule::Cocoa,
+            thread: "barista-1",
+            order: Some(3),
+            message: "Queda poco cacao.",
+        };
+        assert_eq!(
+            record.format(),
+            "[   510 ms] [WARN] [cocoa] [barista-1] [pedido 3] Queda poco cacao."
+        );
+        let _scope = order_scope(7);
+        assert_eq!(CURRENT_ORDER.with(Cell::get), Some(7));
+        {
+            let _inner = order_scope(8);
+            assert_eq!(CURRENT_ORDER.with(Cell::get), Some(8));
+        }
+        assert_eq!(CURRENT_ORDER.with(Cell::get), Some(7));
+    }
+}
@@ -1,3 +1,10 @@
+#[macro_use]
+pub mod logger;
+pub mod cli;
+pub mod clock;
+pub mod config;
 pub mod constants;
 pub mod error;
 pub mod file_reader;
+pub mod json;
+pub mod random;
@@ -0,0 +1,51 @@
+/// Generador pseudoaleatorio SplitMix64. Dado el mismo valor devuelve siempre el mismo resultado,
+/// por lo que una corrida con la misma semilla repite las mismas variaciones.
+pub fn split_mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Aplica al tiempo una variación de hasta `percentage`% hacia arriba o hacia abajo.
+/// La variación depende únicamente de la semilla, el nombre de la acción y el identificador,
+/// por lo que no importa en qué orden se ejecuten los hilos.
+pub fn vary(time: u64, percentage: u32, seed: u64, action: &str, id: u64) -> u64 {
+    if percentage == 0 || time == 0 {
+        return time;
+    }
+    let action_hash = action
+        .bytes()
+        .fold(0xCBF2_9CE4_8422_2325_u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+        });
+    let random = split_mix(seed ^ split_mix(action_hash ^ split_mix(id)));
+    let max_variation = time * percentage.min(100) as u64 / 100;
+    let variation = random % (2 * max_variation + 1);
+    time + variation - max_variation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vary_is_reproducible() {
+        let first = vary(1000, 20, 42, "servir_cafe", 7);
+        assert_eq!(first, vary(1000, 20, 42, "servir_cafe", 7));
+        assert!((800..=1200).contains(&first));
+    }
+
+    #[test]
+    fn test_vary_without_percentage() {
+        assert_eq!(vary(1000, 0, 42, "servir_cafe", 7), 1000);
+    }
+
+    #[test]
+    fn test_vary_depends_on_seed() {
+        let times: Vec<u64> = (0..10)
+            .map(|seed| vary(1000, 50, seed, "servir_cafe", 1))
+            .collect();
+        assert!(times.iter().any(|time| *time != times[0]));
+    }
+}
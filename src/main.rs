@@ -1,20 +1,105 @@
+// Los tests comparan los flags con `assert_eq!(x, true)`, así se lee el valor esperado.
+#![cfg_attr(test, allow(clippy::bool_assert_comparison))]
+
 use std::env;
+use std::io;
+use std::process::ExitCode;
 
+use crate::helpers::cli::{CliArgs, Command, HELP};
+use crate::helpers::clock::Clock;
 use crate::helpers::error::CustomError;
+use crate::helpers::logger;
 use crate::structs::coffee_maker::CoffeeMaker;
+use crate::structs::dashboard::Dashboard;
+use crate::structs::event_bus::ConsoleSubscriber;
+use crate::structs::metrics_server;
+use crate::structs::order_server;
 
+#[macro_use]
 mod helpers;
 mod structs;
 
-/// Espera un argumento que sea el path del archivo que se utilizará para leer las ordenes.
-/// Si no se puede leer el argumento se devuelve el error [`CustomError::InvalidOrderFilePath`]
-fn main() -> Result<(), CustomError> {
-    let args: Vec<String> = env::args().collect();
-    if let Some(file_path) = args.get(1) {
-        let coffee_maker = CoffeeMaker::new();
-        coffee_maker.take_orders(file_path)
+/// Interpreta la línea de comandos (ver [`HELP`]) y prepara los pedidos del archivo indicado o recibidos por TCP.
+/// Si el programa termina con algún [`CustomError`] el código de salida es el de [`CustomError::exit_code`].
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => ExitCode::from(e.exit_code()),
+    }
+}
+
+/// Ejecuta el comando pedido.
+/// Con `--dry-run` solo se valida el archivo de pedidos, con `--simulated` se usa el reloj simulado
+/// y con `--serve` se reciben los pedidos por TCP. Con `--metrics` se publican las métricas mientras se preparan los pedidos.
+/// Los eventos de la cafetera se imprimen por pantalla y, con `--events`, también se escriben en un archivo.
+/// Con `--dashboard` en lugar de imprimir los eventos y las estadísticas se redibuja un [`Dashboard`].
+fn run(args: &[String]) -> Result<(), CustomError> {
+    let cli_args = match CliArgs::parse(args)? {
+        Command::Help => {
+            print!("{}", HELP);
+            return Ok(());
+        }
+        Command::Run(cli_args) => cli_args,
+    };
+    logger::set_filter(cli_args.load_log_filter()?);
+    let config = cli_args.load_config()?;
+    let clock = if cli_args.simulated {
+        Clock::simulated()
+    } else {
+        Clock::real()
+    };
+    logger::set_clock(clock.clone());
+    let menu = cli_args.load_menu()?;
+    let coffee_maker = CoffeeMaker::new(config, clock, menu);
+    if let Some(renderer) = cli_args.load_stats_renderer(config.stats_format)? {
+        coffee_maker.set_stats_renderer(renderer)?;
+    }
+    if cli_args.dry_run {
+        return coffee_maker.check_orders(&cli_args.orders_path).map(|_| ());
+    }
+    let file_subscriber = cli_args.load_event_subscriber()?;
+    let mut subscribers = Vec::new();
+    if cli_args.dashboard {
+        logger::use_stderr();
+        let dashboard = Dashboard::new(config, coffee_maker.events().subscribe(), io::stdout());
+        coffee_maker.set_stats_renderer(Box::new(dashboard))?;
     } else {
-        println!("No se específico el archivo de pedidos.");
-        Err(CustomError::InvalidOrderFilePath)
+        subscribers.push(coffee_maker.events().attach(Box::new(ConsoleSubscriber)));
+    }
+    if let Some(subscriber) = file_subscriber {
+        subscribers.push(coffee_maker.events().attach(subscriber));
+    }
+    let trace_output = cli_args.load_trace_output()?;
+    if trace_output.is_some() {
+        coffee_maker.tracer().start();
+    }
+    let metrics = match &cli_args.metrics {
+        Some(address) => Some(metrics_server::start(
+            metrics_server::bind(address)?,
+            coffee_maker.clone(),
+        )?),
+        None => None,
+    };
+    let result = match &cli_args.serve {
+        Some(address) => {
+            order_server::bind(address).and_then(|listener| coffee_maker.serve(listener))
+        }
+        None => coffee_maker.take_orders(&cli_args.orders_path),
+    };
+    if let Some(metrics) = metrics {
+        metrics.stop();
+    }
+    coffee_maker.events().close();
+    for subscriber in subscribers {
+        if subscriber.join().is_err() {
+            error!("No se pudo unir el hilo de un suscriptor de eventos.");
+        }
+    }
+    if let Some(output) = trace_output {
+        if let Err(e) = coffee_maker.tracer().write_chrome_trace(output) {
+            error!("No se pudo escribir la línea de tiempo: {:?}", e);
+        }
     }
+    result.map(|report| report.log_summary())
 }
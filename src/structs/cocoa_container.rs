@@ -1,4 +1,5 @@
-use crate::helpers::constants::C;
+use crate::helpers::config::Config;
+use crate::structs::dispenser_queue::DispenserQueue;
 
 /// Estructura simple que actúa como contenedor de cacao.
 #[derive(Debug)]
@@ -7,22 +8,49 @@ pub struct CocoaContainer {
     pub cocoa: u32,
     /// Cantidad de cacao ya utilizado.
     pub used: u32,
+    /// Cantidad de dispensadores de cacao libres para servir un pedido.
+    pub free_dispensers: u32,
+    /// Pedidos bloqueados esperando un dispensador de cacao, en orden de prioridad.
+    pub dispenser_queue: DispenserQueue,
+    /// Cantidad de cacao reservada por pedidos que todavía no se sirvieron.
+    pub reserved: u32,
 }
 
 impl CocoaContainer {
-    pub fn new() -> CocoaContainer {
-        CocoaContainer { cocoa: C, used: 0 }
+    pub fn new(config: &Config) -> CocoaContainer {
+        CocoaContainer {
+            cocoa: config.cocoa_capacity,
+            used: 0,
+            free_dispensers: config.dispensers,
+            dispenser_queue: DispenserQueue::new(config.cocoa_dispenser_policy),
+            reserved: 0,
+        }
+    }
+
+    /// Cantidad de cacao que todavía se puede reservar.
+    pub fn available(&self) -> u32 {
+        self.cocoa.saturating_sub(self.reserved)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::helpers::constants::{C, N};
 
     #[test]
     fn test_new_cocoa_container() {
-        let cocoa_container = CocoaContainer::new();
+        let cocoa_container = CocoaContainer::new(&Config::default());
         assert_eq!(cocoa_container.cocoa, C);
         assert_eq!(cocoa_container.used, 0);
+        assert_eq!(cocoa_container.free_dispensers, N);
+        assert_eq!(cocoa_container.reserved, 0);
+    }
+
+    #[test]
+    fn test_available_discounts_reserved() {
+        let mut cocoa_container = CocoaContainer::new(&Config::default());
+        cocoa_container.reserved = 30;
+        assert_eq!(cocoa_container.available(), C - 30);
     }
 }
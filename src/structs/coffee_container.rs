@@ -1,10 +1,13 @@
 use std::cmp::min;
 use std::sync::{Arc, Condvar, Mutex};
-use std::thread;
-use std::time::Duration;
 
-use crate::helpers::constants::{CANTIDAD_RELLENO, G, M, REFILL_COFFEE_TIME, X};
+use crate::helpers::clock::Clock;
+use crate::helpers::config::Config;
 use crate::helpers::error::CustomError;
+use crate::structs::dispenser_queue::DispenserQueue;
+use crate::structs::event_bus::EventBus;
+use crate::structs::machine_event::{Ingredient, MachineEvent};
+use crate::structs::tracer::Tracer;
 
 /// Contenedor de café.
 pub struct CoffeeContainer {
@@ -16,57 +19,127 @@ pub struct CoffeeContainer {
     pub coffee_grains_used: u32,
     /// Cantidad de café molido ya utilizado.
     pub ground_coffee_used: u32,
+    /// Cantidad de dispensadores de café libres para servir un pedido.
+    pub free_dispensers: u32,
+    /// Pedidos bloqueados esperando un dispensador de café, en orden de prioridad.
+    pub dispenser_queue: DispenserQueue,
+    /// Cantidad de café reservada por pedidos que todavía no se sirvieron.
+    pub reserved: u32,
+    /// Cantidades de café molido que esperan los dispensadores que no pudieron servir por falta de stock.
+    pub waiting: Vec<u32>,
+    /// Flag que indica que se está rellenando el café molido, por lo que no se puede servir.
+    pub refilling: bool,
+    /// Cantidad de veces que se rellenó el café molido.
+    pub refills: u32,
     /// Flag para indicar que ya no se deben rellenar el café molido.
     pub shutdown: bool,
 }
 
 impl CoffeeContainer {
-    pub fn new() -> CoffeeContainer {
+    pub fn new(config: &Config) -> CoffeeContainer {
         CoffeeContainer {
-            coffee_grains_container: G,
-            ground_coffee_container: M,
+            coffee_grains_container: config.coffee_grains_capacity,
+            ground_coffee_container: config.ground_coffee_capacity,
             coffee_grains_used: 0,
             ground_coffee_used: 0,
+            free_dispensers: config.dispensers,
+            dispenser_queue: DispenserQueue::new(config.coffee_dispenser_policy),
+            reserved: 0,
+            waiting: Vec::new(),
+            refilling: false,
+            refills: 0,
             shutdown: false,
         }
     }
 
-    /// Rellena el café molido cuando tiene una disponibilidad menor a [`CANTIDAD_RELLENO`].
+    /// Mayor cantidad de café molido que espera algún dispensador, o 0 si no hay ninguno esperando.
+    pub fn demand(&self) -> u32 {
+        self.waiting.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Quita de los pedidos en espera uno que esperaba la cantidad indicada.
+    pub fn stop_waiting(&mut self, amount: u32) {
+        if let Some(position) = self.waiting.iter().position(|waiting| *waiting == amount) {
+            self.waiting.swap_remove(position);
+        }
+    }
+
+    /// Cantidad de café que todavía se puede reservar, contando el que se puede moler con los granos restantes.
+    pub fn available(&self) -> u32 {
+        (self.ground_coffee_container + self.coffee_grains_container).saturating_sub(self.reserved)
+    }
+
+    /// Rellena el café molido cuando tiene una disponibilidad menor a [`Config::refill_threshold`]
+    /// o cuando no alcanza para algún pedido que está esperando ([`CoffeeContainer::demand`]).
     /// Es un loop donde se tiene en cuenta la disponibilidad del café molido y si el mismo debe apagarse.
-    /// Mientras se esta recargando el cafe molido no se puede utilizar el contenedor.
+    /// Mientras se esta recargando el cafe molido no se puede utilizar el contenedor, lo que se indica con [`CoffeeContainer::refilling`].
+    /// Las esperas se hacen con el [`Clock`] indicado, los rellenados se publican en `events` y se registran en `tracer`.
     /// Si la cantidad de granos llega a cero, se deja de ejecutar ya que no se pueden recargar los granos.
-    /// Al llegar al [`X%`] de su disponibilidad de granos se alerta por pantalla.
+    /// Al llegar al [`Config::alert_threshold`]% de su disponibilidad de granos se publica [`MachineEvent::LowStock`].
     pub fn grind_coffee(
         coffee_container: Arc<(Mutex<CoffeeContainer>, Condvar)>,
+        config: Config,
+        clock: Clock,
+        events: EventBus,
+        tracer: Tracer,
     ) -> Result<(), CustomError> {
         let (coffee_lock, coffee_cvar) = &*coffee_container;
         loop {
-            if let Ok(mut state) = coffee_cvar.wait_while(coffee_lock.lock()?, |coffee_container| {
-                coffee_container.ground_coffee_container > CANTIDAD_RELLENO
+            let mut state = clock.wait_while(coffee_lock, coffee_cvar, |coffee_container| {
+                coffee_container.ground_coffee_container > config.refill_threshold
+                    && coffee_container.ground_coffee_container >= coffee_container.demand()
                     && !coffee_container.shutdown
-            }) {
-                if state.shutdown {
-                    break;
-                }
-                if state.coffee_grains_container == 0 {
-                    println!("[DEBUG] No hay mas granos.");
-                    break;
-                }
-                println!("[DEBUG] Rellenando el café molido.");
-                thread::sleep(Duration::from_millis(REFILL_COFFEE_TIME));
-                let grains_to_grind = min(
-                    M - state.ground_coffee_container,
-                    state.coffee_grains_container,
+            })?;
+            if state.shutdown {
+                break;
+            }
+            if state.coffee_grains_container == 0 {
+                debug!("No hay mas granos.");
+                break;
+            }
+            events.publish(
+                clock.now(),
+                MachineEvent::RefillStarted {
+                    ingredient: Ingredient::GroundCoffee,
+                },
+            );
+            state.refilling = true;
+            let refill = state.refills.into();
+            drop(state);
+            tracer.measure(&clock, "refill", "moler café", || {
+                clock.sleep(config.action_time(config.refill_coffee_time, "moler_cafe", refill))
+            });
+
+            let mut state = tracer.measure(&clock, "lock", "lock café", || coffee_lock.lock())?;
+            state.refilling = false;
+            state.refills += 1;
+            let grains_to_grind = min(
+                config.ground_coffee_capacity - state.ground_coffee_container,
+                state.coffee_grains_container,
+            );
+            state.ground_coffee_container += grains_to_grind;
+            state.coffee_grains_container -= grains_to_grind;
+            state.coffee_grains_used += grains_to_grind;
+            events.publish(
+                clock.now(),
+                MachineEvent::RefillFinished {
+                    ingredient: Ingredient::GroundCoffee,
+                    amount: grains_to_grind,
+                },
+            );
+            let capacity_percentage =
+                config.alert_threshold as f32 / 100.0 * config.coffee_grains_capacity as f32;
+            if (state.coffee_grains_container as f32) < capacity_percentage {
+                events.publish(
+                    clock.now(),
+                    MachineEvent::LowStock {
+                        ingredient: Ingredient::CoffeeGrains,
+                        available: state.coffee_grains_container,
+                        threshold: config.alert_threshold,
+                    },
                 );
-                state.ground_coffee_container += grains_to_grind;
-                state.coffee_grains_container -= grains_to_grind;
-                state.coffee_grains_used += grains_to_grind;
-                let capacity_percentage = X as f32 / 100.0 * G as f32;
-                if (state.coffee_grains_container as f32) < capacity_percentage {
-                    println!("[WARN] El contenedor de granos se encuentra por debajo de {:?}% de su capacidad", X);
-                }
-                coffee_cvar.notify_all();
             }
+            coffee_cvar.notify_all();
         }
         Ok(())
     }
@@ -75,30 +148,45 @@ impl CoffeeContainer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::helpers::config::DispenserPolicy;
+    use crate::helpers::constants::{CANTIDAD_RELLENO, G, M, N};
+    use std::thread;
 
     #[test]
     fn test_new_coffee_container() {
-        let coffee_container = CoffeeContainer::new();
+        let coffee_container = CoffeeContainer::new(&Config::default());
         assert_eq!(coffee_container.coffee_grains_container, G);
         assert_eq!(coffee_container.ground_coffee_container, M);
         assert_eq!(coffee_container.coffee_grains_used, 0);
         assert_eq!(coffee_container.ground_coffee_used, 0);
+        assert_eq!(coffee_container.free_dispensers, N);
+        assert_eq!(coffee_container.available(), M + G);
         assert_eq!(coffee_container.shutdown, false);
     }
 
     #[test]
     fn test_grind_coffee_refill() -> Result<(), CustomError> {
-        let coffee_container = Arc::new((Mutex::new(CoffeeContainer::new()), Condvar::new()));
+        let coffee_container = Arc::new((
+            Mutex::new(CoffeeContainer::new(&Config::default())),
+            Condvar::new(),
+        ));
         let coffee_container_clone = coffee_container.clone();
-        let thread_handle =
-            thread::spawn(
-                move || match CoffeeContainer::grind_coffee(coffee_container_clone) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        println!("[ERROR] Testeando grind_coffee: {:?}", e);
-                    }
-                },
-            );
+        let events = EventBus::default();
+        let received = events.subscribe();
+        let thread_handle = thread::spawn(move || {
+            match CoffeeContainer::grind_coffee(
+                coffee_container_clone,
+                Config::default(),
+                Clock::real(),
+                events,
+                Tracer::default(),
+            ) {
+                Ok(_) => {}
+                Err(e) => {
+                    println!("[ERROR] Testeando grind_coffee: {:?}", e);
+                }
+            }
+        });
 
         let (coffee_lock, coffee_cvar) = &*coffee_container;
         match coffee_lock.lock() {
@@ -125,6 +213,7 @@ mod tests {
             assert_eq!(state.ground_coffee_used, M - CANTIDAD_RELLENO + 1);
             assert_eq!(state.coffee_grains_container, G - M + CANTIDAD_RELLENO - 1);
             assert_eq!(state.ground_coffee_container, M);
+            assert_eq!(state.refills, 1);
         }
         coffee_cvar.notify_all();
 
@@ -144,6 +233,19 @@ mod tests {
             println!("[ERROR] Testeando grind_coffee, uniendo hilo.");
             return Err(CustomError::TestFailing);
         }
+        let events: Vec<MachineEvent> = received.iter().map(|record| record.event).collect();
+        assert_eq!(
+            events,
+            vec![
+                MachineEvent::RefillStarted {
+                    ingredient: Ingredient::GroundCoffee
+                },
+                MachineEvent::RefillFinished {
+                    ingredient: Ingredient::GroundCoffee,
+                    amount: M - CANTIDAD_RELLENO + 1
+                },
+            ]
+        );
         Ok(())
     }
 
@@ -155,20 +257,31 @@ mod tests {
                 ground_coffee_container: 40,
                 coffee_grains_used: 0,
                 ground_coffee_used: 0,
+                free_dispensers: N,
+                dispenser_queue: DispenserQueue::new(DispenserPolicy::Priority),
+                reserved: 0,
+                waiting: Vec::new(),
+                refilling: false,
+                refills: 0,
                 shutdown: false,
             }),
             Condvar::new(),
         ));
         let coffee_container_clone = coffee_container.clone();
-        let thread_handle =
-            thread::spawn(
-                move || match CoffeeContainer::grind_coffee(coffee_container_clone) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        println!("[ERROR] Testeando grind_coffee: {:?}", e);
-                    }
-                },
-            );
+        let thread_handle = thread::spawn(move || {
+            match CoffeeContainer::grind_coffee(
+                coffee_container_clone,
+                Config::default(),
+                Clock::real(),
+                EventBus::default(),
+                Tracer::default(),
+            ) {
+                Ok(_) => {}
+                Err(e) => {
+                    println!("[ERROR] Testeando grind_coffee: {:?}", e);
+                }
+            }
+        });
 
         let (coffee_lock, coffee_cvar) = &*coffee_container;
         match coffee_lock.lock() {
@@ -212,19 +325,72 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_grind_coffee_refills_for_waiting_demand() -> Result<(), CustomError> {
+        let coffee_container = Arc::new((
+            Mutex::new(CoffeeContainer::new(&Config::default())),
+            Condvar::new(),
+        ));
+        let coffee_container_clone = coffee_container.clone();
+        let thread_handle = thread::spawn(move || {
+            if let Err(e) = CoffeeContainer::grind_coffee(
+                coffee_container_clone,
+                Config::default(),
+                Clock::real(),
+                EventBus::default(),
+                Tracer::default(),
+            ) {
+                println!("[ERROR] Testeando grind_coffee: {:?}", e);
+            }
+        });
+
+        let (coffee_lock, coffee_cvar) = &*coffee_container;
+        {
+            let mut state = coffee_lock.lock()?;
+            state.ground_coffee_container = CANTIDAD_RELLENO + 10;
+            state.waiting.push(CANTIDAD_RELLENO + 20);
+            assert_eq!(state.demand(), CANTIDAD_RELLENO + 20);
+        }
+        coffee_cvar.notify_all();
+
+        let mut state = coffee_cvar.wait_while(coffee_lock.lock()?, |coffee_container| {
+            coffee_container.ground_coffee_container < M
+        })?;
+        assert_eq!(state.coffee_grains_used, M - CANTIDAD_RELLENO - 10);
+        state.stop_waiting(CANTIDAD_RELLENO + 20);
+        assert_eq!(state.demand(), 0);
+        state.shutdown = true;
+        drop(state);
+        coffee_cvar.notify_all();
+
+        if thread_handle.join().is_err() {
+            println!("[ERROR] Testeando grind_coffee, uniendo hilo.");
+            return Err(CustomError::TestFailing);
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_grind_coffee_shutdown() -> Result<(), CustomError> {
-        let coffee_container = Arc::new((Mutex::new(CoffeeContainer::new()), Condvar::new()));
+        let coffee_container = Arc::new((
+            Mutex::new(CoffeeContainer::new(&Config::default())),
+            Condvar::new(),
+        ));
         let coffee_container_clone = coffee_container.clone();
-        let thread_handle =
-            thread::spawn(
-                move || match CoffeeContainer::grind_coffee(coffee_container_clone) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        println!("[ERROR] Testeando grind_coffee: {:?}", e);
-                    }
-                },
-            );
+        let thread_handle = thread::spawn(move || {
+            match CoffeeContainer::grind_coffee(
+                coffee_container_clone,
+                Config::default(),
+                Clock::real(),
+                EventBus::default(),
+                Tracer::default(),
+            ) {
+                Ok(_) => {}
+                Err(e) => {
+                    println!("[ERROR] Testeando grind_coffee: {:?}", e);
+                }
+            }
+        });
 
         let (coffee_lock, coffee_cvar) = &*coffee_container;
         match coffee_lock.lock() {
@@ -1,24 +1,39 @@
 extern crate std_semaphore;
 
-use std::sync::{Arc, Condvar, Mutex};
-use std::thread;
-use std::thread::JoinHandle;
+use std::collections::HashMap;
+use std::io;
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
 use std::time::Duration;
 
-use crate::helpers::constants::{
-    C, SERVE_COCOA_TIME, SERVE_COFFEE_TIME, SERVE_HOT_WATER_TIME, SERVE_MILK_FOAM_TIME,
-    TAKE_ORDER_TIME, TIME_TO_STATS, X,
-};
+use crate::helpers::clock::{Clock, Handle};
+use crate::helpers::config::Config;
 use crate::helpers::error::CustomError;
 use crate::helpers::file_reader;
+use crate::helpers::logger;
 use crate::structs::cocoa_container::CocoaContainer;
 use crate::structs::coffee_container::CoffeeContainer;
+use crate::structs::event_bus::EventBus;
 use crate::structs::hot_water_container::HotWaterContainer;
+use crate::structs::machine_event::{Ingredient, MachineEvent};
+use crate::structs::menu::Menu;
 use crate::structs::milk_container::MilkContainer;
-use crate::structs::order::Order;
+use crate::structs::order::{Order, OrderFormat, OrderStatus};
+use crate::structs::order_queue::OrderQueue;
+use crate::structs::order_server;
+use crate::structs::run_report::RunReport;
 use crate::structs::statistics_values::StatisticsValues;
+use crate::structs::stats_renderer::{self, StatsRenderer};
+use crate::structs::stats_snapshot::StatsSnapshot;
+use crate::structs::tracer::Tracer;
+
+/// Función que recibe el estado final de un pedido. Ver [`CoffeeMaker::on_order_finished`].
+pub type StatusListener = Box<dyn FnOnce(OrderStatus) + Send>;
 
 /// Estructura principal del programa.
+/// Los clones comparten los contenedores y las estadísticas, se utilizan para pasarle la cafetera a cada barista.
+#[derive(Clone)]
 pub struct CoffeeMaker {
     /// Contenedor de café molido y granos de café.
     coffee_container: Arc<(Mutex<CoffeeContainer>, Condvar)>,
@@ -30,381 +45,1202 @@ pub struct CoffeeMaker {
     milk_container: Arc<(Mutex<MilkContainer>, Condvar)>,
     /// Contiene datos utilizados para las estadísticas.
     statistics_values: Arc<Mutex<StatisticsValues>>,
+    /// Parámetros del modelo de cafetera: capacidades, umbrales y tiempos.
+    config: Config,
+    /// Reloj con el que se hacen todas las esperas, real o simulado.
+    clock: Clock,
+    /// Menú con las bebidas que se pueden pedir por nombre.
+    menu: Menu,
+    /// Contador de dispensadores liberados, con el que se despiertan los pedidos que esperan un dispensador.
+    dispensers_released: Arc<(Mutex<u64>, Condvar)>,
+    /// Cantidad de veces que un pedido bloqueado revisó si se liberó un dispensador, para verificar que no giran en un loop.
+    dispenser_checks: Arc<AtomicU64>,
+    /// Funciones a llamar con el estado final de cada pedido, por identificador.
+    status_listeners: Arc<Mutex<HashMap<u32, StatusListener>>>,
+    /// Con qué se imprimen las estadísticas. Ver [`CoffeeMaker::set_stats_renderer`].
+    stats_renderer: Arc<Mutex<Box<dyn StatsRenderer>>>,
+    /// Donde se publica lo que pasa en la cafetera. Ver [`CoffeeMaker::events`].
+    events: EventBus,
+    /// Registra la actividad de los hilos para `--trace`. Ver [`CoffeeMaker::tracer`].
+    tracer: Tracer,
 }
 
 impl CoffeeMaker {
-    pub fn new() -> CoffeeMaker {
+    pub fn new(config: Config, clock: Clock, menu: Menu) -> CoffeeMaker {
         CoffeeMaker {
-            coffee_container: Arc::new((Mutex::new(CoffeeContainer::new()), Condvar::new())),
-            hot_water_container: Arc::new((Mutex::new(HotWaterContainer::new()), Condvar::new())),
-            cocoa_container: Arc::new((Mutex::new(CocoaContainer::new()), Condvar::new())),
-            milk_container: Arc::new((Mutex::new(MilkContainer::new()), Condvar::new())),
+            coffee_container: Arc::new((Mutex::new(CoffeeContainer::new(&config)), Condvar::new())),
+            hot_water_container: Arc::new((
+                Mutex::new(HotWaterContainer::new(&config)),
+                Condvar::new(),
+            )),
+            cocoa_container: Arc::new((Mutex::new(CocoaContainer::new(&config)), Condvar::new())),
+            milk_container: Arc::new((Mutex::new(MilkContainer::new(&config)), Condvar::new())),
             statistics_values: Arc::new(Mutex::new(StatisticsValues::new())),
+            config,
+            clock,
+            menu,
+            dispensers_released: Arc::new((Mutex::new(0), Condvar::new())),
+            dispenser_checks: Arc::new(AtomicU64::new(0)),
+            status_listeners: Arc::new(Mutex::new(HashMap::new())),
+            stats_renderer: Arc::new(Mutex::new(stats_renderer::for_format(
+                config.stats_format,
+                Box::new(io::stdout()),
+            ))),
+            events: EventBus::default(),
+            tracer: Tracer::default(),
         }
     }
 
-    /// Lee las líneas del archivo y las interpreta como órdenes.
-    /// Si alguna linea falla la ejecución continuará sin preparar ese pedido erróneo.
-    /// Para cada una de ellas abre un hilo para prepar la misma.
-    /// Crea 3 hilos para los contenedores que deben ser rellenados dadas ciertas condiciones.
-    /// Crea un último hilo que se encargara de la impresion de las estadísiticas.
-    /// Una vez que finalizan todos los pedidos le envía una señal a los contenedores de rellenado para que dejen de correr.
+    /// Bus en el que se publican los [`MachineEvent`] de la cafetera: pedidos recibidos, ingredientes servidos,
+    /// rellenados, alertas de stock, pedidos completados o rechazados y el apagado.
+    /// Sin suscriptores los eventos se descartan.
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Registro de la actividad de los hilos: tomar pedidos, preparar cada pedido, esperar locks, dispensadores o stock,
+    /// servir cada ingrediente y rellenar los contenedores. No registra nada hasta que se llama a [`Tracer::start`].
+    pub fn tracer(&self) -> &Tracer {
+        &self.tracer
+    }
+
+    /// Lee las líneas del archivo y las prepara como pedidos con [`CoffeeMaker::run_orders`],
+    /// recibiéndolas con [`CoffeeMaker::receive_orders`].
+    /// Las líneas están en el formato de [`Config::order_format`] o, si no se indicó, en el que corresponde a la extensión del archivo.
     /// Errores:
     /// * Si no se puede abrir el archivo fallará con error [`CustomError::CantOpenOrderFile`]
-    /// * Si no se puede enviar la señal a los contenedores de rellenado falla con [`CustomError::InvalidShutDown`] porque sino la ejecución no terminaría nunca.
-    pub fn take_orders(&self, file_path: &str) -> Result<(), CustomError> {
-        let lines = file_reader::read_lines(file_path);
-        let mut id: u32 = 0;
-        match lines {
-            Ok(lines) => {
-                let mut order_handle: Vec<JoinHandle<()>> = Vec::new();
-                let mut refills_handle: Vec<JoinHandle<()>> = Vec::new();
-
-                for line in lines {
-                    match line {
-                        Ok(line) => {
-                            thread::sleep(Duration::from_millis(TAKE_ORDER_TIME));
-                            match Order::from_file_record(&line, id) {
-                                Ok(order) => {
-                                    order_handle.push(self.prepare_order(order));
-                                    id += 1;
-                                }
-                                Err(e) => {
-                                    println!("[ERROR] No se pudo crear el pedido: {:?}", e);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            println!("[ERROR] El pedido no pudo ser procesado: {:?}", e);
-                        }
-                    }
-                }
+    /// * Los de [`CoffeeMaker::run_orders`]
+    pub fn take_orders(&self, file_path: &str) -> Result<RunReport, CustomError> {
+        let format = self
+            .config
+            .order_format
+            .unwrap_or_else(|| OrderFormat::from_path(file_path));
+        match file_reader::read_lines(file_path) {
+            Ok(lines) => self.run_orders(move |coffee_maker, queue| {
+                coffee_maker.receive_orders(lines, format, &queue)
+            }),
+            Err(e) => {
+                error!(
+                    "No se puedo abrir el archivo de ordenes correctamente: {:?}",
+                    e
+                );
+                Err(CustomError::CantOpenOrderFile)
+            }
+        }
+    }
 
-                let coffee_container_clone = self.coffee_container.clone();
-                refills_handle.push(thread::spawn(move || {
-                    if CoffeeContainer::grind_coffee(coffee_container_clone).is_err() {
-                        println!("[ERROR] Error en sistema al rellenar contenedor de café molido.");
-                    }
-                }));
+    /// Recibe pedidos por TCP con [`order_server::accept_orders`] y los prepara con [`CoffeeMaker::run_orders`],
+    /// hasta que algún cliente apague el servidor.
+    /// Devuelve los errores de [`CoffeeMaker::run_orders`].
+    pub fn serve(&self, listener: TcpListener) -> Result<RunReport, CustomError> {
+        self.run_orders(move |coffee_maker, queue| {
+            order_server::accept_orders(listener, coffee_maker, queue)
+        })
+    }
 
-                let milk_container_clone = self.milk_container.clone();
-                refills_handle.push(thread::spawn(move || {
-                    if MilkContainer::make_milk_foam(milk_container_clone).is_err() {
-                        println!(
-                            "[ERROR] Error en sistema al rellenar contenedor de espuma de leche."
-                        );
-                    }
-                }));
+    /// Pone en marcha la cafetera y prepara los pedidos que va recibiendo `intake`.
+    /// Antes de tomar el primer pedido crea 3 hilos para los contenedores que deben ser rellenados dadas ciertas condiciones
+    /// y un hilo que se encargara de la impresion de las estadísiticas, para que funcionen mientras se reciben los pedidos.
+    /// `intake` corre en un hilo aparte, encola los pedidos con [`CoffeeMaker::receive_order`] en una [`OrderQueue`] acotada,
+    /// de la que los toman [`Config::baristas`] hilos para prepararlos, y devuelve la cantidad de líneas inválidas.
+    /// Una vez que finalizan todos los pedidos le envía una señal a los contenedores de rellenado para que dejen de correr.
+    /// Devuelve un [`RunReport`] con el estado final de cada pedido.
+    /// Errores:
+    /// * Si falla la recepción de pedidos devuelve su error, luego de terminar los pedidos ya recibidos.
+    /// * Si no se puede enviar la señal a los contenedores de rellenado falla con [`CustomError::InvalidShutDown`] porque sino la ejecución no terminaría nunca.
+    fn run_orders<F>(&self, intake: F) -> Result<RunReport, CustomError>
+    where
+        F: FnOnce(CoffeeMaker, Arc<OrderQueue>) -> Result<u32, CustomError> + Send + 'static,
+    {
+        let _participant = self.clock.participate();
+        let mut refills_handle: Vec<Handle<()>> = Vec::new();
 
-                let hot_water_container_clone = self.hot_water_container.clone();
-                refills_handle.push(thread::spawn(move || {
-                    if HotWaterContainer::heat_water(hot_water_container_clone).is_err() {
-                        println!(
-                            "[ERROR] Error en sistema al rellenar contenedor de agua caliente."
-                        );
-                    }
-                }));
+        let coffee_container_clone = self.coffee_container.clone();
+        let config = self.config;
+        let clock_clone = self.clock.clone();
+        let events = self.events.clone();
+        let tracer = self.tracer.clone();
+        refills_handle.push(self.clock.spawn_named("moler-cafe".to_string(), move || {
+            if CoffeeContainer::grind_coffee(
+                coffee_container_clone,
+                config,
+                clock_clone,
+                events,
+                tracer,
+            )
+            .is_err()
+            {
+                error!("Error en sistema al rellenar contenedor de café molido.");
+            }
+        }));
 
-                let coffee_container_clone = self.coffee_container.clone();
-                let hot_water_container_clone = self.hot_water_container.clone();
-                let cocoa_container_clone = self.cocoa_container.clone();
-                let milk_container_clone = self.milk_container.clone();
-                let statistics_values_clone = self.statistics_values.clone();
-                let statistics_handle = thread::spawn(move || {
-                    if Self::show_statistics(
-                        coffee_container_clone,
+        let milk_container_clone = self.milk_container.clone();
+        let clock_clone = self.clock.clone();
+        let events = self.events.clone();
+        let tracer = self.tracer.clone();
+        refills_handle.push(self.clock.spawn_named("hacer-espuma".to_string(), move || {
+            if MilkContainer::make_milk_foam(
+                milk_container_clone,
+                config,
+                clock_clone,
+                events,
+                tracer,
+            )
+            .is_err()
+            {
+                error!("Error en sistema al rellenar contenedor de espuma de leche.");
+            }
+        }));
+
+        let hot_water_container_clone = self.hot_water_container.clone();
+        let clock_clone = self.clock.clone();
+        let events = self.events.clone();
+        let tracer = self.tracer.clone();
+        refills_handle.push(
+            self.clock
+                .spawn_named("calentar-agua".to_string(), move || {
+                    if HotWaterContainer::heat_water(
                         hot_water_container_clone,
-                        cocoa_container_clone,
-                        milk_container_clone,
-                        statistics_values_clone,
+                        config,
+                        clock_clone,
+                        events,
+                        tracer,
                     )
                     .is_err()
                     {
-                        println!("[ERROR] Fallo el procesamiento de las estadísticas. Continua la preparación de pedidos sin ellas.");
+                        error!("Error en sistema al rellenar contenedor de agua caliente.");
                     }
-                });
+                }),
+        );
 
-                for order_thread in order_handle {
-                    if order_thread.join().is_err() {
-                        println!("[ERROR] No se pudo unir el hilo de una orden.");
-                    }
-                }
+        let coffee_maker = self.clone();
+        let statistics_handle = self.clock.spawn_named("estadisticas".to_string(), move || {
+            if coffee_maker.show_statistics().is_err() {
+                error!("Fallo el procesamiento de las estadísticas. Continua la preparación de pedidos sin ellas.");
+            }
+        });
+
+        let queue = Arc::new(OrderQueue::new(
+            self.config.queue_capacity,
+            self.config.aging_time,
+        ));
+        let baristas_handle: Vec<Handle<()>> = (0..self.config.baristas)
+            .map(|number| self.start_barista(number, queue.clone()))
+            .collect();
+
+        let coffee_maker = self.clone();
+        let queue_clone = queue.clone();
+        let intake_handle = self.clock.spawn_named("recepcion".to_string(), move || {
+            let received = intake(coffee_maker, queue_clone.clone());
+            if queue_clone.close().is_err() {
+                error!("No se pudo cerrar la cola de pedidos.");
+            }
+            received
+        });
 
-                if self.send_shutdown_signal().is_err() {
-                    println!("[ERROR] No se pudo enviar la señal de apagado a los contenedores.\nTerminando proceso con error.");
-                    return Err(CustomError::InvalidShutDown);
-                }
+        let intake = intake_handle.join().unwrap_or_else(|_| {
+            error!("No se pudo unir el hilo de recepción de pedidos.");
+            Err(CustomError::PoisonedLock)
+        });
 
-                for refill_thread in refills_handle {
-                    if refill_thread.join().is_err() {
-                        println!("[ERROR] No se pudo unir el hilo de rellenados.");
-                    }
-                }
+        for barista_thread in baristas_handle {
+            if barista_thread.join().is_err() {
+                error!("No se pudo unir el hilo de un barista.");
+            }
+        }
+
+        if self.send_shutdown_signal().is_err() {
+            error!("No se pudo enviar la señal de apagado a los contenedores.\nTerminando proceso con error.");
+            return Err(CustomError::InvalidShutDown);
+        }
+        self.events
+            .publish(self.clock.now(), MachineEvent::Shutdown);
+
+        for refill_thread in refills_handle {
+            if refill_thread.join().is_err() {
+                error!("No se pudo unir el hilo de rellenados.");
+            }
+        }
+
+        if statistics_handle.join().is_err() {
+            error!("No se pudo unir el hilo de estadísticas.");
+        }
+        info!("Pedidos finalizados en {:?} ms.", self.clock.now());
+
+        let statistics_values = self.statistics_values.lock()?;
+        Ok(RunReport {
+            orders_served: statistics_values.orders_served,
+            orders_rejected: statistics_values.orders_rejected,
+            orders_partially_served: statistics_values.orders_partially_served,
+            invalid_records: intake?,
+            waits: statistics_values.waits.clone(),
+            latency: statistics_values.latency(),
+            container_waits: statistics_values.container_waits(),
+        })
+    }
 
-                if statistics_handle.join().is_err() {
-                    println!("[ERROR] No se pudo unir el hilo de estadísticas.");
+    /// Interpreta cada línea como una orden con [`CoffeeMaker::take_order`] y la recibe con [`CoffeeMaker::receive_order`].
+    /// Si alguna linea falla la recepción continuará sin preparar ese pedido erróneo, informando el número de línea.
+    /// Devuelve la cantidad de líneas inválidas.
+    /// Si algún lock está envenenado devuelve [`CustomError::PoisonedLock`]
+    fn receive_orders(
+        &self,
+        lines: impl Iterator<Item = io::Result<String>>,
+        format: OrderFormat,
+        queue: &OrderQueue,
+    ) -> Result<u32, CustomError> {
+        let mut id: u32 = 0;
+        let mut invalid_records: u32 = 0;
+        for (index, line) in lines.enumerate() {
+            match line {
+                Ok(line) => match self.take_order(&line, id, format) {
+                    Ok(order) => {
+                        self.receive_order(order, queue)?;
+                        id += 1;
+                    }
+                    Err(e) => {
+                        error!("Pedido inválido en línea {:?}: {}", index + 1, e);
+                        invalid_records += 1;
+                    }
+                },
+                Err(e) => {
+                    error!("El pedido no pudo ser procesado: {:?}", e);
+                    invalid_records += 1;
                 }
             }
+        }
+        Ok(invalid_records)
+    }
+
+    /// Toma un pedido: espera el tiempo de [`Config::take_order_time`] e interpreta la línea con [`CoffeeMaker::parse_order`].
+    /// Si la línea es inválida devuelve el motivo.
+    pub fn take_order(&self, line: &str, id: u32, format: OrderFormat) -> Result<Order, String> {
+        self.tracer
+            .measure(&self.clock, "take", "tomar pedido", || {
+                self.clock.sleep(self.config.action_time(
+                    self.config.take_order_time,
+                    "tomar_pedido",
+                    id.into(),
+                ))
+            });
+        self.parse_order(line, id, format)
+    }
+
+    /// Interpreta la línea con [`Menu::order_from_record`] o [`Order::from_json_record`], según el formato.
+    fn parse_order(&self, line: &str, id: u32, format: OrderFormat) -> Result<Order, String> {
+        match format {
+            OrderFormat::Csv => self.menu.order_from_record(line, id),
+            OrderFormat::Jsonl => Order::from_json_record(line, id),
+        }
+    }
+
+    /// Encola el pedido para que lo prepare algún barista.
+    /// Antes verifica que se pueda servir con [`Order::validate`] y le reserva sus ingredientes
+    /// con [`CoffeeMaker::reserve_ingredients`]. Si no se puede servir o no alcanzan los ingredientes es rechazado.
+    /// Si la cola está llena espera a que se libere un lugar o rechaza el pedido, según [`Config::queue_policy`].
+    /// Si algún lock está envenenado devuelve [`CustomError::PoisonedLock`]
+    pub fn receive_order(&self, mut order: Order, queue: &OrderQueue) -> Result<(), CustomError> {
+        let _order_scope = logger::order_scope(order.id);
+        order.received_at = self.clock.now();
+        self.events.publish(
+            order.received_at,
+            MachineEvent::OrderReceived {
+                order_id: order.id,
+                reference: order.reference.clone(),
+                priority: order.priority,
+            },
+        );
+        if let Err(e) = order
+            .validate(&self.config)
+            .and_then(|_| self.reserve_ingredients(&order))
+        {
+            return self.set_order_status(order.id, OrderStatus::Rejected(e));
+        }
+        self.set_order_status(order.id, OrderStatus::Queued)?;
+        let reserved = order.clone();
+        if let Err(e) = queue.push(order, self.config.queue_policy, &self.clock) {
+            Self::release_reservation(
+                &reserved,
+                &self.coffee_container,
+                &self.cocoa_container,
+                &self.milk_container,
+            )?;
+            self.set_order_status(reserved.id, OrderStatus::Rejected(e))?;
+        }
+        Ok(())
+    }
+
+    /// Registra la función que se llamará con el estado final del pedido, cuando se complete o se rechace.
+    /// Se debe registrar antes de recibir el pedido con [`CoffeeMaker::receive_order`].
+    /// Si el lock está envenenado devuelve [`CustomError::PoisonedLock`]
+    pub fn on_order_finished(&self, id: u32, listener: StatusListener) -> Result<(), CustomError> {
+        self.status_listeners.lock()?.insert(id, listener);
+        Ok(())
+    }
+
+    /// Reemplaza con qué se imprimen las estadísticas, por ejemplo para escribirlas en un archivo.
+    /// Por defecto se imprimen por la salida estándar en el formato de [`Config::stats_format`].
+    /// Si el lock está envenenado devuelve [`CustomError::PoisonedLock`]
+    pub fn set_stats_renderer(&self, renderer: Box<dyn StatsRenderer>) -> Result<(), CustomError> {
+        *self.stats_renderer.lock()? = renderer;
+        Ok(())
+    }
+
+    /// Actualiza el estado del pedido en las estadísticas y, si es final, avisa a quien lo esté esperando.
+    /// Si el pedido no se pudo terminar publica [`MachineEvent::OrderRejected`].
+    /// Si algún lock está envenenado devuelve [`CustomError::PoisonedLock`]
+    fn set_order_status(&self, id: u32, status: OrderStatus) -> Result<(), CustomError> {
+        self.statistics_values.lock()?.set_status(status);
+        if matches!(status, OrderStatus::Queued | OrderStatus::InProgress) {
+            return Ok(());
+        }
+        if let OrderStatus::Rejected(error) | OrderStatus::PartiallyServed(error) = status {
+            self.events.publish(
+                self.clock.now(),
+                MachineEvent::OrderRejected {
+                    order_id: id,
+                    error,
+                    partially_served: matches!(status, OrderStatus::PartiallyServed(_)),
+                },
+            );
+        }
+        let listener = self.status_listeners.lock()?.remove(&id);
+        if let Some(listener) = listener {
+            listener(status);
+        }
+        Ok(())
+    }
+
+    /// Valida el archivo de pedidos sin prepararlos.
+    /// Imprime el error de cada línea inválida o pedido que supere la capacidad de algún contenedor
+    /// y devuelve la cantidad de pedidos válidos.
+    /// Errores:
+    /// * Si no se puede abrir el archivo fallará con error [`CustomError::CantOpenOrderFile`]
+    /// * Si alguna línea es inválida fallará con error [`CustomError::InvalidOrderValue`]
+    pub fn check_orders(&self, file_path: &str) -> Result<u32, CustomError> {
+        let lines = match file_reader::read_lines(file_path) {
+            Ok(lines) => lines,
             Err(e) => {
-                println!(
-                    "[ERROR] No se puedo abrir el archivo de ordenes correctamente: {:?}",
+                error!(
+                    "No se puedo abrir el archivo de ordenes correctamente: {:?}",
                     e
                 );
                 return Err(CustomError::CantOpenOrderFile);
             }
+        };
+        let (mut valid, mut invalid) = (0, 0);
+        let format = self
+            .config
+            .order_format
+            .unwrap_or_else(|| OrderFormat::from_path(file_path));
+        for (index, line) in lines.enumerate() {
+            match line
+                .map_err(|e| format!("{:?}", e))
+                .and_then(|line| self.parse_order(&line, valid, format))
+                .and_then(|order| order.validate(&self.config).map_err(|e| format!("{:?}", e)))
+            {
+                Ok(_) => valid += 1,
+                Err(e) => {
+                    error!("Pedido inválido en línea {:?}: {}", index + 1, e);
+                    invalid += 1;
+                }
+            }
         }
-        Ok(())
+        info!(
+            "Pedidos válidos: {:?}. Pedidos inválidos: {:?}.",
+            valid, invalid
+        );
+        if invalid > 0 {
+            return Err(CustomError::InvalidOrderValue);
+        }
+        Ok(valid)
     }
 
-    /// En un hilo nuevo intentará servir todos los ingredientes que correspondan con sus respectivos contenedores.
-    /// Si no puede utilizar uno, ira por otro ingrediente para luego volver y asi no perder tiempo.
-    /// Devuelve un [`JoinHandle`] que luego sera utilizado para finalizar el programa.
-    fn prepare_order(&self, mut order: Order) -> JoinHandle<()> {
-        let coffee_container_clone = self.coffee_container.clone();
-        let hot_water_container_clone = self.hot_water_container.clone();
-        let cocoa_container_clone = self.cocoa_container.clone();
-        let milk_container_clone = self.milk_container.clone();
-        let statistics_values_clone = self.statistics_values.clone();
+    /// En un hilo nuevo toma pedidos de la cola y los prepara de a uno con [`CoffeeMaker::prepare_order`],
+    /// hasta que la cola se cierre y no queden pedidos.
+    /// Devuelve un [`Handle`] que luego sera utilizado para finalizar el programa.
+    fn start_barista(&self, number: u32, queue: Arc<OrderQueue>) -> Handle<()> {
+        let coffee_maker = self.clone();
+        self.clock
+            .spawn_named(format!("barista-{}", number), move || loop {
+                match queue.pop(&coffee_maker.clock) {
+                    Ok(Some(order)) => coffee_maker.prepare_order(order),
+                    Ok(None) => break,
+                    Err(e) => {
+                        error!(
+                            "El barista {:?} no puede tomar más pedidos: {:?}",
+                            number, e
+                        );
+                        break;
+                    }
+                }
+            })
+    }
 
-        thread::spawn(move || {
-            let mut ready = false;
-            while !ready {
-                if order.ground_coffee > 0 {
-                    match Self::try_serve_ground_coffee(&mut order, coffee_container_clone.clone())
-                    {
-                        Ok(_) => {
-                            ready = order.check_if_ready();
-                        }
-                        Err(e) => {
-                            println!(
-                                "[ERROR] Pedido {:?} no podrá ser preparado: {:?}",
-                                order.id, e
-                            );
-                            break;
+    /// Intentará servir todos los ingredientes que correspondan con sus respectivos contenedores.
+    /// Si no puede utilizar uno, ira por otro ingrediente para luego volver y asi no perder tiempo.
+    /// Si en una vuelta no pudo servir ningún ingrediente porque todos los dispensadores estaban ocupados, el pedido se anota en la
+    /// espera de los contenedores que le faltan (ver [`CoffeeMaker::join_dispenser_queues`]) y el barista queda
+    /// bloqueado hasta que algún pedido libere un dispensador (ver [`CoffeeMaker::notify_dispenser_released`]), sin consumir procesador.
+    /// Al bloquearse publica [`MachineEvent::OrderWaiting`] con el primer ingrediente que le falta, si cambió desde la última vez.
+    /// Al terminar registra lo que esperó el pedido, en la cola y por dispensadores, según su prioridad.
+    /// Si algún ingrediente no se puede servir deja de prepararlo, libera lo que le quedaba reservado
+    /// y lo registra como rechazado, o como servido parcialmente si ya tenía algún ingrediente servido.
+    fn prepare_order(&self, mut order: Order) {
+        let _order_scope = logger::order_scope(order.id);
+        let _span = self.tracer.span(&self.clock, "order", "preparar pedido");
+        let requested = order.clone();
+        let mut failure = None;
+        let started = self.clock.now();
+        order.times.started = Some(started);
+        let waiting = Arc::new(AtomicBool::new(false));
+        let mut blocked = 0;
+        let mut waiting_for = None;
+        if let Err(e) = self.set_order_status(order.id, OrderStatus::InProgress) {
+            error!(
+                "No se pudo actualizar el estado del pedido {:?}: {:?}",
+                order.id, e
+            );
+        }
+        let mut ready = order.check_if_ready();
+        while !ready {
+            let seen_releases = match self.dispensers_released.0.lock() {
+                Ok(releases) => *releases,
+                Err(_) => {
+                    failure = Some(CustomError::PoisonedLock);
+                    break;
+                }
+            };
+            let mut served = false;
+
+            if order.ground_coffee > 0 {
+                match Self::try_serve_ground_coffee(
+                    &mut order,
+                    self.coffee_container.clone(),
+                    &self.config,
+                    &self.clock,
+                    &self.events,
+                    &self.tracer,
+                    &self.dispensers_released,
+                ) {
+                    Ok(_) => {
+                        if order.ground_coffee == 0 {
+                            served = true;
+                            Self::notify_dispenser_released(&self.dispensers_released);
                         }
+                        ready = order.check_if_ready();
+                    }
+                    Err(e) => {
+                        failure = Some(e);
+                        break;
                     }
                 }
+            }
 
-                if order.hot_water > 0 {
-                    match Self::try_serve_hot_water(&mut order, hot_water_container_clone.clone()) {
-                        Ok(_) => {
-                            ready = order.check_if_ready();
-                        }
-                        Err(e) => {
-                            println!(
-                                "[ERROR] No se pudo servir agua caliente para el pedido: {:?}.",
-                                order.id
-                            );
-                            println!(
-                                "[ERROR] Pedido {:?} no podrá ser preparado: {:?}",
-                                order.id, e
-                            );
-                            break;
+            if order.hot_water > 0 {
+                match Self::try_serve_hot_water(
+                    &mut order,
+                    self.hot_water_container.clone(),
+                    &self.config,
+                    &self.clock,
+                    &self.events,
+                    &self.tracer,
+                    &self.dispensers_released,
+                ) {
+                    Ok(_) => {
+                        if order.hot_water == 0 {
+                            served = true;
+                            Self::notify_dispenser_released(&self.dispensers_released);
                         }
+                        ready = order.check_if_ready();
+                    }
+                    Err(e) => {
+                        failure = Some(e);
+                        break;
                     }
                 }
+            }
 
-                if order.cocoa > 0 {
-                    match Self::try_serve_cocoa(&mut order, cocoa_container_clone.clone()) {
-                        Ok(_) => {
-                            ready = order.check_if_ready();
-                        }
-                        Err(e) => {
-                            println!(
-                                "[ERROR] Pedido {:?} no podrá ser preparado: {:?}",
-                                order.id, e
-                            );
-                            break;
+            if order.cocoa > 0 {
+                match Self::try_serve_cocoa(
+                    &mut order,
+                    self.cocoa_container.clone(),
+                    &self.config,
+                    &self.clock,
+                    &self.events,
+                    &self.tracer,
+                    &self.dispensers_released,
+                ) {
+                    Ok(_) => {
+                        if order.cocoa == 0 {
+                            served = true;
+                            Self::notify_dispenser_released(&self.dispensers_released);
                         }
+                        ready = order.check_if_ready();
+                    }
+                    Err(e) => {
+                        failure = Some(e);
+                        break;
                     }
                 }
+            }
 
-                if order.milk_foam > 0 {
-                    match Self::try_serve_milk_foam(&mut order, milk_container_clone.clone()) {
-                        Ok(_) => {
-                            ready = order.check_if_ready();
-                        }
-                        Err(e) => {
-                            println!(
-                                "[ERROR] Pedido {:?} no podrá ser preparado: {:?}",
-                                order.id, e
-                            );
-                            break;
+            if order.milk_foam > 0 {
+                match Self::try_serve_milk_foam(
+                    &mut order,
+                    self.milk_container.clone(),
+                    &self.config,
+                    &self.clock,
+                    &self.events,
+                    &self.tracer,
+                    &self.dispensers_released,
+                ) {
+                    Ok(_) => {
+                        if order.milk_foam == 0 {
+                            served = true;
+                            Self::notify_dispenser_released(&self.dispensers_released);
                         }
+                        ready = order.check_if_ready();
+                    }
+                    Err(e) => {
+                        failure = Some(e);
+                        break;
                     }
                 }
             }
-            match statistics_values_clone.lock() {
-                Ok(mut statistics_values_lock) => {
-                    statistics_values_lock.orders_served += 1;
+
+            if !ready && !served {
+                if let Some(ingredient) = order
+                    .next_ingredient()
+                    .filter(|next| Some(*next) != waiting_for)
+                {
+                    waiting_for = Some(ingredient);
+                    self.events.publish(
+                        self.clock.now(),
+                        MachineEvent::OrderWaiting {
+                            order_id: order.id,
+                            ingredient,
+                        },
+                    );
                 }
-                Err(e) => {
-                    println!("[ERROR] No se pudo entregar el pedido finalizado: {:?}", e);
+                if let Err(e) = self.join_dispenser_queues(&order, &waiting) {
+                    failure = Some(e);
+                    break;
+                }
+                let blocked_since = self.clock.now();
+                let (releases_lock, releases_cvar) = &*self.dispensers_released;
+                if let Err(e) =
+                    self.tracer
+                        .measure(&self.clock, "wait", "esperar dispensador", || {
+                            self.clock
+                                .wait_while(releases_lock, releases_cvar, |releases| {
+                                    self.dispenser_checks.fetch_add(1, Ordering::Relaxed);
+                                    *releases == seen_releases
+                                })
+                        })
+                {
+                    failure = Some(e);
+                    break;
                 }
+                blocked += self.clock.now() - blocked_since;
             }
-            println!("[DEBUG] Pedido listo id:{:?}", order.id);
-        })
+        }
+        if failure.is_some() {
+            // Si el pedido tenía un dispensador tomado ya se devolvió al contenedor: que lo intenten los que esperan.
+            Self::notify_dispenser_released(&self.dispensers_released);
+        }
+        if self.leave_dispenser_queues(order.id).is_err() {
+            error!(
+                "No se pudo quitar al pedido {:?} de la espera de dispensadores.",
+                order.id
+            );
+        }
+        if failure.is_some()
+            && Self::release_reservation(
+                &order,
+                &self.coffee_container,
+                &self.cocoa_container,
+                &self.milk_container,
+            )
+            .is_err()
+        {
+            error!(
+                "No se pudo liberar la reserva del pedido cancelado: {:?}",
+                order.id
+            );
+        }
+        let status = match failure {
+            None => OrderStatus::Completed,
+            Some(e) if order.has_served_any(&requested) => OrderStatus::PartiallyServed(e),
+            Some(e) => OrderStatus::Rejected(e),
+        };
+        if status == OrderStatus::Completed {
+            let completed = self.clock.now();
+            order.times.completed = Some(completed);
+            self.events.publish(
+                completed,
+                MachineEvent::OrderCompleted {
+                    order_id: order.id,
+                    reference: order.reference.clone(),
+                    received_at: order.received_at,
+                    started,
+                    first_ingredient: order.times.first_ingredient().unwrap_or(started),
+                    completed,
+                },
+            );
+        }
+        match self.statistics_values.lock() {
+            Ok(mut statistics_values) => {
+                statistics_values.record_wait(
+                    order.priority,
+                    started.saturating_sub(order.received_at) + blocked,
+                );
+                statistics_values.record_times(order.received_at, &order.times);
+            }
+            Err(e) => error!("No se pudo registrar la espera del pedido: {:?}", e),
+        }
+        if let Err(e) = self.set_order_status(order.id, status) {
+            error!("No se pudo entregar el pedido finalizado: {:?}", e);
+        }
+    }
+
+    /// Anota al pedido en la espera de los contenedores de los ingredientes que le faltan servir,
+    /// para que los dispensadores que se liberen le correspondan según su prioridad.
+    /// Si algún lock está envenenado devuelve [`CustomError::PoisonedLock`]
+    fn join_dispenser_queues(
+        &self,
+        order: &Order,
+        waiting: &Arc<AtomicBool>,
+    ) -> Result<(), CustomError> {
+        waiting.store(true, Ordering::SeqCst);
+        if order.ground_coffee > 0 {
+            self.coffee_container
+                .0
+                .lock()?
+                .dispenser_queue
+                .register(order, waiting);
+        }
+        if order.hot_water > 0 {
+            self.hot_water_container
+                .0
+                .lock()?
+                .dispenser_queue
+                .register(order, waiting);
+        }
+        if order.cocoa > 0 {
+            self.cocoa_container
+                .0
+                .lock()?
+                .dispenser_queue
+                .register(order, waiting);
+        }
+        if order.milk_foam > 0 {
+            self.milk_container
+                .0
+                .lock()?
+                .dispenser_queue
+                .register(order, waiting);
+        }
+        Ok(())
+    }
+
+    /// Quita al pedido de la espera de todos los contenedores, para que no les reserve dispensadores al terminar o cancelarse.
+    /// Si algún lock está envenenado devuelve [`CustomError::PoisonedLock`]
+    fn leave_dispenser_queues(&self, order_id: u32) -> Result<(), CustomError> {
+        self.coffee_container
+            .0
+            .lock()?
+            .dispenser_queue
+            .withdraw(order_id);
+        self.hot_water_container
+            .0
+            .lock()?
+            .dispenser_queue
+            .withdraw(order_id);
+        self.cocoa_container
+            .0
+            .lock()?
+            .dispenser_queue
+            .withdraw(order_id);
+        self.milk_container
+            .0
+            .lock()?
+            .dispenser_queue
+            .withdraw(order_id);
+        Ok(())
+    }
+
+    /// Avisa a los pedidos bloqueados esperando un dispensador que se liberó uno, para que vuelvan a intentar.
+    /// Si el lock está envenenado no avisa, los pedidos lo van a detectar al volver a esperar.
+    fn notify_dispenser_released(dispensers_released: &Arc<(Mutex<u64>, Condvar)>) {
+        let (releases_lock, releases_cvar) = &**dispensers_released;
+        if let Ok(mut releases) = releases_lock.lock() {
+            *releases += 1;
+        }
+        releases_cvar.notify_all();
+    }
+
+    /// Reserva todos los ingredientes del pedido antes de servir alguno, para no preparar bebidas a medias.
+    /// Cuenta lo que todavía se puede producir con los granos de café y la leche fría.
+    /// Si alguno no alcanza no reserva nada. El agua caliente no se reserva porque se toma de la red.
+    /// Los locks se toman siempre en el mismo orden (café, cacao, leche) para no generar deadlocks.
+    /// Errores:
+    /// * Si algún ingrediente no alcanza devuelve [`CustomError::InsufficientIngredients`]
+    /// * Si algún lock está envenenado devuelve [`CustomError::PoisonedLock`]
+    fn reserve_ingredients(&self, order: &Order) -> Result<(), CustomError> {
+        let mut coffee = self.coffee_container.0.lock()?;
+        let mut cocoa = self.cocoa_container.0.lock()?;
+        let mut milk = self.milk_container.0.lock()?;
+        if coffee.available() < order.ground_coffee
+            || cocoa.available() < order.cocoa
+            || milk.available() < order.milk_foam
+        {
+            error!(
+                "No hay suficientes ingredientes para realizar este pedido. Pedido: {:?}",
+                order.id
+            );
+            return Err(CustomError::InsufficientIngredients);
+        }
+        coffee.reserved += order.ground_coffee;
+        cocoa.reserved += order.cocoa;
+        milk.reserved += order.milk_foam;
+        Ok(())
+    }
+
+    /// Libera la reserva de los ingredientes que el pedido todavía no se sirvió, por ejemplo si se cancela.
+    /// Si algún lock está envenenado devuelve [`CustomError::PoisonedLock`]
+    fn release_reservation(
+        order: &Order,
+        coffee_container: &Arc<(Mutex<CoffeeContainer>, Condvar)>,
+        cocoa_container: &Arc<(Mutex<CocoaContainer>, Condvar)>,
+        milk_container: &Arc<(Mutex<MilkContainer>, Condvar)>,
+    ) -> Result<(), CustomError> {
+        let mut coffee = coffee_container.0.lock()?;
+        let mut cocoa = cocoa_container.0.lock()?;
+        let mut milk = milk_container.0.lock()?;
+        coffee.reserved = coffee.reserved.saturating_sub(order.ground_coffee);
+        cocoa.reserved = cocoa.reserved.saturating_sub(order.cocoa);
+        milk.reserved = milk.reserved.saturating_sub(order.milk_foam);
+        Ok(())
     }
 
-    /// Si el lock del contenedor esta disponible y el mismo tiene la capacidad para servirle café molido, le sirve.
-    /// Actualiza las referencias de disponibilidades y cantidades.
+    /// Si el contenedor tiene algún dispensador libre y la capacidad para servirle café molido, le sirve.
+    /// Si todos los dispensadores están ocupados, o los libres le corresponden a pedidos más urgentes que esperan en la
+    /// [`crate::structs::dispenser_queue::DispenserQueue`], vuelve sin servir para que el pedido pruebe con otro ingrediente.
+    /// La cantidad se descuenta del contenedor antes de servir, por lo que varios dispensadores
+    /// pueden servir en simultáneo compartiendo el mismo stock.
+    /// Actualiza las referencias de disponibilidades y cantidades y publica [`MachineEvent::IngredientServed`] en `events`,
+    /// antes [`MachineEvent::OrderWaiting`] si tiene que esperar a que alcance el stock.
+    /// Si al tomar el dispensador el pedido dejó de esperar en otros contenedores, avisa por `dispensers_released`
+    /// a los pedidos que le cedían esos dispensadores.
     ///
-    /// En caso de que el lock del contenedor de café esté envenenado en la segunda oportunidad, devuevle [`CustomError::PoisonedLock`]
+    /// En caso de que el lock del contenedor de café esté envenenado, devuevle [`CustomError::PoisonedLock`]
     /// Si el contenedor no tiene la capacidad, entre su disponibilidad y reservas, de satisfacer el pedido, devuelve [`CustomError::InsufficientIngredients`]
+    /// Si el pedido supera la capacidad del contenedor, devuelve [`CustomError::OrderExceedsCapacity`]
     fn try_serve_ground_coffee(
         order: &mut Order,
         coffee_container: Arc<(Mutex<CoffeeContainer>, Condvar)>,
+        config: &Config,
+        clock: &Clock,
+        events: &EventBus,
+        tracer: &Tracer,
+        dispensers_released: &Arc<(Mutex<u64>, Condvar)>,
     ) -> Result<(), CustomError> {
         if order.ground_coffee == 0 {
             return Ok(());
         }
+        if order.ground_coffee > config.ground_coffee_capacity {
+            return Err(CustomError::OrderExceedsCapacity);
+        }
         let (coffee_lock, coffee_cvar) = &*coffee_container;
-        match coffee_lock.try_lock() {
-            Ok(temp_lock) => {
-                if temp_lock.coffee_grains_container + temp_lock.ground_coffee_container
-                    < order.ground_coffee
-                {
-                    println!(
-                        "[ERROR] No hay suficiente café para realizar este pedido. Pedido: {:?}",
-                        order.id
-                    );
-                    drop(temp_lock);
-                    return Err(CustomError::InsufficientIngredients);
-                }
-                drop(temp_lock);
-                if let Ok(mut state) =
-                    coffee_cvar.wait_while(coffee_lock.lock()?, |coffee_container| {
-                        coffee_container.ground_coffee_container < order.ground_coffee
-                    })
-                {
-                    thread::sleep(Duration::from_millis(SERVE_COFFEE_TIME));
-                    state.ground_coffee_container -= order.ground_coffee;
-                    state.ground_coffee_used += order.ground_coffee;
-                    println!("[DEBUG] Café servido Pedido:{:?}", order.id);
-                    coffee_cvar.notify_all();
-                    order.ground_coffee = 0;
-                }
-            }
-            Err(_) => {}
-        };
+        let mut temp_lock = tracer.measure(clock, "lock", "lock café", || coffee_lock.lock())?;
+        if temp_lock.coffee_grains_container + temp_lock.ground_coffee_container
+            < order.ground_coffee
+        {
+            error!(
+                "No hay suficiente café para realizar este pedido. Pedido: {:?}",
+                order.id
+            );
+            return Err(CustomError::InsufficientIngredients);
+        }
+        order.times.ground_coffee.arrive(clock.now());
+        temp_lock.dispenser_queue.arrive(order);
+        if temp_lock.free_dispensers == 0
+            || !temp_lock.dispenser_queue.may_take(
+                order,
+                temp_lock.free_dispensers,
+                clock.now(),
+                config.aging_time,
+            )
+        {
+            return Ok(());
+        }
+        let dispenser = DispenserGuard::take(&coffee_container, &mut temp_lock, |container| {
+            &mut container.free_dispensers
+        });
+        let stopped_waiting = temp_lock.dispenser_queue.take(order.id);
+        order.times.ground_coffee.dispensed = Some(clock.now());
+        temp_lock.waiting.push(order.ground_coffee);
+        let must_wait =
+            temp_lock.refilling || temp_lock.ground_coffee_container < order.ground_coffee;
+        drop(temp_lock);
+        coffee_cvar.notify_all();
+        if stopped_waiting {
+            Self::notify_dispenser_released(dispensers_released);
+        }
+        if must_wait {
+            events.publish(
+                clock.now(),
+                MachineEvent::OrderWaiting {
+                    order_id: order.id,
+                    ingredient: Ingredient::GroundCoffee,
+                },
+            );
+        }
+
+        let mut state = tracer.measure(clock, "wait", "esperar café molido", || {
+            clock.wait_while(coffee_lock, coffee_cvar, |coffee_container| {
+                coffee_container.refilling
+                    || coffee_container.ground_coffee_container < order.ground_coffee
+            })
+        })?;
+        state.stop_waiting(order.ground_coffee);
+        state.ground_coffee_container -= order.ground_coffee;
+        state.ground_coffee_used += order.ground_coffee;
+        state.reserved = state.reserved.saturating_sub(order.ground_coffee);
+        coffee_cvar.notify_all();
+        drop(state);
+
+        tracer.measure(clock, "serve", "servir café molido", || {
+            clock.sleep(config.action_time(
+                config.serve_coffee_time,
+                "servir_cafe",
+                order.id.into(),
+            ))
+        });
+        events.publish(
+            clock.now(),
+            MachineEvent::IngredientServed {
+                order_id: order.id,
+                ingredient: Ingredient::GroundCoffee,
+                amount: order.ground_coffee,
+            },
+        );
+        order.ground_coffee = 0;
+        order.times.ground_coffee.served = Some(clock.now());
+
+        drop(dispenser);
         Ok(())
     }
 
-    /// Si el lock del contenedor esta disponible y el mismo tiene la capacidad para servirle agua caliente, le sirve.
-    /// Actualiza las referencias de disponibilidades y cantidades.
+    /// Si el contenedor tiene algún dispensador libre, le sirve agua caliente.
+    /// Si todos los dispensadores están ocupados, o los libres le corresponden a pedidos más urgentes que esperan en la
+    /// [`crate::structs::dispenser_queue::DispenserQueue`], vuelve sin servir para que el pedido pruebe con otro ingrediente.
+    /// Actualiza las referencias de disponibilidades y cantidades y publica [`MachineEvent::IngredientServed`] en `events`,
+    /// antes [`MachineEvent::OrderWaiting`] si tiene que esperar a que alcance el stock.
+    /// Si al tomar el dispensador el pedido dejó de esperar en otros contenedores, avisa por `dispensers_released`
+    /// a los pedidos que le cedían esos dispensadores.
     ///
-    /// En caso de que el lock del contenedor de agua caliente esté envenenado en la segunda oportunidad, devuevle [`CustomError::PoisonedLock`]
+    /// En caso de que el lock del contenedor de agua caliente esté envenenado, devuevle [`CustomError::PoisonedLock`]
+    /// Si el pedido supera la capacidad del contenedor, devuelve [`CustomError::OrderExceedsCapacity`]
     fn try_serve_hot_water(
         order: &mut Order,
         hot_water_container: Arc<(Mutex<HotWaterContainer>, Condvar)>,
+        config: &Config,
+        clock: &Clock,
+        events: &EventBus,
+        tracer: &Tracer,
+        dispensers_released: &Arc<(Mutex<u64>, Condvar)>,
     ) -> Result<(), CustomError> {
         if order.hot_water == 0 {
             return Ok(());
         }
+        if order.hot_water > config.hot_water_capacity {
+            return Err(CustomError::OrderExceedsCapacity);
+        }
         let (h_w_lock, h_w_cvar) = &*hot_water_container;
-        match h_w_lock.try_lock() {
-            Ok(temp_lock) => {
-                drop(temp_lock);
-                if let Ok(mut state) = h_w_cvar.wait_while(h_w_lock.lock()?, |h_w_container| {
-                    h_w_container.hot_water < order.hot_water
-                }) {
-                    thread::sleep(Duration::from_millis(SERVE_HOT_WATER_TIME));
-                    state.hot_water -= order.hot_water;
-                    state.used += order.hot_water;
-                    println!("[DEBUG] Agua caliente servida Pedido:{:?}", order.id);
-                    h_w_cvar.notify_all();
-                    order.hot_water = 0;
-                }
-            }
-            Err(_) => {}
-        };
+        let mut temp_lock =
+            tracer.measure(clock, "lock", "lock agua caliente", || h_w_lock.lock())?;
+        order.times.hot_water.arrive(clock.now());
+        temp_lock.dispenser_queue.arrive(order);
+        if temp_lock.free_dispensers == 0
+            || !temp_lock.dispenser_queue.may_take(
+                order,
+                temp_lock.free_dispensers,
+                clock.now(),
+                config.aging_time,
+            )
+        {
+            return Ok(());
+        }
+        let dispenser = DispenserGuard::take(&hot_water_container, &mut temp_lock, |container| {
+            &mut container.free_dispensers
+        });
+        let stopped_waiting = temp_lock.dispenser_queue.take(order.id);
+        order.times.hot_water.dispensed = Some(clock.now());
+        temp_lock.waiting.push(order.hot_water);
+        let must_wait = temp_lock.refilling || temp_lock.hot_water < order.hot_water;
+        drop(temp_lock);
+        h_w_cvar.notify_all();
+        if stopped_waiting {
+            Self::notify_dispenser_released(dispensers_released);
+        }
+        if must_wait {
+            events.publish(
+                clock.now(),
+                MachineEvent::OrderWaiting {
+                    order_id: order.id,
+                    ingredient: Ingredient::HotWater,
+                },
+            );
+        }
+
+        let mut state = tracer.measure(clock, "wait", "esperar agua caliente", || {
+            clock.wait_while(h_w_lock, h_w_cvar, |h_w_container| {
+                h_w_container.refilling || h_w_container.hot_water < order.hot_water
+            })
+        })?;
+        state.stop_waiting(order.hot_water);
+        state.hot_water -= order.hot_water;
+        state.used += order.hot_water;
+        h_w_cvar.notify_all();
+        drop(state);
+
+        tracer.measure(clock, "serve", "servir agua caliente", || {
+            clock.sleep(config.action_time(
+                config.serve_hot_water_time,
+                "servir_agua",
+                order.id.into(),
+            ))
+        });
+        events.publish(
+            clock.now(),
+            MachineEvent::IngredientServed {
+                order_id: order.id,
+                ingredient: Ingredient::HotWater,
+                amount: order.hot_water,
+            },
+        );
+        order.hot_water = 0;
+        order.times.hot_water.served = Some(clock.now());
+
+        drop(dispenser);
         Ok(())
     }
 
-    /// Si el lock del contenedor esta disponible y el mismo tiene la capacidad para servirle cacao, le sirve.
-    /// Actualiza las referencias de disponibilidades y cantidades.
-    /// Al llegar al [`X%`] de la disponibilidad de cacao se alerta por pantalla.
+    /// Si el contenedor tiene algún dispensador libre y la capacidad para servirle cacao, le sirve.
+    /// Si todos los dispensadores están ocupados, o los libres le corresponden a pedidos más urgentes que esperan en la
+    /// [`crate::structs::dispenser_queue::DispenserQueue`], vuelve sin servir para que el pedido pruebe con otro ingrediente.
+    /// Actualiza las referencias de disponibilidades y cantidades y publica [`MachineEvent::IngredientServed`] en `events`,
+    /// antes [`MachineEvent::OrderWaiting`] si tiene que esperar a que alcance el stock.
+    /// Si al tomar el dispensador el pedido dejó de esperar en otros contenedores, avisa por `dispensers_released`
+    /// a los pedidos que le cedían esos dispensadores.
+    /// Al llegar al [`Config::alert_threshold`]% de la disponibilidad de cacao se publica [`MachineEvent::LowStock`].
     ///
     /// En caso de que el lock del contenedor de cacao este envenenado en la segunda oportunidad, devuevle [`CustomError::PoisonedLock`]
     /// Si el contenedor no tiene la capacidad de satisfacer el pedido, devuelve [`CustomError::InsufficientIngredients`]
+    /// Si el pedido supera la capacidad del contenedor, devuelve [`CustomError::OrderExceedsCapacity`]
     fn try_serve_cocoa(
         order: &mut Order,
         cocoa_container: Arc<(Mutex<CocoaContainer>, Condvar)>,
+        config: &Config,
+        clock: &Clock,
+        events: &EventBus,
+        tracer: &Tracer,
+        dispensers_released: &Arc<(Mutex<u64>, Condvar)>,
     ) -> Result<(), CustomError> {
         if order.cocoa == 0 {
             return Ok(());
         }
+        if order.cocoa > config.cocoa_capacity {
+            return Err(CustomError::OrderExceedsCapacity);
+        }
         let (cocoa_lock, cococa_cvar) = &*cocoa_container;
-        match cocoa_lock.try_lock() {
-            Ok(temp_lock) => {
-                if temp_lock.cocoa < order.cocoa {
-                    println!(
-                        "[ERROR] No hay suficiente cacao para realizar este pedido. Pedido: {:?}",
-                        order.id
-                    );
-                    drop(temp_lock);
-                    return Err(CustomError::InsufficientIngredients);
-                }
-                drop(temp_lock);
-                if let Ok(mut state) = cococa_cvar
-                    .wait_while(cocoa_lock.lock()?, |cocoa_container| {
-                        cocoa_container.cocoa < order.cocoa
-                    })
-                {
-                    thread::sleep(Duration::from_millis(SERVE_COCOA_TIME));
-                    state.cocoa -= order.cocoa;
-                    state.used += order.cocoa;
-                    let capacity_percentage = X as f32 / 100.0 * C as f32;
-                    if (state.cocoa as f32) < capacity_percentage {
-                        println!("[WARN] El contenedor de cacao se encuentra por debajo de {:?}% de su capacidad", X);
-                    }
-                    println!("[DEBUG] Cacao servido Pedido:{:?}", order.id);
-                    cococa_cvar.notify_all();
-                    order.cocoa = 0;
-                }
-            }
-            Err(_) => {}
-        };
+        let mut temp_lock = tracer.measure(clock, "lock", "lock cacao", || cocoa_lock.lock())?;
+        if temp_lock.cocoa < order.cocoa {
+            error!(
+                "No hay suficiente cacao para realizar este pedido. Pedido: {:?}",
+                order.id
+            );
+            return Err(CustomError::InsufficientIngredients);
+        }
+        order.times.cocoa.arrive(clock.now());
+        temp_lock.dispenser_queue.arrive(order);
+        if temp_lock.free_dispensers == 0
+            || !temp_lock.dispenser_queue.may_take(
+                order,
+                temp_lock.free_dispensers,
+                clock.now(),
+                config.aging_time,
+            )
+        {
+            return Ok(());
+        }
+        let dispenser = DispenserGuard::take(&cocoa_container, &mut temp_lock, |container| {
+            &mut container.free_dispensers
+        });
+        let stopped_waiting = temp_lock.dispenser_queue.take(order.id);
+        order.times.cocoa.dispensed = Some(clock.now());
+        let must_wait = temp_lock.cocoa < order.cocoa;
+        drop(temp_lock);
+        if stopped_waiting {
+            Self::notify_dispenser_released(dispensers_released);
+        }
+        if must_wait {
+            events.publish(
+                clock.now(),
+                MachineEvent::OrderWaiting {
+                    order_id: order.id,
+                    ingredient: Ingredient::Cocoa,
+                },
+            );
+        }
+
+        let mut state = tracer.measure(clock, "wait", "esperar cacao", || {
+            clock.wait_while(cocoa_lock, cococa_cvar, |cocoa_container| {
+                cocoa_container.cocoa < order.cocoa
+            })
+        })?;
+        state.cocoa -= order.cocoa;
+        state.used += order.cocoa;
+        state.reserved = state.reserved.saturating_sub(order.cocoa);
+        let capacity_percentage =
+            config.alert_threshold as f32 / 100.0 * config.cocoa_capacity as f32;
+        if (state.cocoa as f32) < capacity_percentage {
+            events.publish(
+                clock.now(),
+                MachineEvent::LowStock {
+                    ingredient: Ingredient::Cocoa,
+                    available: state.cocoa,
+                    threshold: config.alert_threshold,
+                },
+            );
+        }
+        cococa_cvar.notify_all();
+        drop(state);
+
+        tracer.measure(clock, "serve", "servir cacao", || {
+            clock.sleep(config.action_time(
+                config.serve_cocoa_time,
+                "servir_cacao",
+                order.id.into(),
+            ))
+        });
+        events.publish(
+            clock.now(),
+            MachineEvent::IngredientServed {
+                order_id: order.id,
+                ingredient: Ingredient::Cocoa,
+                amount: order.cocoa,
+            },
+        );
+        order.cocoa = 0;
+        order.times.cocoa.served = Some(clock.now());
+
+        drop(dispenser);
         Ok(())
     }
 
-    /// Si el lock del contenedor esta disponible y el mismo tiene la capacidad para servirle espuma de leche, le sirve.
-    /// Actualiza las referencias de disponibilidades y cantidades.
+    /// Si el contenedor tiene algún dispensador libre y la capacidad para servirle espuma de leche, le sirve.
+    /// Si todos los dispensadores están ocupados, o los libres le corresponden a pedidos más urgentes que esperan en la
+    /// [`crate::structs::dispenser_queue::DispenserQueue`], vuelve sin servir para que el pedido pruebe con otro ingrediente.
+    /// Actualiza las referencias de disponibilidades y cantidades y publica [`MachineEvent::IngredientServed`] en `events`,
+    /// antes [`MachineEvent::OrderWaiting`] si tiene que esperar a que alcance el stock.
+    /// Si al tomar el dispensador el pedido dejó de esperar en otros contenedores, avisa por `dispensers_released`
+    /// a los pedidos que le cedían esos dispensadores.
     ///
-    /// En caso de que el lock del contenedor de leche esté envenenado en la segunda oportunidad, devuevle [`CustomError::PoisonedLock`]
+    /// En caso de que el lock del contenedor de leche esté envenenado, devuevle [`CustomError::PoisonedLock`]
     /// Si el contenedor no tiene la capacidad, entre su disponibilidad y reservas, de satisfacer el pedido, devuelve [`CustomError::InsufficientIngredients`]
+    /// Si el pedido supera la capacidad del contenedor, devuelve [`CustomError::OrderExceedsCapacity`]
     fn try_serve_milk_foam(
         order: &mut Order,
         milk_container: Arc<(Mutex<MilkContainer>, Condvar)>,
+        config: &Config,
+        clock: &Clock,
+        events: &EventBus,
+        tracer: &Tracer,
+        dispensers_released: &Arc<(Mutex<u64>, Condvar)>,
     ) -> Result<(), CustomError> {
         if order.milk_foam == 0 {
             return Ok(());
         }
+        if order.milk_foam > config.milk_foam_capacity {
+            return Err(CustomError::OrderExceedsCapacity);
+        }
         let (milk_lock, milk_cvar) = &*milk_container;
-        match milk_lock.try_lock() {
-            Ok(temp_lock) => {
-                if temp_lock.milk_foam_container + temp_lock.cold_milk_container < order.milk_foam {
-                    println!(
-                        "[ERROR] No hay suficiente leche para realizar este pedido. Pedido: {:?}",
-                        order.id
-                    );
-                    drop(temp_lock);
-                    return Err(CustomError::InsufficientIngredients);
-                }
-                drop(temp_lock);
-                if let Ok(mut state) = milk_cvar.wait_while(milk_lock.lock()?, |milk_container| {
-                    milk_container.milk_foam_container < order.milk_foam
-                }) {
-                    thread::sleep(Duration::from_millis(SERVE_MILK_FOAM_TIME));
-                    state.milk_foam_container -= order.milk_foam;
-                    state.milk_foam_used += order.milk_foam;
-                    println!("[DEBUG] Espuma de leche servida Pedido:{:?}", order.id);
-                    milk_cvar.notify_all();
-                    order.milk_foam = 0;
-                }
-            }
-            Err(_) => {}
-        };
+        let mut temp_lock = tracer.measure(clock, "lock", "lock leche", || milk_lock.lock())?;
+        if temp_lock.milk_foam_container + temp_lock.cold_milk_container < order.milk_foam {
+            error!(
+                "No hay suficiente leche para realizar este pedido. Pedido: {:?}",
+                order.id
+            );
+            return Err(CustomError::InsufficientIngredients);
+        }
+        order.times.milk_foam.arrive(clock.now());
+        temp_lock.dispenser_queue.arrive(order);
+        if temp_lock.free_dispensers == 0
+            || !temp_lock.dispenser_queue.may_take(
+                order,
+                temp_lock.free_dispensers,
+                clock.now(),
+                config.aging_time,
+            )
+        {
+            return Ok(());
+        }
+        let dispenser = DispenserGuard::take(&milk_container, &mut temp_lock, |container| {
+            &mut container.free_dispensers
+        });
+        let stopped_waiting = temp_lock.dispenser_queue.take(order.id);
+        order.times.milk_foam.dispensed = Some(clock.now());
+        temp_lock.waiting.push(order.milk_foam);
+        let must_wait = temp_lock.refilling || temp_lock.milk_foam_container < order.milk_foam;
+        drop(temp_lock);
+        milk_cvar.notify_all();
+        if stopped_waiting {
+            Self::notify_dispenser_released(dispensers_released);
+        }
+        if must_wait {
+            events.publish(
+                clock.now(),
+                MachineEvent::OrderWaiting {
+                    order_id: order.id,
+                    ingredient: Ingredient::MilkFoam,
+                },
+            );
+        }
+
+        let mut state = tracer.measure(clock, "wait", "esperar espuma de leche", || {
+            clock.wait_while(milk_lock, milk_cvar, |milk_container| {
+                milk_container.refilling || milk_container.milk_foam_container < order.milk_foam
+            })
+        })?;
+        state.stop_waiting(order.milk_foam);
+        state.milk_foam_container -= order.milk_foam;
+        state.milk_foam_used += order.milk_foam;
+        state.reserved = state.reserved.saturating_sub(order.milk_foam);
+        milk_cvar.notify_all();
+        drop(state);
+
+        tracer.measure(clock, "serve", "servir espuma de leche", || {
+            clock.sleep(config.action_time(
+                config.serve_milk_foam_time,
+                "servir_espuma",
+                order.id.into(),
+            ))
+        });
+        events.publish(
+            clock.now(),
+            MachineEvent::IngredientServed {
+                order_id: order.id,
+                ingredient: Ingredient::MilkFoam,
+                amount: order.milk_foam,
+            },
+        );
+        order.milk_foam = 0;
+        order.times.milk_foam.served = Some(clock.now());
+
+        drop(dispenser);
         Ok(())
     }
 
@@ -418,8 +1254,8 @@ impl CoffeeMaker {
                 coffee_lock.shutdown = true;
             }
             Err(e) => {
-                println!(
-                    "[ERROR] No se pudo obtener lock para apagar contenedor de café: {:?}",
+                error!(
+                    "No se pudo obtener lock para apagar contenedor de café: {:?}",
                     e
                 );
                 return Err(CustomError::PoisonedLock);
@@ -433,8 +1269,8 @@ impl CoffeeMaker {
                 milk_lock.shutdown = true;
             }
             Err(e) => {
-                println!(
-                    "[ERROR] No se pudo obtener lock para apagar contenedor de leche: {:?}",
+                error!(
+                    "No se pudo obtener lock para apagar contenedor de leche: {:?}",
                     e
                 );
                 return Err(CustomError::PoisonedLock);
@@ -448,8 +1284,8 @@ impl CoffeeMaker {
                 water_lock.shutdown = true;
             }
             Err(e) => {
-                println!(
-                    "[ERROR] No se pudo obtener lock para apagar contenedor de agua: {:?}",
+                error!(
+                    "No se pudo obtener lock para apagar contenedor de agua: {:?}",
                     e
                 );
                 return Err(CustomError::PoisonedLock);
@@ -463,8 +1299,8 @@ impl CoffeeMaker {
                 s_v_lock.shutdown = true;
             }
             Err(e) => {
-                println!(
-                    "[ERROR] No se pudo obtener lock para apagar reproductor de estadísticas: {:?}",
+                error!(
+                    "No se pudo obtener lock para apagar reproductor de estadísticas: {:?}",
                     e
                 );
                 return Err(CustomError::PoisonedLock);
@@ -474,136 +1310,56 @@ impl CoffeeMaker {
         Ok(())
     }
 
-    /// Se recolectan y mustran las estadísticas cada [`TIME_TO_STATS`] milisegundos.
-    /// Si algún lock falla, se continúa el ciclo por lo que no se imprimirán estadísticas esta vez, sí la siguiente.
-    fn show_statistics(
-        coffee_container: Arc<(Mutex<CoffeeContainer>, Condvar)>,
-        hot_water_container: Arc<(Mutex<HotWaterContainer>, Condvar)>,
-        cocoa_container: Arc<(Mutex<CocoaContainer>, Condvar)>,
-        milk_container: Arc<(Mutex<MilkContainer>, Condvar)>,
-        statistics_values: Arc<Mutex<StatisticsValues>>,
-    ) -> Result<(), CustomError> {
-        loop {
-            thread::sleep(Duration::from_millis(TIME_TO_STATS));
-            let (
-                grains_used,
-                cold_milk_used,
-                cocoa_used,
-                coffee_used,
-                foam_used,
-                water_used,
-                grains,
-                cold_milk,
-                cocoa,
-                coffee,
-                foam,
-                water,
-                orders_served,
+    /// Recolecta las estadísticas actuales de la cafetera en una toma consistente y verifica los invariantes de los
+    /// contenedores ([`StatsSnapshot::invariant_violations`]), informando por pantalla los que no se cumplen.
+    /// Si algún lock está envenenado devuelve [`CustomError::PoisonedLock`]
+    pub fn snapshot(&self) -> Result<StatsSnapshot, CustomError> {
+        let snapshot = StatsSnapshot::collect(
+            &self.coffee_container,
+            &self.hot_water_container,
+            &self.cocoa_container,
+            &self.milk_container,
+            &self.statistics_values,
+            self.clock.now(),
+        )?;
+        for violation in snapshot.invariant_violations(&self.config) {
+            error!(
+                "Invariante violado en las estadísticas de los {:?} ms: {}",
+                snapshot.time, violation
             );
+        }
+        Ok(snapshot)
+    }
 
-            let (coffee_lock, coffee_cvar) = &*coffee_container;
-            match coffee_lock.lock() {
-                Ok(coffee_lock) => {
-                    grains_used = coffee_lock.coffee_grains_used;
-                    coffee_used = coffee_lock.ground_coffee_used;
-                    grains = coffee_lock.coffee_grains_container;
-                    coffee = coffee_lock.ground_coffee_container;
-                }
-                Err(e) => {
-                    println!("[ERROR] No se pudieron obtener las estadísticas: {:?}", e);
-                    continue;
-                }
-            }
-            coffee_cvar.notify_all();
-
-            let (h_w_lock, h_w_cvar) = &*hot_water_container;
-            match h_w_lock.lock() {
-                Ok(h_w_lock) => {
-                    water_used = h_w_lock.used;
-                    water = h_w_lock.hot_water;
-                }
+    /// Se recolectan las estadísticas en un [`StatsSnapshot`] cada [`Config::time_to_stats`] milisegundos y se imprimen
+    /// con el [`StatsRenderer`] de la cafetera, por defecto el del formato indicado por [`Config::stats_format`].
+    /// Si algún lock falla, se continúa el ciclo por lo que no se imprimirán estadísticas esta vez, sí la siguiente.
+    fn show_statistics(&self) -> Result<(), CustomError> {
+        loop {
+            self.clock
+                .sleep(Duration::from_millis(self.config.time_to_stats));
+            let shutdown = match self.statistics_values.lock() {
+                Ok(statistics_values_lock) => statistics_values_lock.shutdown,
                 Err(e) => {
-                    println!("[ERROR] No se pudieron obtener las estadísticas: {:?}", e);
+                    error!("No se pudieron obtener las estadísticas: {:?}", e);
                     continue;
                 }
-            }
-            h_w_cvar.notify_all();
-
-            let (cocoa_lock, cocoa_cvar) = &*cocoa_container;
-            match cocoa_lock.lock() {
-                Ok(cocoa_lock) => {
-                    cocoa_used = cocoa_lock.used;
-                    cocoa = cocoa_lock.cocoa;
-                }
+            };
+            let snapshot = match self.snapshot() {
+                Ok(snapshot) => snapshot,
                 Err(e) => {
-                    println!("[ERROR] No se pudieron obtener las estadísticas: {:?}", e);
+                    error!("No se pudieron obtener las estadísticas: {:?}", e);
                     continue;
                 }
-            }
-            cocoa_cvar.notify_all();
-
-            let (milk_lock, milk_cvar) = &*milk_container;
-            match milk_lock.lock() {
-                Ok(milk_lock) => {
-                    cold_milk_used = milk_lock.cold_milk_used;
-                    foam_used = milk_lock.milk_foam_used;
-                    cold_milk = milk_lock.cold_milk_container;
-                    foam = milk_lock.milk_foam_container;
-                }
-                Err(e) => {
-                    println!("[ERROR] No se pudieron obtener las estadísticas: {:?}", e);
-                    continue;
-                }
-            }
-            milk_cvar.notify_all();
-
-            let shutdown;
-            match statistics_values.lock() {
-                Ok(statistics_values_lock) => {
-                    shutdown = statistics_values_lock.shutdown;
-                    orders_served = statistics_values_lock.orders_served;
-                }
-                Err(e) => {
-                    println!("[ERROR] No se pudieron obtener las estadísticas: {:?}", e);
-                    continue;
+            };
+            match self.stats_renderer.lock() {
+                Ok(mut renderer) => {
+                    if let Err(e) = renderer.render(&snapshot) {
+                        error!("No se pudieron imprimir las estadísticas: {:?}", e);
+                    }
                 }
+                Err(e) => error!("No se pudieron imprimir las estadísticas: {:?}", e),
             }
-
-            println!(
-                r#"
-                Estadísticas:
-                    Ordenes completas: {:?}
-                    Café:
-                        Granos consumidos: {:?}
-                        Café molido consumido: {:?}
-                        Disponibilidad de granos: {:?}
-                        Disponibilidad de café molido: {:?}
-                    Agua caliente:
-                        Consumida: {:?}
-                        Disponibilidad: {:?}
-                    Cacao:
-                        Consumido: {:?}
-                        Disponibilidad: {:?}
-                    Leche:
-                        Leche fría consumida: {:?}
-                        Espuma de leche consumida: {:?}
-                        Leche fría disponible: {:?}
-                        Espuma de leche disponible: {:?}
-            "#,
-                orders_served,
-                grains_used,
-                coffee_used,
-                grains,
-                coffee,
-                water_used,
-                water,
-                cocoa_used,
-                cocoa,
-                cold_milk_used,
-                foam_used,
-                cold_milk,
-                foam
-            );
             if shutdown {
                 break;
             }
@@ -612,19 +1368,67 @@ impl CoffeeMaker {
     }
 }
 
+/// Dispensador que tomó un pedido de un contenedor. Al descartarse lo devuelve al contenedor y avisa a los que esperan
+/// en él, así el dispensador no se pierde aunque el pedido termine con error mientras lo usa.
+struct DispenserGuard<'a, T> {
+    container: &'a (Mutex<T>, Condvar),
+    free_dispensers: fn(&mut T) -> &mut u32,
+}
+
+impl<'a, T> DispenserGuard<'a, T> {
+    /// Toma un dispensador de `container`, del que el pedido ya tiene el lock en `locked`.
+    /// `free_dispensers` indica dónde lleva el contenedor la cantidad de dispensadores libres.
+    fn take(
+        container: &'a (Mutex<T>, Condvar),
+        locked: &mut T,
+        free_dispensers: fn(&mut T) -> &mut u32,
+    ) -> DispenserGuard<'a, T> {
+        *free_dispensers(locked) -= 1;
+        DispenserGuard {
+            container,
+            free_dispensers,
+        }
+    }
+}
+
+impl<T> Drop for DispenserGuard<'_, T> {
+    /// Devuelve el dispensador aunque el lock esté envenenado, para que no quede tomado para siempre.
+    fn drop(&mut self) {
+        let (lock, cvar) = self.container;
+        let mut container = lock.lock().unwrap_or_else(PoisonError::into_inner);
+        *(self.free_dispensers)(&mut container) += 1;
+        drop(container);
+        cvar.notify_all();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::helpers::constants::{A, E, G, L, M};
+    use std::thread;
+    use std::thread::JoinHandle;
+
+    use crate::helpers::config::{DispenserPolicy, QueuePolicy};
+    use crate::helpers::constants::{A, C, E, G, L, M, N, SERVE_COFFEE_TIME};
+    use crate::structs::event_bus::EventRecord;
+    use crate::structs::priority::Priority;
 
     #[test]
     fn test_try_serve_ground_coffee_serial() {
-        let coffee_maker = CoffeeMaker::new();
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
 
         match Order::new(1, 20, 3, 4, 5) {
             Ok(mut order) => {
                 let coffee_container_clone = coffee_maker.coffee_container.clone();
-                match CoffeeMaker::try_serve_ground_coffee(&mut order, coffee_container_clone) {
+                match CoffeeMaker::try_serve_ground_coffee(
+                    &mut order,
+                    coffee_container_clone,
+                    &Config::default(),
+                    &Clock::real(),
+                    &EventBus::default(),
+                    &Tracer::default(),
+                    &Arc::default(),
+                ) {
                     Ok(_) => {
                         assert_eq!(order.ground_coffee, 0);
                         let (coffee_lock, _) = &*coffee_maker.coffee_container;
@@ -651,12 +1455,20 @@ mod tests {
 
     #[test]
     fn test_try_serve_hot_water_serial() {
-        let coffee_maker = CoffeeMaker::new();
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
 
         match Order::new(1, 2, 20, 4, 5) {
             Ok(mut order) => {
                 let hot_water_container_clone = coffee_maker.hot_water_container.clone();
-                match CoffeeMaker::try_serve_hot_water(&mut order, hot_water_container_clone) {
+                match CoffeeMaker::try_serve_hot_water(
+                    &mut order,
+                    hot_water_container_clone,
+                    &Config::default(),
+                    &Clock::real(),
+                    &EventBus::default(),
+                    &Tracer::default(),
+                    &Arc::default(),
+                ) {
                     Ok(_) => {
                         assert_eq!(order.hot_water, 0);
                         let (hot_water_lock, _) = &*coffee_maker.hot_water_container;
@@ -683,12 +1495,20 @@ mod tests {
 
     #[test]
     fn test_try_serve_cocoa_serial() {
-        let coffee_maker = CoffeeMaker::new();
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
 
         match Order::new(1, 2, 3, 20, 5) {
             Ok(mut order) => {
                 let cocoa_container_clone = coffee_maker.cocoa_container.clone();
-                match CoffeeMaker::try_serve_cocoa(&mut order, cocoa_container_clone) {
+                match CoffeeMaker::try_serve_cocoa(
+                    &mut order,
+                    cocoa_container_clone,
+                    &Config::default(),
+                    &Clock::real(),
+                    &EventBus::default(),
+                    &Tracer::default(),
+                    &Arc::default(),
+                ) {
                     Ok(_) => {
                         assert_eq!(order.cocoa, 0);
                         let (cocoa_lock, _) = &*coffee_maker.cocoa_container;
@@ -715,12 +1535,20 @@ mod tests {
 
     #[test]
     fn test_try_serve_milk_foam_serial() {
-        let coffee_maker = CoffeeMaker::new();
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
 
         match Order::new(1, 2, 3, 4, 20) {
             Ok(mut order) => {
                 let milk_container_clone = coffee_maker.milk_container.clone();
-                match CoffeeMaker::try_serve_milk_foam(&mut order, milk_container_clone) {
+                match CoffeeMaker::try_serve_milk_foam(
+                    &mut order,
+                    milk_container_clone,
+                    &Config::default(),
+                    &Clock::real(),
+                    &EventBus::default(),
+                    &Tracer::default(),
+                    &Arc::default(),
+                ) {
                     Ok(_) => {
                         assert_eq!(order.milk_foam, 0);
                         let (milk_lock, _) = &*coffee_maker.milk_container;
@@ -747,7 +1575,7 @@ mod tests {
 
     #[test]
     fn test_try_serve_ground_coffee_concurrent() {
-        let coffee_maker = CoffeeMaker::new();
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
 
         let mut order_1 = Order::new(1, 10, 10, 10, 10).unwrap(); // Ya esta testeado que esto no falla.
         let mut order_2 = Order::new(2, 10, 10, 10, 10).unwrap(); // Ya esta testeado que esto no falla.
@@ -764,6 +1592,11 @@ mod tests {
                     if CoffeeMaker::try_serve_ground_coffee(
                         &mut order_1,
                         coffee_container_clone.clone(),
+                        &Config::default(),
+                        &Clock::real(),
+                        &EventBus::default(),
+                        &Tracer::default(),
+                        &Arc::default(),
                     )
                     .is_err()
                     {
@@ -774,6 +1607,11 @@ mod tests {
                     if CoffeeMaker::try_serve_ground_coffee(
                         &mut order_2,
                         coffee_container_clone.clone(),
+                        &Config::default(),
+                        &Clock::real(),
+                        &EventBus::default(),
+                        &Tracer::default(),
+                        &Arc::default(),
                     )
                     .is_err()
                     {
@@ -813,7 +1651,7 @@ mod tests {
 
     #[test]
     fn test_try_serve_hot_water_concurrent() {
-        let coffee_maker = CoffeeMaker::new();
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
 
         let mut order_1 = Order::new(1, 10, 10, 10, 10).unwrap(); // Ya esta testeado que esto no falla.
         let mut order_2 = Order::new(2, 10, 10, 10, 10).unwrap(); // Ya esta testeado que esto no falla.
@@ -829,6 +1667,11 @@ mod tests {
                     if CoffeeMaker::try_serve_hot_water(
                         &mut order_1,
                         hot_water_container_clone.clone(),
+                        &Config::default(),
+                        &Clock::real(),
+                        &EventBus::default(),
+                        &Tracer::default(),
+                        &Arc::default(),
                     )
                     .is_err()
                     {
@@ -837,6 +1680,11 @@ mod tests {
                     if CoffeeMaker::try_serve_hot_water(
                         &mut order_2,
                         hot_water_container_clone.clone(),
+                        &Config::default(),
+                        &Clock::real(),
+                        &EventBus::default(),
+                        &Tracer::default(),
+                        &Arc::default(),
                     )
                     .is_err()
                     {
@@ -873,7 +1721,7 @@ mod tests {
 
     #[test]
     fn test_try_serve_cocoa_concurrent() {
-        let coffee_maker = CoffeeMaker::new();
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
 
         let mut order_1 = Order::new(1, 10, 10, 10, 10).unwrap(); // Ya esta testeado que esto no falla.
         let mut order_2 = Order::new(2, 10, 10, 10, 10).unwrap(); // Ya esta testeado que esto no falla.
@@ -886,13 +1734,29 @@ mod tests {
             Ok(cocoa_lock) => {
                 assert_eq!(cocoa_lock.cocoa, C);
                 thread_handles.push(thread::spawn(move || {
-                    if CoffeeMaker::try_serve_cocoa(&mut order_1, cocoa_container_clone.clone())
-                        .is_err()
+                    if CoffeeMaker::try_serve_cocoa(
+                        &mut order_1,
+                        cocoa_container_clone.clone(),
+                        &Config::default(),
+                        &Clock::real(),
+                        &EventBus::default(),
+                        &Tracer::default(),
+                        &Arc::default(),
+                    )
+                    .is_err()
                     {
                         println!("[ERROR] Testeando try_serve_cocoa de manera concurrente.");
                     }
-                    if CoffeeMaker::try_serve_cocoa(&mut order_2, cocoa_container_clone.clone())
-                        .is_err()
+                    if CoffeeMaker::try_serve_cocoa(
+                        &mut order_2,
+                        cocoa_container_clone.clone(),
+                        &Config::default(),
+                        &Clock::real(),
+                        &EventBus::default(),
+                        &Tracer::default(),
+                        &Arc::default(),
+                    )
+                    .is_err()
                     {
                         println!("[ERROR] Testeando try_serve_cocoa de manera concurrente.");
                     }
@@ -927,7 +1791,7 @@ mod tests {
 
     #[test]
     fn test_try_serve_milk_foam_concurrent() {
-        let coffee_maker = CoffeeMaker::new();
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
 
         let mut order_1 = Order::new(1, 10, 10, 10, 10).unwrap(); // Ya esta testeado que esto no falla.
         let mut order_2 = Order::new(2, 10, 10, 10, 10).unwrap(); // Ya esta testeado que esto no falla.
@@ -941,13 +1805,29 @@ mod tests {
                 assert_eq!(milk_lock.milk_foam_container, E);
                 assert_eq!(milk_lock.cold_milk_container, L);
                 thread_handles.push(thread::spawn(move || {
-                    if CoffeeMaker::try_serve_milk_foam(&mut order_1, milk_container_clone.clone())
-                        .is_err()
+                    if CoffeeMaker::try_serve_milk_foam(
+                        &mut order_1,
+                        milk_container_clone.clone(),
+                        &Config::default(),
+                        &Clock::real(),
+                        &EventBus::default(),
+                        &Tracer::default(),
+                        &Arc::default(),
+                    )
+                    .is_err()
                     {
                         println!("[ERROR] Testeando try_serve_milk_foam de manera concurrente.");
                     }
-                    if CoffeeMaker::try_serve_milk_foam(&mut order_2, milk_container_clone.clone())
-                        .is_err()
+                    if CoffeeMaker::try_serve_milk_foam(
+                        &mut order_2,
+                        milk_container_clone.clone(),
+                        &Config::default(),
+                        &Clock::real(),
+                        &EventBus::default(),
+                        &Tracer::default(),
+                        &Arc::default(),
+                    )
+                    .is_err()
                     {
                         println!("[ERROR] Testeando try_serve_milk_foam de manera concurrente.");
                     }
@@ -981,9 +1861,111 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_try_serve_ground_coffee_without_free_dispensers() {
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
+        let (coffee_lock, _) = &*coffee_maker.coffee_container;
+        coffee_lock.lock().unwrap().free_dispensers = 0;
+
+        let mut order = Order::new(1, 10, 0, 0, 0).unwrap(); // Ya esta testeado que esto no falla.
+        let result = CoffeeMaker::try_serve_ground_coffee(
+            &mut order,
+            coffee_maker.coffee_container.clone(),
+            &Config::default(),
+            &Clock::real(),
+            &EventBus::default(),
+            &Tracer::default(),
+            &Arc::default(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(order.ground_coffee, 10);
+
+        let coffee_lock = coffee_lock.lock().unwrap();
+        assert_eq!(coffee_lock.ground_coffee_container, M);
+        assert_eq!(coffee_lock.ground_coffee_used, 0);
+    }
+
+    #[test]
+    fn test_try_serve_returns_dispenser_on_error() {
+        let clock = Clock::simulated();
+        let _participant = clock.participate();
+        let coffee_maker = CoffeeMaker::new(Config::default(), clock.clone(), Menu::default());
+        let coffee_container = coffee_maker.coffee_container.clone();
+        coffee_container.0.lock().unwrap().ground_coffee_container = 0;
+
+        // Mientras el pedido espera el café con el dispensador tomado, otro hilo envenena el lock del contenedor.
+        let poisoned = coffee_container.clone();
+        let clock_clone = clock.clone();
+        let poisoner = clock.spawn_named("envenenar".to_string(), move || {
+            clock_clone.sleep(Duration::from_millis(100));
+            let _coffee_lock = poisoned.0.lock().unwrap();
+            panic!("Se envenena el lock del contenedor de café");
+        });
+        let mut order = Order::new(1, 10, 0, 0, 0).unwrap();
+        let result = CoffeeMaker::try_serve_ground_coffee(
+            &mut order,
+            coffee_container.clone(),
+            &Config::default(),
+            &clock,
+            &EventBus::default(),
+            &Tracer::default(),
+            &Arc::default(),
+        );
+        assert_eq!(result, Err(CustomError::PoisonedLock));
+        assert!(poisoner.join().is_err());
+
+        let coffee_lock = coffee_container
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        assert_eq!(order.ground_coffee, 10);
+        assert_eq!(coffee_lock.ground_coffee_used, 0);
+        assert_eq!(coffee_lock.free_dispensers, N);
+    }
+
+    #[test]
+    fn test_try_serve_ground_coffee_parallel_dispensers() {
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
+        let start = std::time::Instant::now();
+
+        let mut thread_handles: Vec<JoinHandle<()>> = Vec::new();
+        for id in 0..N {
+            let coffee_container_clone = coffee_maker.coffee_container.clone();
+            thread_handles.push(thread::spawn(move || {
+                let mut order = Order::new(id, 10, 0, 0, 0).unwrap(); // Ya esta testeado que esto no falla.
+                while order.ground_coffee > 0 {
+                    if CoffeeMaker::try_serve_ground_coffee(
+                        &mut order,
+                        coffee_container_clone.clone(),
+                        &Config::default(),
+                        &Clock::real(),
+                        &EventBus::default(),
+                        &Tracer::default(),
+                        &Arc::default(),
+                    )
+                    .is_err()
+                    {
+                        println!("[ERROR] Testeando dispensadores en paralelo.");
+                        break;
+                    }
+                }
+            }));
+        }
+        for thread in thread_handles {
+            assert!(thread.join().is_ok());
+        }
+
+        // Con N dispensadores los N pedidos se sirven al mismo tiempo y no uno detrás del otro.
+        assert!(start.elapsed() < Duration::from_millis(2 * SERVE_COFFEE_TIME));
+        let coffee_lock = coffee_maker.coffee_container.0.lock().unwrap();
+        assert_eq!(coffee_lock.ground_coffee_container, M - 10 * N);
+        assert_eq!(coffee_lock.ground_coffee_used, 10 * N);
+        assert_eq!(coffee_lock.free_dispensers, N);
+    }
+
     #[test]
     fn test_send_shutdown_signal() {
-        let coffee_maker = CoffeeMaker::new();
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
         if coffee_maker.send_shutdown_signal().is_err() {
             println!("[ERROR] Testeando send_shutdown_signal.");
         }
@@ -1028,6 +2010,7 @@ mod tests {
         };
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn assert_all_stats(
         path: &str,
         grains_used: u32,
@@ -1044,7 +2027,7 @@ mod tests {
         water: u32,
         orders_served: u32,
     ) {
-        let coffee_maker = CoffeeMaker::new();
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::simulated(), Menu::default());
         let result = coffee_maker.take_orders(path);
         assert!(result.is_ok());
 
@@ -1096,9 +2079,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_check_orders() {
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
+        assert_eq!(
+            coffee_maker.check_orders("src/tests/multiple_orders.txt"),
+            Ok(10)
+        );
+        assert_eq!(
+            coffee_maker.check_orders("src/tests/multiple_orders_one_invalid.txt"),
+            Err(CustomError::InvalidOrderValue)
+        );
+        assert_eq!(
+            coffee_maker.check_orders("src/tests/invalid.txt"),
+            Err(CustomError::CantOpenOrderFile)
+        );
+    }
+
     #[test]
     fn test_take_orders_invalid_file() {
-        let coffee_maker = CoffeeMaker::new();
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
         let result = coffee_maker.take_orders("src/tests/invalid.txt");
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), CustomError::CantOpenOrderFile);
@@ -1163,4 +2163,664 @@ mod tests {
             4,                                           //orders_served
         );
     }
+
+    #[test]
+    fn test_take_orders_jsonl() {
+        assert_all_stats(
+            "src/tests/orders.jsonl", // path
+            0,                        // grains_used
+            0,                        // cold_milk_used
+            28,                       //cocoa_used
+            24,                       //coffee_used
+            30,                       //foam_used
+            26,                       //water_used
+            G,                        //grains
+            L,                        //cold_milk
+            C - 28,                   //cocoa
+            M - 24,                   //coffee
+            E - 30,                   //foam
+            A - 26,                   //water
+            4,                        //orders_served
+        );
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
+        assert_eq!(
+            coffee_maker.check_orders("src/tests/orders.jsonl"),
+            Err(CustomError::InvalidOrderValue)
+        );
+        let config = Config {
+            order_format: Some(OrderFormat::Jsonl),
+            ..Config::default()
+        };
+        let coffee_maker = CoffeeMaker::new(config, Clock::real(), Menu::default());
+        assert_eq!(
+            coffee_maker.check_orders("src/tests/multiple_orders.txt"),
+            Err(CustomError::InvalidOrderValue)
+        );
+    }
+
+    #[test]
+    fn test_take_orders_menu_drinks() {
+        assert_all_stats(
+            "src/tests/menu_orders.txt", // path
+            0,                           // grains_used
+            0,                           // cold_milk_used
+            24,                          //cocoa_used
+            36,                          //coffee_used
+            29,                          //foam_used
+            38,                          //water_used
+            G,                           //grains
+            L,                           //cold_milk
+            C - 24,                      //cocoa
+            M - 36,                      //coffee
+            E - 29,                      //foam
+            A - 38,                      //water
+            5,                           //orders_served
+        );
+    }
+
+    #[test]
+    fn test_take_orders_report_with_insufficient_cocoa() {
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::simulated(), Menu::default());
+        let events = coffee_maker.events().subscribe();
+        let report = coffee_maker
+            .take_orders("src/tests/multiple_orders_cacao_overflow.txt")
+            .unwrap();
+        assert_eq!(report.completed(), 10);
+        assert_eq!(report.rejected(), 1);
+        assert_eq!(report.partially_served(), 0);
+        assert_eq!(report.invalid_records, 0);
+        assert!(events.try_iter().all(|record| match record.event {
+            MachineEvent::OrderRejected { error, .. } => {
+                error == CustomError::InsufficientIngredients
+            }
+            _ => true,
+        }));
+
+        let statistics_values = coffee_maker.statistics_values.lock().unwrap();
+        assert_eq!(statistics_values.orders_served, 10);
+        assert_eq!(statistics_values.orders_rejected, 1);
+        drop(statistics_values);
+
+        let coffee_container = coffee_maker.coffee_container.0.lock().unwrap();
+        assert_eq!(coffee_container.ground_coffee_used, 100);
+        assert_eq!(coffee_container.reserved, 0);
+    }
+
+    #[test]
+    fn test_reserve_ingredients_all_or_nothing() {
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
+        let first = Order::new(0, 10, 10, 60, 10).unwrap();
+        let second = Order::new(1, 10, 10, 60, 10).unwrap();
+        assert!(coffee_maker.reserve_ingredients(&first).is_ok());
+        assert_eq!(
+            coffee_maker.reserve_ingredients(&second),
+            Err(CustomError::InsufficientIngredients)
+        );
+        assert_eq!(coffee_maker.coffee_container.0.lock().unwrap().reserved, 10);
+        assert_eq!(coffee_maker.cocoa_container.0.lock().unwrap().reserved, 60);
+        assert_eq!(coffee_maker.milk_container.0.lock().unwrap().reserved, 10);
+
+        assert!(CoffeeMaker::release_reservation(
+            &first,
+            &coffee_maker.coffee_container,
+            &coffee_maker.cocoa_container,
+            &coffee_maker.milk_container,
+        )
+        .is_ok());
+        assert!(coffee_maker.reserve_ingredients(&second).is_ok());
+        assert_eq!(coffee_maker.cocoa_container.0.lock().unwrap().reserved, 60);
+    }
+
+    #[test]
+    fn test_take_orders_rejects_order_exceeding_capacity() {
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::simulated(), Menu::default());
+        let statuses = Arc::new(Mutex::new(HashMap::new()));
+        for id in 0..2 {
+            let statuses = statuses.clone();
+            coffee_maker
+                .on_order_finished(
+                    id,
+                    Box::new(move |status| {
+                        statuses.lock().unwrap().insert(id, status);
+                    }),
+                )
+                .unwrap();
+        }
+        coffee_maker
+            .take_orders("src/tests/order_exceeds_capacity.txt")
+            .unwrap();
+        let statuses = statuses.lock().unwrap();
+        assert_eq!(
+            statuses.get(&0),
+            Some(&OrderStatus::Rejected(CustomError::OrderExceedsCapacity))
+        );
+        assert_eq!(statuses.get(&1), Some(&OrderStatus::Completed));
+        assert_eq!(
+            coffee_maker.check_orders("src/tests/order_exceeds_capacity.txt"),
+            Err(CustomError::InvalidOrderValue)
+        );
+    }
+
+    #[test]
+    fn test_take_orders_publishes_events() {
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::simulated(), Menu::default());
+        let received = coffee_maker.events().subscribe();
+        coffee_maker
+            .take_orders("src/tests/order_exceeds_capacity.txt")
+            .unwrap();
+        coffee_maker.events().close();
+
+        let events: Vec<MachineEvent> = received
+            .iter()
+            .map(|record| record.event)
+            .filter(|event| {
+                !matches!(
+                    event,
+                    MachineEvent::RefillStarted { .. }
+                        | MachineEvent::RefillFinished { .. }
+                        | MachineEvent::LowStock { .. }
+                )
+            })
+            .collect();
+        let served = |ingredient| MachineEvent::IngredientServed {
+            order_id: 1,
+            ingredient,
+            amount: 10,
+        };
+        assert_eq!(events.len(), 9, "Eventos: {:?}", events);
+        assert_eq!(
+            events[..7],
+            [
+                MachineEvent::OrderReceived {
+                    order_id: 0,
+                    reference: None,
+                    priority: Priority::Regular
+                },
+                MachineEvent::OrderRejected {
+                    order_id: 0,
+                    error: CustomError::OrderExceedsCapacity,
+                    partially_served: false
+                },
+                MachineEvent::OrderReceived {
+                    order_id: 1,
+                    reference: None,
+                    priority: Priority::Regular
+                },
+                served(Ingredient::GroundCoffee),
+                served(Ingredient::HotWater),
+                served(Ingredient::Cocoa),
+                served(Ingredient::MilkFoam),
+            ]
+        );
+        assert!(matches!(
+            events[7],
+            MachineEvent::OrderCompleted { order_id: 1, .. }
+        ));
+        assert_eq!(events[8], MachineEvent::Shutdown);
+    }
+
+    #[test]
+    fn test_blocked_orders_publish_waiting_events() {
+        let config = Config {
+            dispensers: 1,
+            ..Config::default()
+        };
+        let coffee_maker = CoffeeMaker::new(config, Clock::simulated(), Menu::default());
+        let received = coffee_maker.events().subscribe();
+        coffee_maker
+            .take_orders("src/tests/priority_orders.txt")
+            .unwrap();
+        coffee_maker.events().close();
+
+        let events: Vec<MachineEvent> = received.iter().map(|record| record.event).collect();
+        let waiting: Vec<u32> = events
+            .iter()
+            .filter_map(|event| match event {
+                MachineEvent::OrderWaiting {
+                    order_id,
+                    ingredient: Ingredient::GroundCoffee,
+                } => Some(*order_id),
+                _ => None,
+            })
+            .collect();
+        // Con un solo dispensador esperan todos menos el primero, y cada uno avisa una sola vez.
+        assert_eq!(waiting.len(), 4, "Eventos: {:?}", events);
+        for order_id in waiting {
+            let waited = events
+                .iter()
+                .position(|event| {
+                    *event
+                        == MachineEvent::OrderWaiting {
+                            order_id,
+                            ingredient: Ingredient::GroundCoffee,
+                        }
+                })
+                .unwrap();
+            assert!(events[waited..].iter().any(|event| matches!(
+                event,
+                MachineEvent::IngredientServed { order_id: id, .. } if *id == order_id
+            )));
+        }
+    }
+
+    #[test]
+    fn test_try_serve_exceeding_capacity_does_not_wait() {
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
+        let mut order = Order::new(1, M + 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            CoffeeMaker::try_serve_ground_coffee(
+                &mut order,
+                coffee_maker.coffee_container.clone(),
+                &Config::default(),
+                &Clock::real(),
+                &EventBus::default(),
+                &Tracer::default(),
+                &Arc::default(),
+            ),
+            Err(CustomError::OrderExceedsCapacity)
+        );
+        assert_eq!(order.ground_coffee, M + 1);
+    }
+
+    #[test]
+    fn test_take_orders_refills_for_order_above_stock() {
+        assert_all_stats(
+            "src/tests/order_above_stock.txt", // path
+            60,                                // grains_used
+            0,                                 // cold_milk_used
+            0,                                 //cocoa_used
+            110,                               //coffee_used
+            0,                                 //foam_used
+            0,                                 //water_used
+            G - 60,                            //grains
+            L,                                 //cold_milk
+            C,                                 //cocoa
+            M - 50,                            //coffee
+            E,                                 //foam
+            A,                                 //water
+            2,                                 //orders_served
+        );
+    }
+
+    #[test]
+    fn test_waiting_orders_do_not_spin() {
+        let config = Config {
+            dispensers: 1,
+            take_order_time: 0,
+            serve_coffee_time: 10,
+            baristas: 30,
+            time_to_stats: 10,
+            ..Config::default()
+        };
+        let coffee_maker = CoffeeMaker::new(config, Clock::real(), Menu::default());
+        let report = coffee_maker
+            .take_orders("src/tests/many_coffee_orders.txt")
+            .unwrap();
+        assert_eq!(report.completed(), 30);
+
+        // Con 1 dispensador casi todos los pedidos esperan. Cada uno revisa la condición al bloquearse y cada vez que se
+        // libera un dispensador; si girasen en un loop la revisarían miles de veces.
+        let releases = *coffee_maker.dispensers_released.0.lock().unwrap();
+        let checks = coffee_maker.dispenser_checks.load(Ordering::Relaxed);
+        assert!(
+            checks <= 2 * (releases + 1) * 30,
+            "Los pedidos en espera revisaron {} veces con {} dispensadores liberados",
+            checks,
+            releases
+        );
+    }
+
+    #[test]
+    fn test_take_orders_rejects_when_queue_is_full() {
+        let config = Config {
+            take_order_time: 0,
+            baristas: 1,
+            queue_capacity: 1,
+            queue_policy: QueuePolicy::Reject,
+            ..Config::default()
+        };
+        let coffee_maker = CoffeeMaker::new(config, Clock::simulated(), Menu::default());
+        let events = coffee_maker.events().subscribe();
+        let report = coffee_maker
+            .take_orders("src/tests/many_coffee_orders.txt")
+            .unwrap();
+        assert!(report.completed() >= 1);
+        assert!(report.rejected() >= 1);
+        assert_eq!(report.completed() + report.rejected(), 30);
+        assert!(events.try_iter().all(|record| match record.event {
+            MachineEvent::OrderRejected { error, .. } => error == CustomError::QueueFull,
+            _ => true,
+        }));
+        assert_eq!(coffee_maker.coffee_container.0.lock().unwrap().reserved, 0);
+    }
+
+    #[test]
+    fn test_take_orders_serves_while_receiving() {
+        let config = Config {
+            take_order_time: 300,
+            serve_coffee_time: 10,
+            serve_hot_water_time: 10,
+            serve_cocoa_time: 10,
+            serve_milk_foam_time: 10,
+            ..Config::default()
+        };
+        let coffee_maker = CoffeeMaker::new(config, Clock::simulated(), Menu::default());
+        let received = coffee_maker.events().subscribe();
+        let report = coffee_maker
+            .take_orders("src/tests/two_equal_orders.txt")
+            .unwrap();
+        coffee_maker.events().close();
+        assert_eq!(report.completed(), 2);
+
+        // El primer pedido se recibe a los 300 ms y el segundo a los 600 ms: el primero se sirve en el medio.
+        let records: Vec<EventRecord> = received.iter().collect();
+        let first_served = records
+            .iter()
+            .find(|record| matches!(record.event, MachineEvent::IngredientServed { .. }))
+            .unwrap();
+        let last_received = records
+            .iter()
+            .rfind(|record| matches!(record.event, MachineEvent::OrderReceived { .. }))
+            .unwrap();
+        assert_eq!(first_served.event.order_id(), Some(0));
+        assert_eq!(last_received.event.order_id(), Some(1));
+        assert!(
+            first_served.time < last_received.time,
+            "Primer ingrediente servido a los {} ms, último pedido recibido a los {} ms",
+            first_served.time,
+            last_received.time
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_take_orders_from_fifo_starts_each_order_on_arrival() {
+        use std::io::Write;
+
+        let path = std::env::temp_dir().join(format!("coffee-gpt-pedidos-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let created = std::process::Command::new("mkfifo").arg(&path).status();
+        assert!(created.map(|status| status.success()).unwrap_or(false));
+
+        let config = Config {
+            take_order_time: 0,
+            serve_coffee_time: 10,
+            serve_hot_water_time: 10,
+            serve_cocoa_time: 10,
+            serve_milk_foam_time: 10,
+            time_to_stats: 10,
+            ..Config::default()
+        };
+        let coffee_maker = CoffeeMaker::new(config, Clock::real(), Menu::default());
+        let coffee_maker_clone = coffee_maker.clone();
+        let fifo_path = path.to_str().unwrap().to_string();
+        let handle = thread::spawn(move || coffee_maker_clone.take_orders(&fifo_path));
+
+        let mut fifo = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        writeln!(fifo, "1,1,1,1").unwrap();
+        fifo.flush().unwrap();
+        // El primer pedido se tiene que completar sin que se cierre el pipe.
+        let start = std::time::Instant::now();
+        while coffee_maker.statistics_values.lock().unwrap().orders_served < 1 {
+            assert!(start.elapsed() < std::time::Duration::from_secs(5));
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        writeln!(fifo, "mocha").unwrap();
+        drop(fifo);
+
+        let report = handle.join().unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(report.completed(), 2);
+    }
+
+    #[test]
+    fn test_take_orders_simulated_is_deterministic() {
+        let run = || {
+            let config = Config {
+                time_variation: 30,
+                seed: 7,
+                ..Config::default()
+            };
+            let coffee_maker = CoffeeMaker::new(config, Clock::simulated(), Menu::default());
+            let start = std::time::Instant::now();
+            assert!(coffee_maker
+                .take_orders("src/tests/multiple_orders.txt")
+                .is_ok());
+            assert!(start.elapsed() < std::time::Duration::from_secs(5));
+
+            let coffee_container = coffee_maker.coffee_container.0.lock().unwrap();
+            let milk_container = coffee_maker.milk_container.0.lock().unwrap();
+            let statistics_values = coffee_maker.statistics_values.lock().unwrap();
+            (
+                coffee_maker.clock.now(),
+                coffee_container.ground_coffee_used,
+                milk_container.milk_foam_used,
+                statistics_values.orders_served,
+            )
+        };
+        let first = run();
+        assert!(first.0 >= SERVE_COFFEE_TIME);
+        assert_eq!(first.3, 10);
+        assert_eq!(first, run());
+    }
+
+    #[test]
+    fn test_take_orders_serves_higher_priority_first() {
+        let config = Config {
+            dispensers: 1,
+            take_order_time: 100,
+            serve_coffee_time: 1000,
+            time_to_stats: 10000,
+            ..Config::default()
+        };
+        let coffee_maker = CoffeeMaker::new(config, Clock::simulated(), Menu::default());
+        let report = coffee_maker
+            .take_orders("src/tests/priority_orders.txt")
+            .unwrap();
+        assert_eq!(report.completed(), 5);
+        let staff = report.waits[&Priority::Staff];
+        let regular = report.waits[&Priority::Regular];
+        assert_eq!(staff.orders, 1);
+        assert_eq!(regular.orders, 4);
+        // El pedido staff llega último, cuando los demás ya esperan el único dispensador, y lo toma
+        // apenas se libera: espera menos de un café. El último regular espera los otros tres.
+        assert!(staff.max < 1000, "Espera staff: {:?}", staff);
+        assert!(regular.max > 3000, "Espera regular: {:?}", regular);
+    }
+
+    #[test]
+    fn test_tracer_records_order_activity() {
+        let config = Config {
+            dispensers: 1,
+            ..Config::default()
+        };
+        let coffee_maker = CoffeeMaker::new(config, Clock::simulated(), Menu::default());
+        coffee_maker.tracer().start();
+        coffee_maker
+            .take_orders("src/tests/priority_orders.txt")
+            .unwrap();
+        let spans = coffee_maker.tracer().spans();
+        let has_span = |name: &str, thread: &str| {
+            spans
+                .iter()
+                .any(|span| span.name == name && span.thread.starts_with(thread))
+        };
+        assert!(spans
+            .iter()
+            .filter(|span| span.name == "servir café molido")
+            .all(|span| span.thread.starts_with("barista-") && span.order.is_some()));
+        assert!(has_span("servir café molido", "barista-"));
+        assert!(has_span("esperar dispensador", "barista-"));
+        assert!(has_span("tomar pedido", "recepcion"));
+        assert!(spans.iter().all(|span| span.start < span.end));
+    }
+
+    /// Renderizador que guarda las tomas de estadísticas en lugar de imprimirlas.
+    struct RecordingRenderer(Arc<Mutex<Vec<StatsSnapshot>>>);
+
+    impl StatsRenderer for RecordingRenderer {
+        fn render(&mut self, snapshot: &StatsSnapshot) -> io::Result<()> {
+            self.0.lock().unwrap().push(*snapshot);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stats_renderer_receives_snapshots() {
+        let config = Config {
+            time_to_stats: 500,
+            ..Config::default()
+        };
+        let coffee_maker = CoffeeMaker::new(config, Clock::simulated(), Menu::default());
+        let snapshots = Arc::new(Mutex::new(Vec::new()));
+        coffee_maker
+            .set_stats_renderer(Box::new(RecordingRenderer(snapshots.clone())))
+            .unwrap();
+        let report = coffee_maker
+            .take_orders("src/tests/priority_orders.txt")
+            .unwrap();
+        let snapshots = snapshots.lock().unwrap();
+        assert!(snapshots.len() > 1);
+        assert!(snapshots.windows(2).all(|pair| pair[0].time < pair[1].time));
+        let last = snapshots[snapshots.len() - 1];
+        assert_eq!(last.orders_served as usize, report.completed());
+        assert_eq!(last.coffee_used, 50);
+        assert_eq!(last.latency, report.latency);
+        for snapshot in snapshots.iter() {
+            assert_eq!(snapshot.invariant_violations(&config), Vec::<String>::new());
+        }
+    }
+
+    #[test]
+    fn test_snapshots_are_consistent_while_preparing() {
+        let config = Config {
+            take_order_time: 0,
+            serve_coffee_time: 5,
+            serve_hot_water_time: 5,
+            serve_cocoa_time: 5,
+            serve_milk_foam_time: 5,
+            refill_coffee_time: 5,
+            refill_water_time: 5,
+            refill_milk_time: 5,
+            time_to_stats: 50,
+            ..Config::default()
+        };
+        let coffee_maker = CoffeeMaker::new(config, Clock::real(), Menu::default());
+        let coffee_maker_clone = coffee_maker.clone();
+        let run = thread::spawn(move || {
+            coffee_maker_clone
+                .take_orders("src/tests/multiple_orders.txt")
+                .unwrap()
+        });
+        let mut snapshots = Vec::new();
+        while !run.is_finished() {
+            snapshots.push(coffee_maker.snapshot().unwrap());
+            thread::sleep(std::time::Duration::from_millis(1));
+        }
+        run.join().unwrap();
+        snapshots.push(coffee_maker.snapshot().unwrap());
+        for snapshot in &snapshots {
+            assert_eq!(snapshot.invariant_violations(&config), Vec::<String>::new());
+        }
+        assert!(snapshots[snapshots.len() - 1].coffee_used > 0);
+    }
+
+    #[test]
+    fn test_take_orders_reports_latency() {
+        let config = Config {
+            dispensers: 1,
+            take_order_time: 100,
+            serve_coffee_time: 1000,
+            time_to_stats: 10000,
+            ..Config::default()
+        };
+        let coffee_maker = CoffeeMaker::new(config, Clock::simulated(), Menu::default());
+        let report = coffee_maker
+            .take_orders("src/tests/priority_orders.txt")
+            .unwrap();
+        let latency = report.latency;
+        assert_eq!(latency.count, 5);
+        assert!(latency.p50 <= latency.p95 && latency.p95 <= latency.p99);
+        assert!(latency.p99 <= latency.max);
+        // Los cinco cafés se sirven de a uno: el último termina después de servirse los demás.
+        assert!(latency.max >= 4500, "Latencia: {:?}", latency);
+        assert!(latency.p50 >= 1000, "Latencia: {:?}", latency);
+        let (container, coffee_waits) = report.container_waits[0];
+        assert_eq!(container, "coffee");
+        assert_eq!(coffee_waits.count, 5);
+        assert!(coffee_waits.max >= 3000, "Espera: {:?}", coffee_waits);
+        assert_eq!(report.container_waits[1].1.count, 0);
+    }
+
+    #[test]
+    fn test_order_using_a_dispenser_does_not_hold_back_others() {
+        // El pedido vip espera café y agua; cuando toma el café, el pedido 2, que solo pide agua,
+        // tiene que poder tomar el dispensador de agua libre mientras al vip le sirven el café.
+        let config = Config {
+            dispensers: 1,
+            baristas: 4,
+            take_order_time: 10,
+            serve_coffee_time: 1010,
+            serve_hot_water_time: 1000,
+            time_to_stats: 10000,
+            ..Config::default()
+        };
+        let coffee_maker = CoffeeMaker::new(config, Clock::simulated(), Menu::default());
+        let events = coffee_maker.events().subscribe();
+        let report = coffee_maker
+            .take_orders("src/tests/vip_coffee_and_water_orders.txt")
+            .unwrap();
+        assert_eq!(report.completed(), 4);
+        let served: Vec<EventRecord> = events
+            .try_iter()
+            .filter(|record| matches!(record.event, MachineEvent::IngredientServed { .. }))
+            .collect();
+        let served_at = |order_id: u32, wanted: Ingredient| {
+            served
+                .iter()
+                .find(|record| {
+                    matches!(record.event, MachineEvent::IngredientServed { order_id: id, ingredient, .. }
+                        if id == order_id && ingredient == wanted)
+                })
+                .map(|record| record.time)
+                .unwrap()
+        };
+        assert!(
+            served_at(2, Ingredient::HotWater) <= served_at(3, Ingredient::GroundCoffee),
+            "Eventos: {:?}",
+            served
+        );
+    }
+
+    /// Prepara los pedidos de `src/tests/priority_orders.txt`, que solo piden café, con un único dispensador
+    /// y devuelve los identificadores en el orden de sus eventos [`MachineEvent::IngredientServed`].
+    fn served_order(config: Config) -> Vec<u32> {
+        let coffee_maker = CoffeeMaker::new(config, Clock::simulated(), Menu::default());
+        let events = coffee_maker.events().subscribe();
+        let report = coffee_maker
+            .take_orders("src/tests/priority_orders.txt")
+            .unwrap();
+        assert_eq!(report.completed(), 5);
+        events
+            .try_iter()
+            .filter_map(|record| match record.event {
+                MachineEvent::IngredientServed { order_id, .. } => Some(order_id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fifo_dispensers_serve_in_arrival_order() {
+        let mut config = Config {
+            dispensers: 1,
+            take_order_time: 100,
+            serve_coffee_time: 1000,
+            time_to_stats: 10000,
+            ..Config::default()
+        };
+        assert_eq!(served_order(config), vec![0, 4, 1, 2, 3]);
+        config.coffee_dispenser_policy = DispenserPolicy::Fifo;
+        assert_eq!(served_order(config), vec![0, 1, 2, 3, 4]);
+    }
 }
@@ -0,0 +1,337 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Write};
+use std::sync::mpsc::Receiver;
+
+use crate::helpers::config::Config;
+use crate::structs::event_bus::EventRecord;
+use crate::structs::machine_event::{Ingredient, MachineEvent};
+use crate::structs::priority::Priority;
+use crate::structs::stats_renderer::StatsRenderer;
+use crate::structs::stats_snapshot::StatsSnapshot;
+
+/// Milisegundos entre cada redibujado del tablero, si no se indicó `--stats-interval`.
+pub const REFRESH_TIME: u64 = 250;
+/// Cantidad de eventos recientes que se muestran.
+const RECENT_EVENTS: usize = 8;
+/// Cantidad máxima de pedidos en curso que se listan.
+const ORDERS_SHOWN: usize = 10;
+/// Milisegundos hacia atrás en los que se mide el ritmo de pedidos completados.
+const THROUGHPUT_WINDOW: u64 = 10000;
+/// Ancho en caracteres de las barras de stock.
+const GAUGE_WIDTH: usize = 30;
+/// Secuencia ANSI que lleva el cursor al principio y borra la pantalla.
+const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+
+/// Pedido recibido que todavía no terminó, según los eventos de la cafetera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct OrderProgress {
+    priority: Priority,
+    /// Ingrediente que está esperando, si está bloqueado.
+    waiting_for: Option<Ingredient>,
+    /// Cantidad de ingredientes que ya se le sirvieron.
+    served: u32,
+}
+
+/// Tablero que se redibuja en la terminal en lugar de imprimir las estadísticas: el stock de cada contenedor, los
+/// pedidos en curso y qué ingrediente espera cada uno, los últimos eventos y el ritmo de pedidos completados.
+/// Es un [`StatsRenderer`]: se redibuja con cada toma de estadísticas, con los eventos recibidos desde la anterior.
+pub struct Dashboard<W: Write + Send> {
+    output: W,
+    /// Capacidades de los contenedores, para el largo de las barras.
+    config: Config,
+    events: Receiver<EventRecord>,
+    orders: BTreeMap<u32, OrderProgress>,
+    recent: VecDeque<EventRecord>,
+    /// Momentos en que se completaron los pedidos dentro de [`THROUGHPUT_WINDOW`].
+    completed: VecDeque<u64>,
+}
+
+impl<W: Write + Send> Dashboard<W> {
+    /// Crea el tablero que dibuja en `output` los eventos de `events`, obtenido con
+    /// [`crate::structs::event_bus::EventBus::subscribe`] antes de empezar a recibir pedidos.
+    pub fn new(config: Config, events: Receiver<EventRecord>, output: W) -> Dashboard<W> {
+        Dashboard {
+            output,
+            config,
+            events,
+            orders: BTreeMap::new(),
+            recent: VecDeque::new(),
+            completed: VecDeque::new(),
+        }
+    }
+
+    /// Actualiza los pedidos en curso y los eventos recientes con un evento recibido.
+    fn update(&mut self, record: EventRecord) {
+        match &record.event {
+            MachineEvent::OrderReceived {
+                order_id, priority, ..
+            } => {
+                self.orders.insert(
+                    *order_id,
+                    OrderProgress {
+                        priority: *priority,
+                        waiting_for: None,
+                        served: 0,
+                    },
+                );
+            }
+            MachineEvent::OrderWaiting {
+                order_id,
+                ingredient,
+            } => {
+                if let Some(order) = self.orders.get_mut(order_id) {
+                    order.waiting_for = Some(*ingredient);
+                }
+            }
+            MachineEvent::IngredientServed { order_id, .. } => {
+                if let Some(order) = self.orders.get_mut(order_id) {
+                    order.waiting_for = None;
+                    order.served += 1;
+                }
+            }
+            MachineEvent::OrderCompleted {
+                order_id,
+                completed,
+                ..
+            } => {
+                self.orders.remove(order_id);
+                self.completed.push_back(*completed);
+            }
+            MachineEvent::OrderRejected { order_id, .. } => {
+                self.orders.remove(order_id);
+            }
+            _ => {}
+        }
+        self.recent.push_back(record);
+        if self.recent.len() > RECENT_EVENTS {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Pedidos completados por minuto en los últimos [`THROUGHPUT_WINDOW`] milisegundos hasta `now`,
+    /// o desde el principio si todavía no pasaron.
+    fn throughput(&mut self, now: u64) -> u64 {
+        let since = now.saturating_sub(THROUGHPUT_WINDOW);
+        while self.completed.front().is_some_and(|time| *time < since) {
+            self.completed.pop_front();
+        }
+        self.completed.len() as u64 * 60000 / (now - since).max(1)
+    }
+
+    /// Arma el cuadro a dibujar con la toma de estadísticas y los eventos recibidos hasta ahora.
+    fn frame(&mut self, s: &StatsSnapshot) -> String {
+        let c = self.config;
+        let gauges = [
+            ("Granos de café", s.grains, c.coffee_grains_capacity),
+            ("Café molido", s.coffee, c.ground_coffee_capacity),
+            ("Leche fría", s.cold_milk, c.cold_milk_capacity),
+            ("Espuma de leche", s.foam, c.milk_foam_capacity),
+            ("Cacao", s.cocoa, c.cocoa_capacity),
+            ("Agua caliente", s.water, c.hot_water_capacity),
+        ];
+        let mut lines = vec![format!("CoffeeGPT ({:?} ms)", s.time), String::new()];
+        lines.push("Stock:".to_string());
+        for (name, available, capacity) in gauges {
+            lines.push(format!(
+                "  {:<16} {} {:>5}/{}",
+                name,
+                gauge(available, capacity),
+                available,
+                capacity
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push(format!(
+            "Pedidos: {:?} completos, {:?} rechazados, {:?} servidos parcialmente. Ritmo: {:?} por minuto.",
+            s.orders_served,
+            s.orders_rejected,
+            s.orders_partially_served,
+            self.throughput(s.time)
+        ));
+        lines.push(format!("En curso ({:?}):", self.orders.len()));
+        for (id, order) in self.orders.iter().take(ORDERS_SHOWN) {
+            let state = match order.waiting_for {
+                Some(ingredient) => format!("esperando {}", ingredient.name()),
+                None => "en preparación".to_string(),
+            };
+            lines.push(format!(
+                "  Pedido {:<5} {:<8} {:<30} {:?} ingredientes servidos",
+                id,
+                order.priority.name(),
+                state,
+                order.served
+            ));
+        }
+        if self.orders.len() > ORDERS_SHOWN {
+            lines.push(format!(
+                "  ... y {:?} más",
+                self.orders.len() - ORDERS_SHOWN
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push("Eventos recientes:".to_string());
+        for record in &self.recent {
+            lines.push(format!(
+                "  [{:>6} ms] {}",
+                record.time,
+                record.event.describe()
+            ));
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+impl<W: Write + Send> StatsRenderer for Dashboard<W> {
+    fn render(&mut self, snapshot: &StatsSnapshot) -> io::Result<()> {
+        while let Ok(record) = self.events.try_recv() {
+            self.update(record);
+        }
+        let frame = self.frame(snapshot);
+        write!(self.output, "{}{}", CLEAR_SCREEN, frame)?;
+        self.output.flush()
+    }
+}
+
+/// Barra de [`GAUGE_WIDTH`] caracteres llena en la proporción de `available` sobre `capacity`.
+fn gauge(available: u32, capacity: u32) -> String {
+    let filled = match capacity {
+        0 => 0,
+        _ => (available.min(capacity) as usize * GAUGE_WIDTH) / capacity as usize,
+    };
+    format!(
+        "[{}{}]",
+        "#".repeat(filled),
+        ".".repeat(GAUGE_WIDTH - filled)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::error::CustomError;
+    use std::sync::mpsc;
+
+    fn record(time: u64, event: MachineEvent) -> EventRecord {
+        EventRecord {
+            time,
+            thread: "barista-0".to_string(),
+            event,
+        }
+    }
+
+    #[test]
+    fn test_gauge() {
+        assert_eq!(gauge(0, 100), format!("[{}]", ".".repeat(GAUGE_WIDTH)));
+        assert_eq!(
+            gauge(50, 100),
+            format!("[{}{}]", "#".repeat(15), ".".repeat(15))
+        );
+        assert_eq!(gauge(120, 100), format!("[{}]", "#".repeat(GAUGE_WIDTH)));
+        assert_eq!(gauge(10, 0), format!("[{}]", ".".repeat(GAUGE_WIDTH)));
+    }
+
+    #[test]
+    fn test_dashboard_tracks_orders() {
+        let (sender, receiver) = mpsc::channel();
+        let mut dashboard = Dashboard::new(Config::default(), receiver, Vec::new());
+        for (time, event) in [
+            (
+                0,
+                MachineEvent::OrderReceived {
+                    order_id: 0,
+                    reference: None,
+                    priority: Priority::Regular,
+                },
+            ),
+            (
+                10,
+                MachineEvent::OrderReceived {
+                    order_id: 1,
+                    reference: None,
+                    priority: Priority::Vip,
+                },
+            ),
+            (
+                20,
+                MachineEvent::OrderReceived {
+                    order_id: 2,
+                    reference: None,
+                    priority: Priority::Regular,
+                },
+            ),
+            (
+                30,
+                MachineEvent::OrderWaiting {
+                    order_id: 1,
+                    ingredient: Ingredient::MilkFoam,
+                },
+            ),
+            (
+                40,
+                MachineEvent::IngredientServed {
+                    order_id: 0,
+                    ingredient: Ingredient::GroundCoffee,
+                    amount: 10,
+                },
+            ),
+            (
+                50,
+                MachineEvent::OrderRejected {
+                    order_id: 2,
+                    error: CustomError::InsufficientIngredients,
+                    partially_served: false,
+                },
+            ),
+            (
+                60,
+                MachineEvent::OrderCompleted {
+                    order_id: 0,
+                    reference: None,
+                    received_at: 0,
+                    started: 0,
+                    first_ingredient: 40,
+                    completed: 60,
+                },
+            ),
+        ] {
+            sender.send(record(time, event)).unwrap();
+        }
+        let snapshot = StatsSnapshot {
+            time: 6000,
+            orders_served: 1,
+            orders_rejected: 1,
+            foam: 50,
+            ..StatsSnapshot::default()
+        };
+        dashboard.render(&snapshot).unwrap();
+
+        assert_eq!(dashboard.orders.len(), 1);
+        assert_eq!(dashboard.orders[&1].waiting_for, Some(Ingredient::MilkFoam));
+        let output = String::from_utf8(dashboard.output.clone()).unwrap();
+        assert!(output.starts_with(CLEAR_SCREEN));
+        assert!(output.contains("CoffeeGPT (6000 ms)"));
+        assert!(output.contains(&format!("  Espuma de leche  {}    50/100", gauge(50, 100))));
+        assert!(output.contains(
+            "Pedidos: 1 completos, 1 rechazados, 0 servidos parcialmente. Ritmo: 10 por minuto."
+        ));
+        assert!(output.contains("En curso (1):"));
+        assert!(output.contains("Pedido 1     vip      esperando espuma de leche"));
+        assert!(output.contains("[    60 ms] Pedido listo id:0."));
+    }
+
+    #[test]
+    fn test_throughput_window() {
+        let (_, receiver) = mpsc::channel();
+        let mut dashboard = Dashboard::new(Config::default(), receiver, Vec::new());
+        dashboard.completed.extend([1000, 12000, 15000, 18000]);
+        assert_eq!(dashboard.throughput(20000), 3 * 60000 / THROUGHPUT_WINDOW);
+        assert_eq!(dashboard.completed.len(), 3);
+
+        let (_, receiver) = mpsc::channel();
+        let mut dashboard = Dashboard::new(Config::default(), receiver, Vec::new());
+        dashboard.completed.push_back(500);
+        assert_eq!(dashboard.throughput(2000), 30);
+    }
+}
@@ -0,0 +1,199 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::helpers::config::DispenserPolicy;
+use crate::structs::order::Order;
+use crate::structs::priority::Priority;
+
+/// Pedido esperando un dispensador de un contenedor.
+#[derive(Debug)]
+struct Contender {
+    order_id: u32,
+    priority: Priority,
+    received_at: u64,
+    /// Turno del pedido: cuántos pedidos se anotaron en el contenedor antes que él.
+    ticket: u64,
+    /// Flag compartido entre todos los contenedores en los que espera el pedido.
+    /// Deja de valer `true` cuando el pedido consigue algún dispensador, para que mientras lo usa no
+    /// les quite a otros pedidos los dispensadores de los demás ingredientes.
+    waiting: Arc<AtomicBool>,
+}
+
+impl Contender {
+    /// Indica si el pedido tiene que servirse antes que `order`: por mayor urgencia o, si es la misma,
+    /// por haber llegado antes.
+    fn goes_before(&self, order: &Order, now: u64, aging_time: u64) -> bool {
+        let urgency = self.priority.urgency(self.received_at, now, aging_time);
+        let order_urgency = order.priority.urgency(order.received_at, now, aging_time);
+        urgency > order_urgency
+            || (urgency == order_urgency
+                && (self.received_at, self.order_id) < (order.received_at, order.id))
+    }
+}
+
+/// Pedidos esperando un dispensador de un contenedor.
+/// Con [`DispenserPolicy::Priority`], cuando se libera un dispensador lo toma el pedido bloqueado más urgente según su
+/// [`Priority`] y lo que lleva esperando.
+/// Con [`DispenserPolicy::Fifo`] los pedidos toman turno al llegar al contenedor y los dispensadores se entregan
+/// estrictamente en ese orden, aunque el pedido que tiene el turno esté usando otro contenedor.
+#[derive(Debug)]
+pub struct DispenserQueue {
+    policy: DispenserPolicy,
+    contenders: Vec<Contender>,
+    /// Turno que le corresponde al próximo pedido que se anote.
+    next_ticket: u64,
+}
+
+impl DispenserQueue {
+    pub fn new(policy: DispenserPolicy) -> DispenserQueue {
+        DispenserQueue {
+            policy,
+            contenders: Vec::new(),
+            next_ticket: 0,
+        }
+    }
+
+    /// Anota la llegada del pedido al contenedor, antes de intentar tomar un dispensador.
+    /// Con [`DispenserPolicy::Fifo`] le da su turno; con [`DispenserPolicy::Priority`] los pedidos recién
+    /// se anotan al bloquearse, con [`DispenserQueue::register`].
+    pub fn arrive(&mut self, order: &Order) {
+        if self.policy == DispenserPolicy::Fifo {
+            self.register(order, &Arc::new(AtomicBool::new(true)));
+        }
+    }
+
+    /// Anota al pedido como esperando un dispensador, si no lo estaba.
+    /// `waiting` es el flag del pedido, compartido con los demás contenedores en los que espera.
+    pub fn register(&mut self, order: &Order, waiting: &Arc<AtomicBool>) {
+        if self.contenders.iter().all(|c| c.order_id != order.id) {
+            self.contenders.push(Contender {
+                order_id: order.id,
+                priority: order.priority,
+                received_at: order.received_at,
+                ticket: self.next_ticket,
+                waiting: waiting.clone(),
+            });
+            self.next_ticket += 1;
+        }
+    }
+
+    /// Indica si el pedido puede tomar uno de los `free_dispensers` dispensadores libres:
+    /// hay menos pedidos esperando que deban servirse antes que él que dispensadores libres.
+    pub fn may_take(&self, order: &Order, free_dispensers: u32, now: u64, aging_time: u64) -> bool {
+        let ahead = match self.policy {
+            DispenserPolicy::Priority => self
+                .contenders
+                .iter()
+                .filter(|c| c.order_id != order.id && c.waiting.load(Ordering::SeqCst))
+                .filter(|c| c.goes_before(order, now, aging_time))
+                .count(),
+            DispenserPolicy::Fifo => {
+                let ticket = self
+                    .contenders
+                    .iter()
+                    .find(|c| c.order_id == order.id)
+                    .map_or(self.next_ticket, |c| c.ticket);
+                self.contenders.iter().filter(|c| c.ticket < ticket).count()
+            }
+        };
+        ahead < free_dispensers as usize
+    }
+
+    /// El pedido consiguió un dispensador: deja de esperar en este contenedor y,
+    /// mientras lo usa, tampoco cuenta como esperando en los demás.
+    /// Devuelve si el pedido estaba esperando: los pedidos que le cedían los dispensadores de los demás
+    /// contenedores ya pueden tomarlos, por lo que hay que avisarles.
+    pub fn take(&mut self, order_id: u32) -> bool {
+        match self.contenders.iter().position(|c| c.order_id == order_id) {
+            Some(position) => self
+                .contenders
+                .remove(position)
+                .waiting
+                .swap(false, Ordering::SeqCst),
+            None => false,
+        }
+    }
+
+    /// Quita al pedido de la espera, por ejemplo porque terminó o se canceló.
+    pub fn withdraw(&mut self, order_id: u32) {
+        self.contenders.retain(|c| c.order_id != order_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(id: u32, priority: Priority, received_at: u64) -> Order {
+        let mut order = Order::new(id, 1, 1, 1, 1).unwrap();
+        order.priority = priority;
+        order.received_at = received_at;
+        order
+    }
+
+    #[test]
+    fn test_higher_priority_takes_first() {
+        let mut queue = DispenserQueue::new(DispenserPolicy::Priority);
+        let staff = order(0, Priority::Staff, 100);
+        let regular = order(1, Priority::Regular, 0);
+        queue.register(&staff, &Arc::new(AtomicBool::new(true)));
+        queue.register(&regular, &Arc::new(AtomicBool::new(true)));
+        assert!(queue.may_take(&staff, 1, 100, 1000));
+        assert!(!queue.may_take(&regular, 1, 100, 1000));
+        assert!(queue.may_take(&regular, 2, 100, 1000));
+
+        assert!(queue.take(staff.id));
+        assert!(!queue.take(staff.id));
+        assert_eq!(queue.contenders.len(), 1);
+        assert!(queue.may_take(&regular, 1, 100, 1000));
+    }
+
+    #[test]
+    fn test_aging_prevents_starvation() {
+        let mut queue = DispenserQueue::new(DispenserPolicy::Priority);
+        let regular = order(0, Priority::Regular, 0);
+        let early_staff = order(1, Priority::Staff, 1500);
+        let late_staff = order(2, Priority::Staff, 2500);
+        queue.register(&regular, &Arc::new(AtomicBool::new(true)));
+        queue.register(&early_staff, &Arc::new(AtomicBool::new(true)));
+        assert!(!queue.may_take(&regular, 1, 2500, 1000));
+        queue.withdraw(early_staff.id);
+        queue.register(&late_staff, &Arc::new(AtomicBool::new(true)));
+        assert!(queue.may_take(&regular, 1, 2500, 1000));
+        assert!(!queue.may_take(&late_staff, 1, 2500, 1000));
+    }
+
+    #[test]
+    fn test_orders_using_another_dispenser_do_not_wait() {
+        let mut queue = DispenserQueue::new(DispenserPolicy::Priority);
+        let waiting = Arc::new(AtomicBool::new(true));
+        let vip = order(0, Priority::Vip, 0);
+        let regular = order(1, Priority::Regular, 0);
+        queue.register(&vip, &waiting);
+        assert!(!queue.may_take(&regular, 1, 0, 1000));
+        waiting.store(false, Ordering::SeqCst);
+        assert!(queue.may_take(&regular, 1, 0, 1000));
+        queue.withdraw(vip.id);
+        assert!(queue.contenders.is_empty());
+    }
+
+    #[test]
+    fn test_fifo_serves_in_arrival_order() {
+        let mut queue = DispenserQueue::new(DispenserPolicy::Fifo);
+        let regular = order(0, Priority::Regular, 0);
+        let staff = order(1, Priority::Staff, 100);
+        queue.arrive(&regular);
+        queue.arrive(&staff);
+        assert!(!queue.may_take(&staff, 1, 100, 1000));
+        assert!(queue.may_take(&regular, 1, 100, 1000));
+        assert!(queue.may_take(&staff, 2, 100, 1000));
+
+        // El turno se respeta aunque el pedido esté usando otro contenedor.
+        let waiting = Arc::new(AtomicBool::new(false));
+        queue.register(&regular, &waiting);
+        assert!(!queue.may_take(&staff, 1, 100, 1000));
+
+        queue.take(regular.id);
+        assert!(queue.may_take(&staff, 1, 100, 1000));
+    }
+}
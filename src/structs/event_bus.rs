@@ -0,0 +1,198 @@
+use std::io::Write;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::helpers::logger::{self, LogRecord};
+use crate::structs::machine_event::MachineEvent;
+
+/// Evento publicado junto con el momento, en milisegundos del reloj, en que ocurrió y el hilo que lo publicó.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventRecord {
+    pub time: u64,
+    pub thread: String,
+    pub event: MachineEvent,
+}
+
+impl EventRecord {
+    /// Mensaje del logger con la descripción `message` del evento y el contexto de quien lo publicó.
+    pub fn log_record<'a>(&'a self, message: &'a str) -> LogRecord<'a> {
+        LogRecord {
+            time: self.time,
+            level: self.event.level(),
+            module: self.event.module(),
+            thread: &self.thread,
+            order: self.event.order_id(),
+            message,
+        }
+    }
+}
+
+/// Algo que procesa los eventos de la cafetera en su propio hilo. Ver [`EventBus::attach`].
+pub trait EventSubscriber: Send {
+    fn handle(&mut self, record: &EventRecord);
+}
+
+/// Canal por el que la cafetera publica sus [`MachineEvent`].
+/// Cada suscriptor tiene su propio canal, así uno lento no demora a la cafetera ni a los demás.
+/// Los clones comparten los suscriptores.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<Sender<EventRecord>>>>,
+}
+
+impl EventBus {
+    /// Devuelve un canal que recibe los eventos que se publiquen desde ahora.
+    /// El canal se desconecta con [`EventBus::close`].
+    pub fn subscribe(&self) -> Receiver<EventRecord> {
+        let (sender, receiver) = mpsc::channel();
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(sender);
+        }
+        receiver
+    }
+
+    /// Atiende los eventos con el suscriptor en un hilo nuevo, hasta que se cierre el bus.
+    /// Devuelve el [`JoinHandle`] del hilo, para esperar a que procese todos los eventos.
+    pub fn attach(&self, mut subscriber: Box<dyn EventSubscriber>) -> JoinHandle<()> {
+        let receiver = self.subscribe();
+        thread::spawn(move || {
+            for record in receiver {
+                subscriber.handle(&record);
+            }
+        })
+    }
+
+    /// Envía el evento a todos los suscriptores, con el momento `time` y el nombre del hilo actual.
+    /// Olvida a los suscriptores que ya no reciben eventos.
+    pub fn publish(&self, time: u64, event: MachineEvent) {
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            if subscribers.is_empty() {
+                return;
+            }
+            let record = EventRecord {
+                time,
+                thread: logger::thread_name(),
+                event,
+            };
+            subscribers.retain(|subscriber| subscriber.send(record.clone()).is_ok());
+        }
+    }
+
+    /// Desconecta a todos los suscriptores. Los eventos que se publiquen después se descartan.
+    pub fn close(&self) {
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.clear();
+        }
+    }
+}
+
+/// Imprime cada evento por pantalla con el logger, si el filtro habilita su nivel para su módulo.
+pub struct ConsoleSubscriber;
+
+impl EventSubscriber for ConsoleSubscriber {
+    fn handle(&mut self, record: &EventRecord) {
+        if logger::enabled(record.event.module(), record.event.level()) {
+            logger::write(&record.log_record(&record.event.describe()));
+        }
+    }
+}
+
+/// Escribe todos los eventos, sin filtrar, una línea cada uno en el formato de [`LogRecord::format`].
+pub struct FileSubscriber<W: Write + Send> {
+    output: W,
+}
+
+impl<W: Write + Send> FileSubscriber<W> {
+    pub fn new(output: W) -> FileSubscriber<W> {
+        FileSubscriber { output }
+    }
+}
+
+impl<W: Write + Send> EventSubscriber for FileSubscriber<W> {
+    fn handle(&mut self, record: &EventRecord) {
+        let description = record.event.describe();
+        let written = writeln!(self.output, "{}", record.log_record(&description).format())
+            .and_then(|_| self.output.flush());
+        if let Err(e) = written {
+            error!("No se pudo escribir el evento en el archivo: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::machine_event::Ingredient;
+
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_publish_to_subscribers() {
+        let events = EventBus::default();
+        events.publish(10, MachineEvent::Shutdown);
+        let receiver = events.subscribe();
+        let clone = events.clone();
+        clone.publish(
+            20,
+            MachineEvent::RefillStarted {
+                ingredient: Ingredient::HotWater,
+            },
+        );
+        events.close();
+        events.publish(30, MachineEvent::Shutdown);
+
+        let records: Vec<EventRecord> = receiver.iter().collect();
+        assert_eq!(
+            records,
+            vec![EventRecord {
+                time: 20,
+                thread: logger::thread_name(),
+                event: MachineEvent::RefillStarted {
+                    ingredient: Ingredient::HotWater
+                }
+            }]
+        );
+    }
+
+    #[test]
+    fn test_file_subscriber() {
+        let events = EventBus::default();
+        let output = SharedOutput::default();
+        let handle = events.attach(Box::new(FileSubscriber::new(output.clone())));
+        let thread = logger::thread_name();
+        events.publish(
+            5,
+            MachineEvent::IngredientServed {
+                order_id: 2,
+                ingredient: Ingredient::Cocoa,
+                amount: 15,
+            },
+        );
+        events.publish(8, MachineEvent::Shutdown);
+        events.close();
+        handle.join().unwrap();
+
+        let written = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            written,
+            format!(
+                "[     5 ms] [DEBUG] [orders] [{0}] [pedido 2] Pedido 2: se sirvieron 15 de cacao.\n\
+                 [     8 ms] [INFO] [general] [{0}] Cafetera apagada.\n",
+                thread
+            )
+        );
+    }
+}
@@ -1,9 +1,12 @@
 use std::sync::{Arc, Condvar, Mutex};
-use std::thread;
-use std::time::Duration;
 
-use crate::helpers::constants::{A, CANTIDAD_RELLENO, REFILL_WATER_TIME};
+use crate::helpers::clock::Clock;
+use crate::helpers::config::Config;
 use crate::helpers::error::CustomError;
+use crate::structs::dispenser_queue::DispenserQueue;
+use crate::structs::event_bus::EventBus;
+use crate::structs::machine_event::{Ingredient, MachineEvent};
+use crate::structs::tracer::Tracer;
 
 /// Contenedor de agua caliente conectado a la red.
 #[derive(Debug)]
@@ -12,39 +15,100 @@ pub struct HotWaterContainer {
     pub hot_water: u32,
     /// Cantidad de agua caliente ya utilizada.
     pub used: u32,
+    /// Cantidad de dispensadores de agua caliente libres para servir un pedido.
+    pub free_dispensers: u32,
+    /// Pedidos bloqueados esperando un dispensador de agua caliente, en orden de prioridad.
+    pub dispenser_queue: DispenserQueue,
+    /// Cantidades de agua caliente que esperan los dispensadores que no pudieron servir por falta de stock.
+    pub waiting: Vec<u32>,
+    /// Flag que indica que se está rellenando el agua caliente, por lo que no se puede servir.
+    pub refilling: bool,
+    /// Cantidad de veces que se rellenó el agua caliente.
+    pub refills: u32,
+    /// Cantidad de agua que se calentó de la red al rellenar.
+    pub heated: u32,
     /// Flag para indicar que ya no se deben rellenar el agua caliente.
     pub shutdown: bool,
 }
 
-/// Caliente agua de la red cuando tiene una disponibilidad menor a [`CANTIDAD_RELLENO`].
+/// Caliente agua de la red cuando tiene una disponibilidad menor a [`Config::refill_threshold`]
+/// o cuando no alcanza para algún pedido que está esperando ([`HotWaterContainer::demand`]).
 /// Es un loop donde se tiene en cuenta la disponibilidad del agua caliente y si el mismo debe apagarse.
-/// Mientras se esta recargando el agua caliente no se puede utilizar el contenedor.
+/// Mientras se esta recargando el agua caliente no se puede utilizar el contenedor, lo que se indica con [`HotWaterContainer::refilling`].
+/// Las esperas se hacen con el [`Clock`] indicado, los rellenados se publican en `events` y se registran en `tracer`.
 /// Solo termina cuando debe apagarse. Como esta conectada a la red podemos suponer que nunca se quedará sin agua.
 impl HotWaterContainer {
-    pub fn new() -> HotWaterContainer {
+    pub fn new(config: &Config) -> HotWaterContainer {
         HotWaterContainer {
-            hot_water: A,
+            hot_water: config.hot_water_capacity,
             used: 0,
+            free_dispensers: config.dispensers,
+            dispenser_queue: DispenserQueue::new(config.hot_water_dispenser_policy),
+            waiting: Vec::new(),
+            refilling: false,
+            refills: 0,
+            heated: 0,
             shutdown: false,
         }
     }
 
+    /// Mayor cantidad de agua caliente que espera algún dispensador, o 0 si no hay ninguno esperando.
+    pub fn demand(&self) -> u32 {
+        self.waiting.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Quita de los pedidos en espera uno que esperaba la cantidad indicada.
+    pub fn stop_waiting(&mut self, amount: u32) {
+        if let Some(position) = self.waiting.iter().position(|waiting| *waiting == amount) {
+            self.waiting.swap_remove(position);
+        }
+    }
+
     pub fn heat_water(
         hot_water_container: Arc<(Mutex<HotWaterContainer>, Condvar)>,
+        config: Config,
+        clock: Clock,
+        events: EventBus,
+        tracer: Tracer,
     ) -> Result<(), CustomError> {
         let (h_w_lock, h_w_cvar) = &*hot_water_container;
         loop {
-            if let Ok(mut state) = h_w_cvar.wait_while(h_w_lock.lock()?, |h_w_container| {
-                h_w_container.hot_water > CANTIDAD_RELLENO && !h_w_container.shutdown
-            }) {
-                if state.shutdown {
-                    break;
-                }
-                println!("[DEBUG] Calentando agua.");
-                thread::sleep(Duration::from_millis(REFILL_WATER_TIME));
-                state.hot_water = A;
-                h_w_cvar.notify_all();
+            let mut state = clock.wait_while(h_w_lock, h_w_cvar, |h_w_container| {
+                h_w_container.hot_water > config.refill_threshold
+                    && h_w_container.hot_water >= h_w_container.demand()
+                    && !h_w_container.shutdown
+            })?;
+            if state.shutdown {
+                break;
             }
+            events.publish(
+                clock.now(),
+                MachineEvent::RefillStarted {
+                    ingredient: Ingredient::HotWater,
+                },
+            );
+            state.refilling = true;
+            let refill = state.refills.into();
+            drop(state);
+            tracer.measure(&clock, "refill", "calentar agua", || {
+                clock.sleep(config.action_time(config.refill_water_time, "calentar_agua", refill))
+            });
+
+            let mut state =
+                tracer.measure(&clock, "lock", "lock agua caliente", || h_w_lock.lock())?;
+            state.refilling = false;
+            state.refills += 1;
+            let heated = config.hot_water_capacity.saturating_sub(state.hot_water);
+            state.heated += heated;
+            state.hot_water = config.hot_water_capacity;
+            events.publish(
+                clock.now(),
+                MachineEvent::RefillFinished {
+                    ingredient: Ingredient::HotWater,
+                    amount: heated,
+                },
+            );
+            h_w_cvar.notify_all();
         }
         Ok(())
     }
@@ -53,28 +117,39 @@ impl HotWaterContainer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::helpers::constants::{A, CANTIDAD_RELLENO, N};
+    use std::thread;
 
     #[test]
     fn test_new_hot_water_container() {
-        let hot_water_container = HotWaterContainer::new();
+        let hot_water_container = HotWaterContainer::new(&Config::default());
         assert_eq!(hot_water_container.hot_water, A);
         assert_eq!(hot_water_container.used, 0);
+        assert_eq!(hot_water_container.free_dispensers, N);
         assert_eq!(hot_water_container.shutdown, false);
     }
 
     #[test]
     fn test_heat_water_refill() -> Result<(), CustomError> {
-        let hot_water_container = Arc::new((Mutex::new(HotWaterContainer::new()), Condvar::new()));
+        let hot_water_container = Arc::new((
+            Mutex::new(HotWaterContainer::new(&Config::default())),
+            Condvar::new(),
+        ));
         let hot_water_container_clone = hot_water_container.clone();
-        let thread_handle =
-            thread::spawn(
-                move || match HotWaterContainer::heat_water(hot_water_container_clone) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        println!("[ERROR] Testeando heat_water: {:?}", e);
-                    }
-                },
-            );
+        let thread_handle = thread::spawn(move || {
+            match HotWaterContainer::heat_water(
+                hot_water_container_clone,
+                Config::default(),
+                Clock::real(),
+                EventBus::default(),
+                Tracer::default(),
+            ) {
+                Ok(_) => {}
+                Err(e) => {
+                    println!("[ERROR] Testeando heat_water: {:?}", e);
+                }
+            }
+        });
 
         let (hot_water_lock, hot_water_cvar) = &*hot_water_container;
         match hot_water_lock.lock() {
@@ -123,17 +198,25 @@ mod tests {
 
     #[test]
     fn test_heat_water_shutdown() -> Result<(), CustomError> {
-        let hot_water_container = Arc::new((Mutex::new(HotWaterContainer::new()), Condvar::new()));
+        let hot_water_container = Arc::new((
+            Mutex::new(HotWaterContainer::new(&Config::default())),
+            Condvar::new(),
+        ));
         let hot_water_container_clone = hot_water_container.clone();
-        let thread_handle =
-            thread::spawn(
-                move || match HotWaterContainer::heat_water(hot_water_container_clone) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        println!("[ERROR] Testeando heat_water: {:?}", e);
-                    }
-                },
-            );
+        let thread_handle = thread::spawn(move || {
+            match HotWaterContainer::heat_water(
+                hot_water_container_clone,
+                Config::default(),
+                Clock::real(),
+                EventBus::default(),
+                Tracer::default(),
+            ) {
+                Ok(_) => {}
+                Err(e) => {
+                    println!("[ERROR] Testeando heat_water: {:?}", e);
+                }
+            }
+        });
 
         let (hot_water_lock, hot_water_cvar) = &*hot_water_container;
         match hot_water_lock.lock() {
@@ -0,0 +1,193 @@
+use crate::helpers::random::split_mix;
+
+/// Resumen de una serie de duraciones en milisegundos: promedio y percentiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LatencySummary {
+    /// Cantidad de muestras.
+    pub count: usize,
+    pub mean: u64,
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl LatencySummary {
+    /// Resume las muestras. Los percentiles se calculan por el método del rango más cercano.
+    /// Sin muestras todos los valores son 0.
+    pub fn from_samples(samples: &[u64]) -> LatencySummary {
+        if samples.is_empty() {
+            return LatencySummary::default();
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        let percentile = |percentage: usize| {
+            let rank = (percentage * sorted.len()).div_ceil(100);
+            sorted[rank.max(1) - 1]
+        };
+        LatencySummary {
+            count: sorted.len(),
+            mean: sorted.iter().sum::<u64>() / sorted.len() as u64,
+            p50: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+            max: sorted[sorted.len() - 1],
+        }
+    }
+
+    /// Describe el resumen en una línea, por ejemplo `10 pedidos, promedio 12 ms, p50 10 ms, ...`.
+    pub fn describe(&self) -> String {
+        format!(
+            "{:?} pedidos, promedio {:?} ms, p50 {:?} ms, p95 {:?} ms, p99 {:?} ms, máximo {:?} ms",
+            self.count, self.mean, self.p50, self.p95, self.p99, self.max
+        )
+    }
+}
+
+/// Límites superiores, en milisegundos, de los intervalos de un [`LatencyHistogram`].
+pub const HISTOGRAM_BUCKETS: [u64; 10] = [10, 50, 100, 250, 500, 1000, 2500, 5000, 10000, 30000];
+
+/// Histograma acumulado de una serie de duraciones en milisegundos, como los de Prometheus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LatencyHistogram {
+    /// Cantidad de muestras menores o iguales a cada límite de [`HISTOGRAM_BUCKETS`].
+    pub buckets: [u64; HISTOGRAM_BUCKETS.len()],
+    /// Cantidad de muestras.
+    pub count: u64,
+    /// Suma de todas las muestras.
+    pub sum: u64,
+}
+
+impl LatencyHistogram {
+    /// Suma la muestra al histograma.
+    pub fn record(&mut self, sample: u64) {
+        self.count += 1;
+        self.sum += sample;
+        for (bucket, bound) in self.buckets.iter_mut().zip(HISTOGRAM_BUCKETS) {
+            if sample <= bound {
+                *bucket += 1;
+            }
+        }
+    }
+}
+
+/// Cantidad máxima de muestras que guarda un [`LatencySamples`] para calcular los percentiles.
+pub const RESERVOIR_SIZE: usize = 1024;
+
+/// Duraciones en milisegundos registradas durante la corrida, con memoria acotada sin importar cuántas sean.
+/// La cantidad, el promedio, el máximo y el histograma son exactos. Los percentiles se calculan sobre una
+/// muestra de hasta [`RESERVOIR_SIZE`] valores elegidos al azar, por lo que son exactos solo hasta esa cantidad.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LatencySamples {
+    histogram: LatencyHistogram,
+    max: u64,
+    reservoir: Vec<u64>,
+}
+
+impl LatencySamples {
+    /// Registra la muestra. Pasado [`RESERVOIR_SIZE`], la muestra reemplaza a una guardada con probabilidad
+    /// `RESERVOIR_SIZE / cantidad`, así todas tienen la misma chance de quedar. La elección depende solo de la
+    /// cantidad de muestras, por lo que dos corridas iguales guardan las mismas.
+    pub fn record(&mut self, sample: u64) {
+        let seen = self.histogram.count;
+        self.histogram.record(sample);
+        self.max = self.max.max(sample);
+        if self.reservoir.len() < RESERVOIR_SIZE {
+            self.reservoir.push(sample);
+            return;
+        }
+        let index = (split_mix(seen) % (seen + 1)) as usize;
+        if index < RESERVOIR_SIZE {
+            self.reservoir[index] = sample;
+        }
+    }
+
+    /// Histograma de todas las muestras registradas.
+    pub fn histogram(&self) -> LatencyHistogram {
+        self.histogram
+    }
+
+    /// Resumen de todas las muestras registradas, con los percentiles de las guardadas.
+    pub fn summary(&self) -> LatencySummary {
+        if self.histogram.count == 0 {
+            return LatencySummary::default();
+        }
+        LatencySummary {
+            count: self.histogram.count as usize,
+            mean: self.histogram.sum / self.histogram.count,
+            max: self.max,
+            ..LatencySummary::from_samples(&self.reservoir)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_samples() {
+        let samples: Vec<u64> = (1..=100).rev().collect();
+        let summary = LatencySummary::from_samples(&samples);
+        assert_eq!(summary.count, 100);
+        assert_eq!(summary.mean, 50);
+        assert_eq!(summary.p50, 50);
+        assert_eq!(summary.p95, 95);
+        assert_eq!(summary.p99, 99);
+        assert_eq!(summary.max, 100);
+
+        let summary = LatencySummary::from_samples(&[7]);
+        assert_eq!((summary.p50, summary.p99, summary.max), (7, 7, 7));
+        assert_eq!(LatencySummary::from_samples(&[]), LatencySummary::default());
+    }
+
+    #[test]
+    fn test_histogram_record() {
+        let mut histogram = LatencyHistogram::default();
+        for sample in [5, 10, 400, 40000] {
+            histogram.record(sample);
+        }
+        assert_eq!(histogram.count, 4);
+        assert_eq!(histogram.sum, 40415);
+        assert_eq!(histogram.buckets[0], 2);
+        assert_eq!(histogram.buckets[3], 2);
+        assert_eq!(histogram.buckets[4], 3);
+        assert_eq!(histogram.buckets[HISTOGRAM_BUCKETS.len() - 1], 3);
+    }
+
+    #[test]
+    fn test_samples_summary() {
+        let mut samples = LatencySamples::default();
+        assert_eq!(samples.summary(), LatencySummary::default());
+        for sample in (1..=100).rev() {
+            samples.record(sample);
+        }
+        let expected: Vec<u64> = (1..=100).collect();
+        assert_eq!(samples.summary(), LatencySummary::from_samples(&expected));
+        let histogram = samples.histogram();
+        assert_eq!((histogram.count, histogram.sum), (100, 5050));
+        assert_eq!(histogram.buckets[0], 10);
+        assert_eq!(histogram.buckets[2], 100);
+    }
+
+    #[test]
+    fn test_samples_memory_is_bounded() {
+        let mut samples = LatencySamples::default();
+        for sample in 1..=10 * RESERVOIR_SIZE as u64 {
+            samples.record(sample);
+        }
+        assert_eq!(samples.reservoir.len(), RESERVOIR_SIZE);
+        let summary = samples.summary();
+        assert_eq!(summary.count, 10 * RESERVOIR_SIZE);
+        assert_eq!(summary.mean, 5 * RESERVOIR_SIZE as u64);
+        assert_eq!(summary.max, 10 * RESERVOIR_SIZE as u64);
+        // Con muestras de todo el rango la mediana queda cerca de la mitad.
+        let half = 5 * RESERVOIR_SIZE as u64;
+        assert!(
+            summary.p50 > half * 8 / 10 && summary.p50 < half * 12 / 10,
+            "{:?}",
+            summary
+        );
+        assert_eq!(samples.histogram().count, 10 * RESERVOIR_SIZE as u64);
+    }
+}
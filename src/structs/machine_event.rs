@@ -0,0 +1,242 @@
+use crate::helpers::error::CustomError;
+use crate::helpers::logger::{LogLevel, LogModule};
+use crate::structs::priority::Priority;
+
+/// Ingredientes que maneja la cafetera, incluidos los que solo se usan para rellenar otro contenedor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ingredient {
+    CoffeeGrains,
+    GroundCoffee,
+    HotWater,
+    Cocoa,
+    ColdMilk,
+    MilkFoam,
+}
+
+impl Ingredient {
+    /// Nombre del ingrediente para los mensajes.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Ingredient::CoffeeGrains => "granos de café",
+            Ingredient::GroundCoffee => "café molido",
+            Ingredient::HotWater => "agua caliente",
+            Ingredient::Cocoa => "cacao",
+            Ingredient::ColdMilk => "leche fría",
+            Ingredient::MilkFoam => "espuma de leche",
+        }
+    }
+
+    /// Módulo del contenedor del ingrediente, para filtrar sus mensajes.
+    pub fn module(&self) -> LogModule {
+        match self {
+            Ingredient::CoffeeGrains | Ingredient::GroundCoffee => LogModule::Coffee,
+            Ingredient::HotWater => LogModule::Water,
+            Ingredient::Cocoa => LogModule::Cocoa,
+            Ingredient::ColdMilk | Ingredient::MilkFoam => LogModule::Milk,
+        }
+    }
+}
+
+/// Algo que pasó en la cafetera. Se publican en el [`crate::structs::event_bus::EventBus`] de la cafetera.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MachineEvent {
+    /// Se recibió un pedido, antes de validarlo y reservarle los ingredientes.
+    /// `reference` es el identificador que le dio el cliente, si lo indicó.
+    OrderReceived {
+        order_id: u32,
+        reference: Option<String>,
+        priority: Priority,
+    },
+    /// Un pedido espera un dispensador libre, o que se rellene el contenedor, para que le sirvan el ingrediente.
+    OrderWaiting {
+        order_id: u32,
+        ingredient: Ingredient,
+    },
+    /// Se le sirvió un ingrediente a un pedido.
+    IngredientServed {
+        order_id: u32,
+        ingredient: Ingredient,
+        amount: u32,
+    },
+    /// Empezó el rellenado del contenedor del ingrediente.
+    RefillStarted { ingredient: Ingredient },
+    /// Terminó el rellenado del contenedor del ingrediente, que recibió `amount`.
+    RefillFinished { ingredient: Ingredient, amount: u32 },
+    /// Quedan `available` del ingrediente, por debajo del `threshold`% de su capacidad.
+    LowStock {
+        ingredient: Ingredient,
+        available: u32,
+        threshold: u32,
+    },
+    /// Se completó un pedido. Los momentos son los de su [`crate::structs::order_times::OrderTimes`].
+    OrderCompleted {
+        order_id: u32,
+        reference: Option<String>,
+        received_at: u64,
+        started: u64,
+        first_ingredient: u64,
+        completed: u64,
+    },
+    /// No se pudo terminar un pedido por el error indicado. Si `partially_served` ya tenía algún ingrediente servido.
+    OrderRejected {
+        order_id: u32,
+        error: CustomError,
+        partially_served: bool,
+    },
+    /// Terminaron todos los pedidos y se apagaron los contenedores.
+    Shutdown,
+}
+
+impl MachineEvent {
+    /// Nivel con el que se informa el evento.
+    pub fn level(&self) -> LogLevel {
+        match self {
+            MachineEvent::OrderRejected { .. } => LogLevel::Error,
+            MachineEvent::LowStock { .. } => LogLevel::Warn,
+            MachineEvent::Shutdown => LogLevel::Info,
+            _ => LogLevel::Debug,
+        }
+    }
+
+    /// Módulo con el que se filtra el evento: el del contenedor para los rellenados y alertas, el de pedidos para el resto.
+    pub fn module(&self) -> LogModule {
+        match self {
+            MachineEvent::RefillStarted { ingredient }
+            | MachineEvent::RefillFinished { ingredient, .. }
+            | MachineEvent::LowStock { ingredient, .. } => ingredient.module(),
+            MachineEvent::Shutdown => LogModule::General,
+            _ => LogModule::Orders,
+        }
+    }
+
+    /// Pedido al que corresponde el evento, si corresponde a uno.
+    pub fn order_id(&self) -> Option<u32> {
+        match self {
+            MachineEvent::OrderReceived { order_id, .. }
+            | MachineEvent::OrderWaiting { order_id, .. }
+            | MachineEvent::IngredientServed { order_id, .. }
+            | MachineEvent::OrderCompleted { order_id, .. }
+            | MachineEvent::OrderRejected { order_id, .. } => Some(*order_id),
+            _ => None,
+        }
+    }
+
+    /// Descripción del evento para los mensajes.
+    pub fn describe(&self) -> String {
+        match self {
+            MachineEvent::OrderReceived {
+                order_id,
+                reference,
+                priority,
+            } => format!(
+                "Pedido recibido id:{:?}{}, prioridad {}.",
+                order_id,
+                describe_reference(reference),
+                priority.name()
+            ),
+            MachineEvent::OrderWaiting {
+                order_id,
+                ingredient,
+            } => format!("Pedido {:?}: esperando {}.", order_id, ingredient.name()),
+            MachineEvent::IngredientServed {
+                order_id,
+                ingredient,
+                amount,
+            } => format!(
+                "Pedido {:?}: se sirvieron {:?} de {}.",
+                order_id,
+                amount,
+                ingredient.name()
+            ),
+            MachineEvent::RefillStarted { ingredient } => {
+                format!("Rellenando {}.", ingredient.name())
+            }
+            MachineEvent::RefillFinished { ingredient, amount } => {
+                format!("Se rellenaron {:?} de {}.", amount, ingredient.name())
+            }
+            MachineEvent::LowStock {
+                ingredient,
+                available,
+                threshold,
+            } => format!(
+                "El contenedor de {} se encuentra por debajo de {:?}% de su capacidad, quedan {:?}.",
+                ingredient.name(),
+                threshold,
+                available
+            ),
+            MachineEvent::OrderCompleted {
+                order_id,
+                reference,
+                received_at,
+                started,
+                first_ingredient,
+                completed,
+            } => format!(
+                "Pedido listo id:{:?}{}. Recibido: {:?} ms, tomado: {:?} ms, primer ingrediente: {:?} ms, completado: {:?} ms.",
+                order_id, describe_reference(reference), received_at, started, first_ingredient, completed
+            ),
+            MachineEvent::OrderRejected {
+                order_id,
+                error,
+                partially_served: false,
+            } => format!("Pedido {:?} rechazado: {:?}.", order_id, error),
+            MachineEvent::OrderRejected {
+                order_id,
+                error,
+                partially_served: true,
+            } => format!("Pedido {:?} servido parcialmente: {:?}.", order_id, error),
+            MachineEvent::Shutdown => "Cafetera apagada.".to_string(),
+        }
+    }
+}
+
+/// Identificador del cliente entre paréntesis para las descripciones, o nada si no lo indicó.
+fn describe_reference(reference: &Option<String>) -> String {
+    match reference {
+        Some(reference) => format!(" ({})", reference),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_machine_event() {
+        let event = MachineEvent::LowStock {
+            ingredient: Ingredient::CoffeeGrains,
+            available: 150,
+            threshold: 20,
+        };
+        assert_eq!(event.level(), LogLevel::Warn);
+        assert_eq!(event.module(), LogModule::Coffee);
+        assert_eq!(event.order_id(), None);
+        assert_eq!(
+            event.describe(),
+            "El contenedor de granos de café se encuentra por debajo de 20% de su capacidad, quedan 150."
+        );
+        let event = MachineEvent::OrderRejected {
+            order_id: 4,
+            error: CustomError::InsufficientIngredients,
+            partially_served: true,
+        };
+        assert_eq!(event.level(), LogLevel::Error);
+        assert_eq!(event.module(), LogModule::Orders);
+        assert_eq!(event.order_id(), Some(4));
+        assert_eq!(
+            event.describe(),
+            "Pedido 4 servido parcialmente: InsufficientIngredients."
+        );
+        let event = MachineEvent::OrderReceived {
+            order_id: 2,
+            reference: Some("A12".to_string()),
+            priority: Priority::Vip,
+        };
+        assert_eq!(event.order_id(), Some(2));
+        assert_eq!(
+            event.describe(),
+            "Pedido recibido id:2 (A12), prioridad vip."
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::fs;
+
+use crate::helpers::error::CustomError;
+use crate::structs::order::Order;
+use crate::structs::priority::Priority;
+
+/// Cantidades de cada ingrediente de una bebida del menú.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recipe {
+    /// Cantidad de café molido.
+    pub ground_coffee: u32,
+    /// Cantidad de agua caliente.
+    pub hot_water: u32,
+    /// Cantidad de cacao.
+    pub cocoa: u32,
+    /// Cantidad de espuma de leche.
+    pub milk_foam: u32,
+}
+
+/// Tamaños en los que se puede pedir una bebida, con el porcentaje de la receta que se sirve.
+const SIZES: [(&str, u32); 3] = [("chico", 75), ("mediano", 100), ("grande", 150)];
+
+/// Bebidas del menú por defecto: `(nombre, café, agua, cacao, espuma)`.
+const DEFAULT_RECIPES: [(&str, u32, u32, u32, u32); 7] = [
+    ("espresso", 10, 5, 0, 0),
+    ("americano", 10, 20, 0, 0),
+    ("cortado", 10, 5, 0, 5),
+    ("cappuccino", 10, 5, 0, 10),
+    ("latte", 8, 5, 0, 15),
+    ("mocha", 10, 5, 5, 10),
+    ("chocolate_caliente", 0, 10, 15, 5),
+];
+
+/// Menú de bebidas con nombre que se pueden pedir en lugar de las cantidades de cada ingrediente.
+///
+/// El archivo de menú tiene una bebida por línea con sus cantidades en el mismo orden que los pedidos:
+/// ```text
+/// # nombre = <cafe molido>,<agua caliente>,<cacao>,<espuma de leche>
+/// espresso = 10,5,0,0
+/// cappuccino = 10,5,0,10
+/// ```
+/// Los nombres no distinguen mayúsculas y no pueden tener espacios.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Menu {
+    recipes: HashMap<String, Recipe>,
+}
+
+impl Default for Menu {
+    fn default() -> Self {
+        Menu {
+            recipes: DEFAULT_RECIPES
+                .iter()
+                .map(|(name, ground_coffee, hot_water, cocoa, milk_foam)| {
+                    (
+                        name.to_string(),
+                        Recipe {
+                            ground_coffee: *ground_coffee,
+                            hot_water: *hot_water,
+                            cocoa: *cocoa,
+                            milk_foam: *milk_foam,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Menu {
+    /// Lee el menú del archivo indicado.
+    /// Errores:
+    /// * Si no se puede abrir el archivo devuelve [`CustomError::CantOpenMenuFile`]
+    /// * Si alguna línea es inválida devuelve [`CustomError::InvalidMenuValue`]
+    pub fn from_file(path: &str) -> Result<Menu, CustomError> {
+        match fs::read_to_string(path) {
+            Ok(content) => Menu::parse(&content),
+            Err(e) => {
+                error!("No se pudo abrir el archivo de menú {:?}: {:?}", path, e);
+                Err(CustomError::CantOpenMenuFile)
+            }
+        }
+    }
+
+    /// Interpreta el contenido de un archivo de menú. Solo contiene las bebidas del archivo.
+    /// Si alguna línea es inválida devuelve [`CustomError::InvalidMenuValue`]
+    pub fn parse(content: &str) -> Result<Menu, CustomError> {
+        let mut recipes = HashMap::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, amounts) = match line.split_once('=') {
+                Some((name, amounts)) => (name.trim().to_lowercase(), amounts.replace(' ', "")),
+                None => {
+                    error!(
+                        "Menú inválido en línea {:?}: se esperaba <nombre> = <cafe>,<agua>,<cacao>,<espuma>.",
+                        index + 1
+                    );
+                    return Err(CustomError::InvalidMenuValue);
+                }
+            };
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                error!(
+                    "Menú inválido en línea {:?}: nombre de bebida inválido {:?}.",
+                    index + 1,
+                    name
+                );
+                return Err(CustomError::InvalidMenuValue);
+            }
+            match Order::from_file_record(&amounts, 0) {
+                Ok(order) => {
+                    recipes.insert(
+                        name,
+                        Recipe {
+                            ground_coffee: order.ground_coffee,
+                            hot_water: order.hot_water,
+                            cocoa: order.cocoa,
+                            milk_foam: order.milk_foam,
+                        },
+                    );
+                }
+                Err(e) => {
+                    error!("Menú inválido en línea {:?}: {}", index + 1, e);
+                    return Err(CustomError::InvalidMenuValue);
+                }
+            }
+        }
+        Ok(Menu { recipes })
+    }
+
+    /// Devuelve la receta de la bebida, si está en el menú.
+    pub fn recipe(&self, name: &str) -> Option<&Recipe> {
+        self.recipes.get(&name.to_lowercase())
+    }
+
+    /// Transforma una línea del archivo de pedidos a un pedido.
+    ///
+    /// La línea puede tener las cantidades de cada ingrediente, como en [`Order::from_file_record`],
+    /// o el nombre de una bebida del menú seguido opcionalmente de un tamaño (`chico`, `mediano` o `grande`)
+    /// y de cantidades que reemplazan a las de la receta (`cafe`, `agua`, `cacao` o `espuma`) o de la prioridad:
+    /// ```text
+    /// cappuccino grande espuma=20 prioridad=vip
+    /// ```
+    pub fn order_from_record(&self, line: &str, id: u32) -> Result<Order, String> {
+        let mut words = line.split_whitespace();
+        let name = match words.next() {
+            Some(name) if !name.starts_with(|c: char| c.is_ascii_digit()) => name,
+            _ => return Order::from_file_record(line, id),
+        };
+        let mut recipe = *self
+            .recipe(name)
+            .ok_or(format!("La bebida {:?} no está en el menú.", name))?;
+
+        let mut words = words.peekable();
+        if let Some((_, percentage)) = words.peek().and_then(|word| {
+            SIZES
+                .iter()
+                .find(|(size, _)| word.eq_ignore_ascii_case(size))
+        }) {
+            let scale = |amount: u32| (amount * percentage + 50) / 100;
+            recipe = Recipe {
+                ground_coffee: scale(recipe.ground_coffee),
+                hot_water: scale(recipe.hot_water),
+                cocoa: scale(recipe.cocoa),
+                milk_foam: scale(recipe.milk_foam),
+            };
+            words.next();
+        }
+
+        let mut priority = Priority::Regular;
+        for word in words {
+            let (ingredient, amount) = word
+                .split_once('=')
+                .ok_or(format!("Se esperaba <ingrediente>=<cantidad>: {:?}.", word))?;
+            if ingredient.eq_ignore_ascii_case("prioridad") {
+                priority =
+                    Priority::parse(amount).ok_or(format!("Prioridad inválida: {:?}.", amount))?;
+                continue;
+            }
+            let amount = amount
+                .parse()
+                .map_err(|_| format!("Cantidad inválida de {}: {:?}.", ingredient, amount))?;
+            match ingredient.to_lowercase().as_str() {
+                "cafe" => recipe.ground_coffee = amount,
+                "agua" => recipe.hot_water = amount,
+                "cacao" => recipe.cocoa = amount,
+                "espuma" => recipe.milk_foam = amount,
+                _ => return Err(format!("Ingrediente desconocido: {:?}.", ingredient)),
+            }
+        }
+        let mut order = Order::new(
+            id,
+            recipe.ground_coffee,
+            recipe.hot_water,
+            recipe.cocoa,
+            recipe.milk_foam,
+        )?;
+        order.priority = priority;
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_menu() {
+        let menu = Menu::default();
+        assert_eq!(
+            menu.recipe("Cappuccino"),
+            Some(&Recipe {
+                ground_coffee: 10,
+                hot_water: 5,
+                cocoa: 0,
+                milk_foam: 10,
+            })
+        );
+        assert_eq!(menu.recipe("te"), None);
+    }
+
+    #[test]
+    fn test_parse_menu() {
+        let menu = Menu::parse("# Bebidas\nlagrima = 2, 5, 0, 20\n\nESPRESSO=12,4,0,0\n").unwrap();
+        assert_eq!(menu.recipe("lagrima").unwrap().milk_foam, 20);
+        assert_eq!(menu.recipe("espresso").unwrap().ground_coffee, 12);
+        assert_eq!(menu.recipe("mocha"), None);
+        assert_eq!(
+            Menu::parse("lagrima = 2,5,0"),
+            Err(CustomError::InvalidMenuValue)
+        );
+        assert_eq!(
+            Menu::parse("cafe con leche = 5,5,0,5"),
+            Err(CustomError::InvalidMenuValue)
+        );
+        assert_eq!(
+            Menu::from_file("src/tests/invalid.txt"),
+            Err(CustomError::CantOpenMenuFile)
+        );
+    }
+
+    #[test]
+    fn test_order_from_record() {
+        let menu = Menu::default();
+
+        let order = menu.order_from_record("mocha", 3).unwrap();
+        assert_eq!(order.id, 3);
+        assert_eq!(
+            (
+                order.ground_coffee,
+                order.hot_water,
+                order.cocoa,
+                order.milk_foam
+            ),
+            (10, 5, 5, 10)
+        );
+
+        let order = menu
+            .order_from_record("cappuccino grande espuma=20", 0)
+            .unwrap();
+        assert_eq!(
+            (
+                order.ground_coffee,
+                order.hot_water,
+                order.cocoa,
+                order.milk_foam
+            ),
+            (15, 8, 0, 20)
+        );
+
+        let order = menu.order_from_record("latte chico cafe=0", 0).unwrap();
+        assert_eq!(
+            (
+                order.ground_coffee,
+                order.hot_water,
+                order.cocoa,
+                order.milk_foam
+            ),
+            (0, 4, 0, 11)
+        );
+
+        let order = menu.order_from_record("1,2,3,4", 0).unwrap();
+        assert_eq!(
+            (
+                order.ground_coffee,
+                order.hot_water,
+                order.cocoa,
+                order.milk_foam
+            ),
+            (1, 2, 3, 4)
+        );
+
+        let order = menu.order_from_record("mocha prioridad=staff", 0).unwrap();
+        assert_eq!(order.priority, Priority::Staff);
+        assert_eq!(order.ground_coffee, 10);
+
+        assert!(menu.order_from_record("te", 0).is_err());
+        assert!(menu
+            .order_from_record("mocha prioridad=urgente", 0)
+            .is_err());
+        assert!(menu.order_from_record("mocha enorme", 0).is_err());
+        assert!(menu.order_from_record("mocha leche=3", 0).is_err());
+        assert!(menu.order_from_record("mocha cafe=-1", 0).is_err());
+    }
+}
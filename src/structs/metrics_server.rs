@@ -0,0 +1,381 @@
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::helpers::error::CustomError;
+use crate::structs::coffee_maker::CoffeeMaker;
+use crate::structs::latency_summary::{LatencyHistogram, HISTOGRAM_BUCKETS};
+use crate::structs::order_times::CONTAINERS;
+use crate::structs::stats_snapshot::StatsSnapshot;
+
+/// Ruta en la que se publican las métricas.
+const METRICS_PATH: &str = "/metrics";
+
+/// Tiempo máximo que se espera el pedido HTTP de un cliente.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Servidor HTTP que publica las estadísticas de la cafetera en el formato de texto de Prometheus.
+pub struct MetricsServer {
+    /// Dirección en la que escucha.
+    address: SocketAddr,
+    /// Flag para que deje de aceptar conexiones.
+    stopping: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl MetricsServer {
+    /// Deja de aceptar conexiones y espera a que termine el hilo del servidor.
+    pub fn stop(self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        if let Err(e) = TcpStream::connect(self.address) {
+            error!(
+                "No se pudo despertar al servidor de métricas para apagarlo: {:?}",
+                e
+            );
+            return;
+        }
+        if self.handle.join().is_err() {
+            error!("No se pudo unir el hilo del servidor de métricas.");
+        }
+    }
+}
+
+/// Abre el puerto de métricas en la dirección indicada, por ejemplo `127.0.0.1:9898`.
+/// Solo se permiten direcciones locales, para no publicar las métricas fuera de la máquina.
+/// Si la dirección no es local o no se puede abrir devuelve [`CustomError::CantStartServer`]
+pub fn bind(address: &str) -> Result<TcpListener, CustomError> {
+    let addresses: Vec<SocketAddr> = match address.to_socket_addrs() {
+        Ok(addresses) => addresses.collect(),
+        Err(e) => {
+            error!("Dirección de métricas inválida {:?}: {:?}", address, e);
+            return Err(CustomError::CantStartServer);
+        }
+    };
+    if addresses.is_empty() || addresses.iter().any(|a| !a.ip().is_loopback()) {
+        error!(
+            "El servidor de métricas solo puede escuchar en una dirección local, por ejemplo 127.0.0.1: {:?}",
+            address
+        );
+        return Err(CustomError::CantStartServer);
+    }
+    match TcpListener::bind(&addresses[..]) {
+        Ok(listener) => {
+            info!(
+                "Métricas disponibles en http://{}{}.",
+                address, METRICS_PATH
+            );
+            Ok(listener)
+        }
+        Err(e) => {
+            error!(
+                "No se pudo abrir el servidor de métricas en {:?}: {:?}",
+                address, e
+            );
+            Err(CustomError::CantStartServer)
+        }
+    }
+}
+
+/// Atiende los pedidos de métricas en un hilo aparte hasta que se llame a [`MetricsServer::stop`].
+/// Cada conexión se responde en su propio hilo, así un cliente que no envía su pedido no demora a los demás.
+/// Cada `GET /metrics` responde con las estadísticas del momento, las mismas que recolecta
+/// [`CoffeeMaker::snapshot`] para imprimirlas.
+/// Si no se puede crear el hilo devuelve [`CustomError::CantStartServer`]
+pub fn start(
+    listener: TcpListener,
+    coffee_maker: CoffeeMaker,
+) -> Result<MetricsServer, CustomError> {
+    let address = listener
+        .local_addr()
+        .map_err(|_| CustomError::CantStartServer)?;
+    let stopping = Arc::new(AtomicBool::new(false));
+    let stopping_clone = stopping.clone();
+    let handle = thread::Builder::new()
+        .name("metricas".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                if stopping_clone.load(Ordering::SeqCst) {
+                    break;
+                }
+                match stream {
+                    Ok(stream) => {
+                        let coffee_maker = coffee_maker.clone();
+                        let answered = thread::Builder::new()
+                            .name("metricas-cliente".to_string())
+                            .spawn(move || {
+                                if let Err(e) = answer(stream, &coffee_maker) {
+                                    warn!("No se pudo responder el pedido de métricas: {:?}", e);
+                                }
+                            });
+                        if let Err(e) = answered {
+                            warn!(
+                                "No se pudo crear el hilo para responder las métricas: {:?}",
+                                e
+                            );
+                        }
+                    }
+                    Err(e) => warn!("No se pudo aceptar una conexión de métricas: {:?}", e),
+                }
+            }
+        })
+        .map_err(|_| CustomError::CantStartServer)?;
+    Ok(MetricsServer {
+        address,
+        stopping,
+        handle,
+    })
+}
+
+/// Lee el pedido HTTP y responde con las métricas, o con 404 si no es `GET /metrics`.
+fn answer(stream: TcpStream, coffee_maker: &CoffeeMaker) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(METRICS_PATH)) => match coffee_maker.snapshot() {
+            Ok(snapshot) => ("200 OK", render(&snapshot)),
+            Err(e) => (
+                "500 Internal Server Error",
+                format!("No se pudieron obtener las estadísticas: {:?}\n", e),
+            ),
+        },
+        _ => (
+            "404 Not Found",
+            format!("Las métricas están en {}\n", METRICS_PATH),
+        ),
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Escribe las estadísticas en el formato de texto de Prometheus.
+pub fn render(snapshot: &StatsSnapshot) -> String {
+    let mut text = String::new();
+    let mut family = |name: &str, kind: &str, help: &str, samples: &[(String, u64)]| {
+        let _ = writeln!(text, "# HELP coffee_gpt_{} {}", name, help);
+        let _ = writeln!(text, "# TYPE coffee_gpt_{} {}", name, kind);
+        for (labels, value) in samples {
+            let _ = writeln!(text, "coffee_gpt_{}{} {}", name, labels, value);
+        }
+    };
+    let label = |key: &str, value: &str| format!("{{{}=\"{}\"}}", key, value);
+
+    family(
+        "orders_total",
+        "counter",
+        "Pedidos terminados, por estado final.",
+        &[
+            (label("status", "completed"), snapshot.orders_served.into()),
+            (label("status", "rejected"), snapshot.orders_rejected.into()),
+            (
+                label("status", "partially_served"),
+                snapshot.orders_partially_served.into(),
+            ),
+        ],
+    );
+    family(
+        "stock",
+        "gauge",
+        "Cantidad disponible de cada ingrediente.",
+        &[
+            (label("ingredient", "grains"), snapshot.grains.into()),
+            (label("ingredient", "ground_coffee"), snapshot.coffee.into()),
+            (label("ingredient", "hot_water"), snapshot.water.into()),
+            (label("ingredient", "cocoa"), snapshot.cocoa.into()),
+            (label("ingredient", "cold_milk"), snapshot.cold_milk.into()),
+            (label("ingredient", "milk_foam"), snapshot.foam.into()),
+        ],
+    );
+    family(
+        "used_total",
+        "counter",
+        "Cantidad consumida de cada ingrediente.",
+        &[
+            (label("ingredient", "grains"), snapshot.grains_used.into()),
+            (
+                label("ingredient", "ground_coffee"),
+                snapshot.coffee_used.into(),
+            ),
+            (label("ingredient", "hot_water"), snapshot.water_used.into()),
+            (label("ingredient", "cocoa"), snapshot.cocoa_used.into()),
+            (
+                label("ingredient", "cold_milk"),
+                snapshot.cold_milk_used.into(),
+            ),
+            (label("ingredient", "milk_foam"), snapshot.foam_used.into()),
+        ],
+    );
+    family(
+        "refills_total",
+        "counter",
+        "Veces que se rellenó cada contenedor.",
+        &[
+            (label("container", "coffee"), snapshot.coffee_refills.into()),
+            (label("container", "water"), snapshot.water_refills.into()),
+            (label("container", "foam"), snapshot.foam_refills.into()),
+        ],
+    );
+
+    let mut latency = Vec::new();
+    histogram_samples(&mut latency, "", &snapshot.latency_histogram);
+    write_histogram(
+        &mut text,
+        "order_latency_seconds",
+        "Latencia de los pedidos completados, desde que se recibieron hasta que se completaron.",
+        &latency,
+    );
+    let mut waits = Vec::new();
+    for (histogram, (container, _)) in snapshot.container_wait_histograms.iter().zip(CONTAINERS) {
+        histogram_samples(
+            &mut waits,
+            &format!("container=\"{}\",", container),
+            histogram,
+        );
+    }
+    write_histogram(
+        &mut text,
+        "dispenser_wait_seconds",
+        "Espera de los pedidos por un dispensador de cada contenedor.",
+        &waits,
+    );
+    text
+}
+
+/// Agrega las líneas `_bucket`, `_sum` y `_count` del histograma, con las etiquetas `labels` seguidas de una coma.
+/// Los milisegundos se pasan a segundos, como es la convención de Prometheus.
+fn histogram_samples(samples: &mut Vec<String>, labels: &str, histogram: &LatencyHistogram) {
+    for (bound, count) in HISTOGRAM_BUCKETS.iter().zip(histogram.buckets) {
+        samples.push(format!(
+            "_bucket{{{}le=\"{}\"}} {}",
+            labels,
+            seconds(*bound),
+            count
+        ));
+    }
+    samples.push(format!(
+        "_bucket{{{}le=\"+Inf\"}} {}",
+        labels, histogram.count
+    ));
+    let labels = labels.trim_end_matches(',');
+    let labels = if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels)
+    };
+    samples.push(format!("_sum{} {}", labels, seconds(histogram.sum)));
+    samples.push(format!("_count{} {}", labels, histogram.count));
+}
+
+fn write_histogram(text: &mut String, name: &str, help: &str, samples: &[String]) {
+    let _ = writeln!(text, "# HELP coffee_gpt_{} {}", name, help);
+    let _ = writeln!(text, "# TYPE coffee_gpt_{} histogram", name);
+    for sample in samples {
+        let _ = writeln!(text, "coffee_gpt_{}{}", name, sample);
+    }
+}
+
+/// Milisegundos como segundos, sin ceros de más: `1500` es `1.5`.
+fn seconds(milliseconds: u64) -> String {
+    (milliseconds as f64 / 1000.0).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    use crate::helpers::clock::Clock;
+    use crate::helpers::config::Config;
+    use crate::structs::menu::Menu;
+
+    #[test]
+    fn test_render_metrics() {
+        let mut latency_histogram = LatencyHistogram::default();
+        latency_histogram.record(20);
+        latency_histogram.record(1500);
+        let snapshot = StatsSnapshot {
+            orders_served: 3,
+            cocoa: 40,
+            water_refills: 2,
+            latency_histogram,
+            ..StatsSnapshot::default()
+        };
+        let text = render(&snapshot);
+        assert!(text.contains("# TYPE coffee_gpt_orders_total counter\n"));
+        assert!(text.contains("coffee_gpt_orders_total{status=\"completed\"} 3\n"));
+        assert!(text.contains("coffee_gpt_stock{ingredient=\"cocoa\"} 40\n"));
+        assert!(text.contains("coffee_gpt_refills_total{container=\"water\"} 2\n"));
+        assert!(text.contains("coffee_gpt_order_latency_seconds_bucket{le=\"0.01\"} 0\n"));
+        assert!(text.contains("coffee_gpt_order_latency_seconds_bucket{le=\"0.05\"} 1\n"));
+        assert!(text.contains("coffee_gpt_order_latency_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(text.contains("coffee_gpt_order_latency_seconds_sum 1.52\n"));
+        assert!(text.contains("coffee_gpt_order_latency_seconds_count 2\n"));
+        assert!(text.contains(
+            "coffee_gpt_dispenser_wait_seconds_bucket{container=\"foam\",le=\"30\"} 0\n"
+        ));
+        assert!(text.contains("coffee_gpt_dispenser_wait_seconds_count{container=\"coffee\"} 0\n"));
+    }
+
+    fn get(address: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serve_metrics() {
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
+        let server = start(bind("127.0.0.1:0").unwrap(), coffee_maker).unwrap();
+
+        let response = get(server.address, "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("coffee_gpt_orders_total{status=\"completed\"} 0\n"));
+        let response = get(server.address, "/");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        server.stop();
+    }
+
+    #[test]
+    fn test_idle_client_does_not_block_scrapes() {
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
+        let server = start(bind("127.0.0.1:0").unwrap(), coffee_maker).unwrap();
+
+        let _idle = TcpStream::connect(server.address).unwrap();
+        let start = std::time::Instant::now();
+        let response = get(server.address, "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(start.elapsed() < READ_TIMEOUT / 2);
+        server.stop();
+    }
+
+    #[test]
+    fn test_bind_only_local_addresses() {
+        assert_eq!(bind("0.0.0.0:0").err(), Some(CustomError::CantStartServer));
+        assert_eq!(
+            bind("192.0.2.1:0").err(),
+            Some(CustomError::CantStartServer)
+        );
+        assert_eq!(bind("localhost").err(), Some(CustomError::CantStartServer));
+        assert!(bind("localhost:0").is_ok());
+    }
+}
@@ -1,10 +1,13 @@
 use std::cmp::min;
 use std::sync::{Arc, Condvar, Mutex};
-use std::thread;
-use std::time::Duration;
 
-use crate::helpers::constants::{CANTIDAD_RELLENO, E, L, REFILL_MILK_TIME, X};
+use crate::helpers::clock::Clock;
+use crate::helpers::config::Config;
 use crate::helpers::error::CustomError;
+use crate::structs::dispenser_queue::DispenserQueue;
+use crate::structs::event_bus::EventBus;
+use crate::structs::machine_event::{Ingredient, MachineEvent};
+use crate::structs::tracer::Tracer;
 
 /// Contenedor de leche.
 pub struct MilkContainer {
@@ -16,53 +19,127 @@ pub struct MilkContainer {
     pub cold_milk_used: u32,
     /// Cantidad de espuma de leche ya utilizada.
     pub milk_foam_used: u32,
+    /// Cantidad de dispensadores de espuma de leche libres para servir un pedido.
+    pub free_dispensers: u32,
+    /// Pedidos bloqueados esperando un dispensador de leche, en orden de prioridad.
+    pub dispenser_queue: DispenserQueue,
+    /// Cantidad de espuma de leche reservada por pedidos que todavía no se sirvieron.
+    pub reserved: u32,
+    /// Cantidades de espuma de leche que esperan los dispensadores que no pudieron servir por falta de stock.
+    pub waiting: Vec<u32>,
+    /// Flag que indica que se está rellenando la espuma de leche, por lo que no se puede servir.
+    pub refilling: bool,
+    /// Cantidad de veces que se rellenó la espuma de leche.
+    pub refills: u32,
     /// Flag para indicar que ya no se deben rellenar la espuma de leche.
     pub shutdown: bool,
 }
 
 impl MilkContainer {
-    pub fn new() -> MilkContainer {
+    pub fn new(config: &Config) -> MilkContainer {
         MilkContainer {
-            cold_milk_container: L,
-            milk_foam_container: E,
+            cold_milk_container: config.cold_milk_capacity,
+            milk_foam_container: config.milk_foam_capacity,
             cold_milk_used: 0,
             milk_foam_used: 0,
+            free_dispensers: config.dispensers,
+            dispenser_queue: DispenserQueue::new(config.milk_dispenser_policy),
+            reserved: 0,
+            waiting: Vec::new(),
+            refilling: false,
+            refills: 0,
             shutdown: false,
         }
     }
 
-    /// Rellena el cafe molido cuando tiene una disponibilidad menor a [`CANTIDAD_RELLENO`].
+    /// Mayor cantidad de espuma de leche que espera algún dispensador, o 0 si no hay ninguno esperando.
+    pub fn demand(&self) -> u32 {
+        self.waiting.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Quita de los pedidos en espera uno que esperaba la cantidad indicada.
+    pub fn stop_waiting(&mut self, amount: u32) {
+        if let Some(position) = self.waiting.iter().position(|waiting| *waiting == amount) {
+            self.waiting.swap_remove(position);
+        }
+    }
+
+    /// Cantidad de espuma de leche que todavía se puede reservar, contando la que se puede hacer con la leche fría restante.
+    pub fn available(&self) -> u32 {
+        (self.milk_foam_container + self.cold_milk_container).saturating_sub(self.reserved)
+    }
+
+    /// Rellena la espuma de leche cuando tiene una disponibilidad menor a [`Config::refill_threshold`]
+    /// o cuando no alcanza para algún pedido que está esperando ([`MilkContainer::demand`]).
     /// Es un loop donde se tiene en cuenta la disponibilidad de la espuma de leche y si el mismo debe apagarse.
-    /// Mientras se esta recargando la espuma de leche no se puede utilizar el contenedor.
+    /// Mientras se esta recargando la espuma de leche no se puede utilizar el contenedor, lo que se indica con [`MilkContainer::refilling`].
+    /// Las esperas se hacen con el [`Clock`] indicado, los rellenados se publican en `events` y se registran en `tracer`.
     /// Si la cantidad de leche fría llega a cero, se deja de ejecutar ya que no se puede recargar.
-    /// Al llegar al [`X%`] de su disponibilidad de leche fría se alerta por pantalla.
+    /// Al llegar al [`Config::alert_threshold`]% de su disponibilidad de leche fría se publica [`MachineEvent::LowStock`].
     pub fn make_milk_foam(
         milk_container: Arc<(Mutex<MilkContainer>, Condvar)>,
+        config: Config,
+        clock: Clock,
+        events: EventBus,
+        tracer: Tracer,
     ) -> Result<(), CustomError> {
         let (milk_lock, milk_cvar) = &*milk_container;
         loop {
-            if let Ok(mut state) = milk_cvar.wait_while(milk_lock.lock()?, |milk_container| {
-                milk_container.milk_foam_container > CANTIDAD_RELLENO && !milk_container.shutdown
-            }) {
-                if state.shutdown {
-                    break;
-                }
-                if state.cold_milk_container == 0 {
-                    println!("[INFO] No hay mas leche fria.");
-                    break;
-                }
-                println!("[DEBUG] Rellenando espuma de leche.");
-                thread::sleep(Duration::from_millis(REFILL_MILK_TIME));
-                let milk_to_foam = min(E - state.milk_foam_container, state.cold_milk_container);
-                state.milk_foam_container += milk_to_foam;
-                state.cold_milk_container -= milk_to_foam;
-                state.cold_milk_used += milk_to_foam;
-                let capacity_percentage = X as f32 / 100.0 * L as f32;
-                if (state.cold_milk_container as f32) < capacity_percentage {
-                    println!("[WARN] El contenedor de leche se encuentra por debajo de {:?}% de su capacidad", X);
-                }
-                milk_cvar.notify_all();
+            let mut state = clock.wait_while(milk_lock, milk_cvar, |milk_container| {
+                milk_container.milk_foam_container > config.refill_threshold
+                    && milk_container.milk_foam_container >= milk_container.demand()
+                    && !milk_container.shutdown
+            })?;
+            if state.shutdown {
+                break;
+            }
+            if state.cold_milk_container == 0 {
+                info!("No hay mas leche fria.");
+                break;
+            }
+            events.publish(
+                clock.now(),
+                MachineEvent::RefillStarted {
+                    ingredient: Ingredient::MilkFoam,
+                },
+            );
+            state.refilling = true;
+            let refill = state.refills.into();
+            drop(state);
+            tracer.measure(&clock, "refill", "hacer espuma de leche", || {
+                clock.sleep(config.action_time(config.refill_milk_time, "hacer_espuma", refill))
+            });
+
+            let mut state = tracer.measure(&clock, "lock", "lock leche", || milk_lock.lock())?;
+            state.refilling = false;
+            state.refills += 1;
+            let milk_to_foam = min(
+                config.milk_foam_capacity - state.milk_foam_container,
+                state.cold_milk_container,
+            );
+            state.milk_foam_container += milk_to_foam;
+            state.cold_milk_container -= milk_to_foam;
+            state.cold_milk_used += milk_to_foam;
+            events.publish(
+                clock.now(),
+                MachineEvent::RefillFinished {
+                    ingredient: Ingredient::MilkFoam,
+                    amount: milk_to_foam,
+                },
+            );
+            let capacity_percentage =
+                config.alert_threshold as f32 / 100.0 * config.cold_milk_capacity as f32;
+            if (state.cold_milk_container as f32) < capacity_percentage {
+                events.publish(
+                    clock.now(),
+                    MachineEvent::LowStock {
+                        ingredient: Ingredient::ColdMilk,
+                        available: state.cold_milk_container,
+                        threshold: config.alert_threshold,
+                    },
+                );
             }
+            milk_cvar.notify_all();
         }
         Ok(())
     }
@@ -71,30 +148,43 @@ impl MilkContainer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::helpers::config::DispenserPolicy;
+    use crate::helpers::constants::{CANTIDAD_RELLENO, E, L, N};
+    use std::thread;
 
     #[test]
     fn test_new_milk_container() {
-        let milk_container = MilkContainer::new();
+        let milk_container = MilkContainer::new(&Config::default());
         assert_eq!(milk_container.cold_milk_container, L);
         assert_eq!(milk_container.milk_foam_container, E);
         assert_eq!(milk_container.cold_milk_used, 0);
         assert_eq!(milk_container.milk_foam_used, 0);
+        assert_eq!(milk_container.free_dispensers, N);
+        assert_eq!(milk_container.available(), E + L);
         assert_eq!(milk_container.shutdown, false);
     }
 
     #[test]
     fn test_make_milk_foam_refill() -> Result<(), CustomError> {
-        let milk_container = Arc::new((Mutex::new(MilkContainer::new()), Condvar::new()));
+        let milk_container = Arc::new((
+            Mutex::new(MilkContainer::new(&Config::default())),
+            Condvar::new(),
+        ));
         let milk_container_clone = milk_container.clone();
-        let thread_handle =
-            thread::spawn(
-                move || match MilkContainer::make_milk_foam(milk_container_clone) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        println!("[ERROR] Testeando make_milk_foam: {:?}", e);
-                    }
-                },
-            );
+        let thread_handle = thread::spawn(move || {
+            match MilkContainer::make_milk_foam(
+                milk_container_clone,
+                Config::default(),
+                Clock::real(),
+                EventBus::default(),
+                Tracer::default(),
+            ) {
+                Ok(_) => {}
+                Err(e) => {
+                    println!("[ERROR] Testeando make_milk_foam: {:?}", e);
+                }
+            }
+        });
 
         let (milk_lock, milk_cvar) = &*milk_container;
         match milk_lock.lock() {
@@ -151,20 +241,31 @@ mod tests {
                 milk_foam_container: 40,
                 cold_milk_used: 0,
                 milk_foam_used: 0,
+                free_dispensers: N,
+                dispenser_queue: DispenserQueue::new(DispenserPolicy::Priority),
+                reserved: 0,
+                waiting: Vec::new(),
+                refilling: false,
+                refills: 0,
                 shutdown: false,
             }),
             Condvar::new(),
         ));
         let milk_container_clone = milk_container.clone();
-        let thread_handle =
-            thread::spawn(
-                move || match MilkContainer::make_milk_foam(milk_container_clone) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        println!("[ERROR] Testeando make_milk_foam: {:?}", e);
-                    }
-                },
-            );
+        let thread_handle = thread::spawn(move || {
+            match MilkContainer::make_milk_foam(
+                milk_container_clone,
+                Config::default(),
+                Clock::real(),
+                EventBus::default(),
+                Tracer::default(),
+            ) {
+                Ok(_) => {}
+                Err(e) => {
+                    println!("[ERROR] Testeando make_milk_foam: {:?}", e);
+                }
+            }
+        });
 
         let (milk_lock, milk_cvar) = &*milk_container;
         match milk_lock.lock() {
@@ -210,17 +311,25 @@ mod tests {
 
     #[test]
     fn test_make_milk_foam_shutdown() -> Result<(), CustomError> {
-        let milk_container = Arc::new((Mutex::new(MilkContainer::new()), Condvar::new()));
+        let milk_container = Arc::new((
+            Mutex::new(MilkContainer::new(&Config::default())),
+            Condvar::new(),
+        ));
         let milk_container_clone = milk_container.clone();
-        let thread_handle =
-            thread::spawn(
-                move || match MilkContainer::make_milk_foam(milk_container_clone) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        println!("[ERROR] Testeando make_milk_foam: {:?}", e);
-                    }
-                },
-            );
+        let thread_handle = thread::spawn(move || {
+            match MilkContainer::make_milk_foam(
+                milk_container_clone,
+                Config::default(),
+                Clock::real(),
+                EventBus::default(),
+                Tracer::default(),
+            ) {
+                Ok(_) => {}
+                Err(e) => {
+                    println!("[ERROR] Testeando make_milk_foam: {:?}", e);
+                }
+            }
+        });
 
         let (milk_lock, milk_cvar) = &*milk_container;
         match milk_lock.lock() {
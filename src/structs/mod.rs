@@ -1,7 +1,22 @@
 pub mod cocoa_container;
 pub mod coffee_container;
 pub mod coffee_maker;
+pub mod dashboard;
+pub mod dispenser_queue;
+pub mod event_bus;
 pub mod hot_water_container;
+pub mod latency_summary;
+pub mod machine_event;
+pub mod menu;
+pub mod metrics_server;
 pub mod milk_container;
 pub mod order;
+pub mod order_queue;
+pub mod order_server;
+pub mod order_times;
+pub mod priority;
+pub mod run_report;
 pub mod statistics_values;
+pub mod stats_renderer;
+pub mod stats_snapshot;
+pub mod tracer;
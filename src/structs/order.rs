@@ -1,7 +1,61 @@
+use std::path::Path;
+
+use crate::helpers::config::Config;
+use crate::helpers::error::CustomError;
+use crate::helpers::json::{self, JsonValue};
+use crate::structs::machine_event::Ingredient;
+use crate::structs::order_times::OrderTimes;
+use crate::structs::priority::Priority;
+
+/// Formato de las líneas de un archivo de pedidos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderFormat {
+    /// Cantidades separadas por comas o bebidas del menú, ver [`crate::structs::menu::Menu::order_from_record`].
+    Csv,
+    /// Un objeto JSON por línea, ver [`Order::from_json_record`].
+    Jsonl,
+}
+
+impl OrderFormat {
+    /// Interpreta el nombre de un formato (`csv` o `jsonl`).
+    pub fn parse(name: &str) -> Option<OrderFormat> {
+        match name.to_lowercase().as_str() {
+            "csv" => Some(OrderFormat::Csv),
+            "jsonl" => Some(OrderFormat::Jsonl),
+            _ => None,
+        }
+    }
+
+    /// Formato según la extensión del archivo: `.jsonl` o `.ndjson` son JSON Lines y el resto CSV.
+    pub fn from_path(path: &str) -> OrderFormat {
+        match Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some(extension)
+                if extension.eq_ignore_ascii_case("jsonl")
+                    || extension.eq_ignore_ascii_case("ndjson") =>
+            {
+                OrderFormat::Jsonl
+            }
+            _ => OrderFormat::Csv,
+        }
+    }
+
+    /// Formato de una línea suelta: si empieza con `{` es JSON.
+    pub fn from_line(line: &str) -> OrderFormat {
+        if line.trim_start().starts_with('{') {
+            OrderFormat::Jsonl
+        } else {
+            OrderFormat::Csv
+        }
+    }
+}
+
 /// Representa un pedido de un cliente.
 /// Se lee del archivo indicado como parametro.
 /// `<cafe molido>,<agua caliente>,<cacao>,<espuma de leche>`
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Order {
     /// Identificador del pedido.
     pub id: u32,
@@ -13,6 +67,18 @@ pub struct Order {
     pub cocoa: u32,
     /// Cantidad de espuma de leche.
     pub milk_foam: u32,
+    /// Identificador que le dio el cliente al pedido, si lo indicó.
+    pub reference: Option<String>,
+    /// Nombre del cliente, si lo indicó.
+    pub customer: Option<String>,
+    /// Clase de prioridad del pedido.
+    pub priority: Priority,
+    /// Momento en que el cliente hizo el pedido, tal como lo indicó.
+    pub timestamp: Option<String>,
+    /// Momento en que la cafetera recibió el pedido, en milisegundos del reloj.
+    pub received_at: u64,
+    /// Momentos en que se fue preparando el pedido.
+    pub times: OrderTimes,
 }
 
 impl Order {
@@ -29,10 +95,17 @@ impl Order {
             hot_water,
             cocoa,
             milk_foam,
+            reference: None,
+            customer: None,
+            priority: Priority::Regular,
+            timestamp: None,
+            received_at: 0,
+            times: OrderTimes::default(),
         })
     }
 
     /// Transforma una línea del archivo .txt a un pedido.
+    /// Después de las cantidades puede indicarse la prioridad: `10,0,0,5,vip`.
     pub fn from_file_record(line: &str, id: u32) -> Result<Order, String> {
         let mut quantity_array = line.split(",");
         let ground_coffee = quantity_array
@@ -55,13 +128,120 @@ impl Order {
             .ok_or("Error, no se encontró espuma de leche en el pedido.")?
             .parse()
             .map_err(|_| "Valor inválido de espuma de leche.")?;
-        Order::new(id, ground_coffee, hot_water, cocoa, milk_foam)
+        let mut order = Order::new(id, ground_coffee, hot_water, cocoa, milk_foam)?;
+        if let Some(priority) = quantity_array.next() {
+            order.priority = Priority::parse(priority.trim())
+                .ok_or(format!("Prioridad inválida: {:?}.", priority))?;
+        }
+        Ok(order)
+    }
+
+    /// Transforma una línea de un archivo JSON Lines a un pedido:
+    /// ```text
+    /// {"id":"A12","customer":"Ana","priority":"vip","timestamp":"2023-04-01T10:00:00","coffee":10,"water":5,"cocoa":0,"foam":10}
+    /// ```
+    /// Todos los campos son opcionales y las cantidades que no se indiquen son 0.
+    /// `id` es el identificador del cliente; el del pedido sigue siendo `id`, el número de pedido.
+    pub fn from_json_record(line: &str, id: u32) -> Result<Order, String> {
+        let mut order = Order::new(id, 0, 0, 0, 0)?;
+        for (key, value) in json::parse_object(line)? {
+            let amount = || match &value {
+                JsonValue::Number(number) => number
+                    .parse::<u32>()
+                    .map_err(|_| format!("Cantidad inválida de {}: {}.", key, number)),
+                other => Err(format!("Cantidad inválida de {}: {:?}.", key, other)),
+            };
+            let text = || match &value {
+                JsonValue::String(_) | JsonValue::Number(_) => Ok(Some(value.as_text())),
+                JsonValue::Null => Ok(None),
+                other => Err(format!("Valor inválido de {}: {:?}.", key, other)),
+            };
+            match key.as_str() {
+                "coffee" => order.ground_coffee = amount()?,
+                "water" => order.hot_water = amount()?,
+                "cocoa" => order.cocoa = amount()?,
+                "foam" => order.milk_foam = amount()?,
+                "id" => order.reference = text()?,
+                "customer" => order.customer = text()?,
+                "priority" => {
+                    order.priority = match &value {
+                        JsonValue::String(name) => Priority::parse(name),
+                        _ => None,
+                    }
+                    .ok_or(format!("Prioridad inválida: {}.", value.as_text()))?
+                }
+                "timestamp" => order.timestamp = text()?,
+                _ => return Err(format!("Campo desconocido {:?}.", key)),
+            }
+        }
+        Ok(order)
     }
 
     /// Indica si el pedido ya tiene todos sus ingredientes y puede ser entregado al cliente.
     pub fn check_if_ready(&self) -> bool {
         self.ground_coffee == 0 && self.hot_water == 0 && self.cocoa == 0 && self.milk_foam == 0
     }
+
+    /// Verifica que ningún ingrediente supere lo máximo que se puede servir de una vez, que es la capacidad de su contenedor.
+    /// Si lo supera devuelve [`CustomError::OrderExceedsCapacity`]
+    pub fn validate(&self, config: &Config) -> Result<(), CustomError> {
+        let limits = [
+            (
+                "café molido",
+                self.ground_coffee,
+                config.ground_coffee_capacity,
+            ),
+            ("agua caliente", self.hot_water, config.hot_water_capacity),
+            ("cacao", self.cocoa, config.cocoa_capacity),
+            ("espuma de leche", self.milk_foam, config.milk_foam_capacity),
+        ];
+        for (ingredient, amount, capacity) in limits {
+            if amount > capacity {
+                error!(
+                    "El pedido {:?} pide {:?} de {} pero como máximo se pueden servir {:?}.",
+                    self.id, amount, ingredient, capacity
+                );
+                return Err(CustomError::OrderExceedsCapacity);
+            }
+        }
+        Ok(())
+    }
+
+    /// Primer ingrediente que le falta servir, en el orden en que la cafetera los intenta servir.
+    pub fn next_ingredient(&self) -> Option<Ingredient> {
+        [
+            (self.ground_coffee, Ingredient::GroundCoffee),
+            (self.hot_water, Ingredient::HotWater),
+            (self.cocoa, Ingredient::Cocoa),
+            (self.milk_foam, Ingredient::MilkFoam),
+        ]
+        .into_iter()
+        .find(|(amount, _)| *amount > 0)
+        .map(|(_, ingredient)| ingredient)
+    }
+
+    /// Indica si ya se sirvió algún ingrediente del pedido original `requested`.
+    pub fn has_served_any(&self, requested: &Order) -> bool {
+        self.ground_coffee < requested.ground_coffee
+            || self.hot_water < requested.hot_water
+            || self.cocoa < requested.cocoa
+            || self.milk_foam < requested.milk_foam
+    }
+}
+
+/// Estado de un pedido a lo largo de su preparación.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    /// Recibido, esperando a que comience su preparación.
+    Queued,
+    /// Se están sirviendo sus ingredientes.
+    InProgress,
+    /// Se sirvieron todos sus ingredientes y fue entregado.
+    Completed,
+    /// No se sirvió ningún ingrediente por el error indicado.
+    Rejected(CustomError),
+    /// Se sirvieron algunos ingredientes pero no se pudo terminar por el error indicado.
+    PartiallyServed(CustomError),
 }
 
 #[cfg(test)]
@@ -101,6 +281,63 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_from_file_record_with_priority() {
+        assert_eq!(
+            Order::from_file_record("2,3,4,5", 0).unwrap().priority,
+            Priority::Regular
+        );
+        assert_eq!(
+            Order::from_file_record("2,3,4,5,staff", 0)
+                .unwrap()
+                .priority,
+            Priority::Staff
+        );
+        assert!(Order::from_file_record("2,3,4,5,urgente", 0).is_err());
+    }
+
+    #[test]
+    fn test_from_json_record() {
+        let order = Order::from_json_record(
+            r#"{"id":"A12","customer":"Ana","priority":"vip","coffee":10,"foam":5}"#,
+            3,
+        )
+        .unwrap();
+        assert_eq!(order.id, 3);
+        assert_eq!(order.reference, Some("A12".to_string()));
+        assert_eq!(order.customer, Some("Ana".to_string()));
+        assert_eq!(order.priority, Priority::Vip);
+        assert_eq!(order.timestamp, None);
+        assert_eq!(
+            (
+                order.ground_coffee,
+                order.hot_water,
+                order.cocoa,
+                order.milk_foam
+            ),
+            (10, 0, 0, 5)
+        );
+
+        assert!(Order::from_json_record(r#"{"coffee":-1}"#, 0).is_err());
+        assert!(Order::from_json_record(r#"{"coffee":"10"}"#, 0).is_err());
+        assert!(Order::from_json_record(r#"{"leche":10}"#, 0).is_err());
+        assert!(Order::from_json_record(r#"{"priority":"urgente"}"#, 0).is_err());
+        assert!(Order::from_json_record("10,0,0,0", 0).is_err());
+    }
+
+    #[test]
+    fn test_order_format() {
+        assert_eq!(OrderFormat::from_path("pedidos.jsonl"), OrderFormat::Jsonl);
+        assert_eq!(OrderFormat::from_path("pedidos.txt"), OrderFormat::Csv);
+        assert_eq!(OrderFormat::from_path("-"), OrderFormat::Csv);
+        assert_eq!(
+            OrderFormat::from_line(" {\"coffee\":1}"),
+            OrderFormat::Jsonl
+        );
+        assert_eq!(OrderFormat::from_line("mocha"), OrderFormat::Csv);
+        assert_eq!(OrderFormat::parse("JSONL"), Some(OrderFormat::Jsonl));
+    }
+
     #[test]
     fn test_check_if_ready() {
         match Order::from_file_record("2,3,4,5", 1) {
@@ -120,4 +357,44 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_validate() {
+        let config = Config::default();
+        assert!(Order::new(1, config.ground_coffee_capacity, 3, 4, 5)
+            .unwrap()
+            .validate(&config)
+            .is_ok());
+        assert_eq!(
+            Order::new(1, 2, config.hot_water_capacity + 1, 4, 5)
+                .unwrap()
+                .validate(&config),
+            Err(CustomError::OrderExceedsCapacity)
+        );
+        assert_eq!(
+            Order::new(1, 2, 3, 4, config.milk_foam_capacity + 1)
+                .unwrap()
+                .validate(&config),
+            Err(CustomError::OrderExceedsCapacity)
+        );
+    }
+
+    #[test]
+    fn test_has_served_any() {
+        let requested = Order::new(1, 2, 3, 0, 5).unwrap();
+        let mut order = requested.clone();
+        assert!(!order.has_served_any(&requested));
+        order.hot_water = 0;
+        assert!(order.has_served_any(&requested));
+    }
+
+    #[test]
+    fn test_next_ingredient() {
+        let mut order = Order::new(1, 0, 3, 0, 5).unwrap();
+        assert_eq!(order.next_ingredient(), Some(Ingredient::HotWater));
+        order.hot_water = 0;
+        assert_eq!(order.next_ingredient(), Some(Ingredient::MilkFoam));
+        order.milk_foam = 0;
+        assert_eq!(order.next_ingredient(), None);
+    }
 }
@@ -0,0 +1,198 @@
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex};
+
+use crate::helpers::clock::Clock;
+use crate::helpers::config::QueuePolicy;
+use crate::helpers::error::CustomError;
+use crate::structs::order::Order;
+
+/// Estado de la cola protegido por el lock.
+struct QueueState {
+    /// Pedidos esperando a un barista, en orden de llegada.
+    orders: VecDeque<Order>,
+    /// Flag que indica que no van a llegar más pedidos.
+    closed: bool,
+}
+
+/// Cola acotada de pedidos que toman los baristas, primero los más urgentes según su [`crate::structs::priority::Priority`].
+/// Al tener un tamaño máximo, la memoria utilizada no depende del tamaño del archivo de pedidos.
+pub struct OrderQueue {
+    state: Mutex<QueueState>,
+    cvar: Condvar,
+    /// Cantidad máxima de pedidos en espera.
+    capacity: usize,
+    /// Milisegundos de espera que equivalen a subir una clase de prioridad, ver [`crate::helpers::config::Config::aging_time`].
+    aging_time: u64,
+}
+
+impl OrderQueue {
+    pub fn new(capacity: u32, aging_time: u64) -> OrderQueue {
+        OrderQueue {
+            state: Mutex::new(QueueState {
+                orders: VecDeque::new(),
+                closed: false,
+            }),
+            cvar: Condvar::new(),
+            capacity: capacity as usize,
+            aging_time,
+        }
+    }
+
+    /// Agrega un pedido al final de la cola.
+    /// Si la cola está llena, con [`QueuePolicy::Block`] espera a que un barista tome algún pedido
+    /// y con [`QueuePolicy::Reject`] devuelve [`CustomError::QueueFull`].
+    /// Si el lock está envenenado devuelve [`CustomError::PoisonedLock`]
+    pub fn push(
+        &self,
+        order: Order,
+        policy: QueuePolicy,
+        clock: &Clock,
+    ) -> Result<(), CustomError> {
+        let mut state = match policy {
+            QueuePolicy::Block => clock.wait_while(&self.state, &self.cvar, |state| {
+                state.orders.len() >= self.capacity
+            })?,
+            QueuePolicy::Reject => {
+                let state = self.state.lock()?;
+                if state.orders.len() >= self.capacity {
+                    warn!("Cola de pedidos llena. Pedido {:?} rechazado.", order.id);
+                    return Err(CustomError::QueueFull);
+                }
+                state
+            }
+        };
+        state.orders.push_back(order);
+        self.cvar.notify_all();
+        Ok(())
+    }
+
+    /// Toma el pedido más urgente de la cola, esperando a que haya alguno.
+    /// Entre pedidos igual de urgentes toma el que llegó primero.
+    /// Devuelve `None` cuando la cola está cerrada y ya no quedan pedidos.
+    /// Si el lock está envenenado devuelve [`CustomError::PoisonedLock`]
+    pub fn pop(&self, clock: &Clock) -> Result<Option<Order>, CustomError> {
+        let mut state = clock.wait_while(&self.state, &self.cvar, |state| {
+            state.orders.is_empty() && !state.closed
+        })?;
+        let now = clock.now();
+        let order = state
+            .orders
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, order)| {
+                (
+                    order
+                        .priority
+                        .urgency(order.received_at, now, self.aging_time),
+                    Reverse((order.received_at, order.id)),
+                )
+            })
+            .map(|(position, _)| position)
+            .and_then(|position| state.orders.remove(position));
+        self.cvar.notify_all();
+        Ok(order)
+    }
+
+    /// Indica que no van a llegar más pedidos, para que los baristas terminen al vaciar la cola.
+    /// Si el lock está envenenado devuelve [`CustomError::PoisonedLock`]
+    pub fn close(&self) -> Result<(), CustomError> {
+        self.state.lock()?.closed = true;
+        self.cvar.notify_all();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::priority::Priority;
+
+    #[test]
+    fn test_queue_keeps_arrival_order() -> Result<(), CustomError> {
+        let queue = OrderQueue::new(3, 1000);
+        let clock = Clock::real();
+        for id in 0..3 {
+            queue.push(
+                Order::new(id, 1, 1, 1, 1).unwrap(),
+                QueuePolicy::Block,
+                &clock,
+            )?;
+        }
+        queue.close()?;
+        for id in 0..3 {
+            assert_eq!(queue.pop(&clock)?.map(|order| order.id), Some(id));
+        }
+        assert!(queue.pop(&clock)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_takes_most_urgent_first() -> Result<(), CustomError> {
+        let queue = OrderQueue::new(3, 1000);
+        let clock = Clock::real();
+        for (id, priority) in [
+            (0, Priority::Regular),
+            (1, Priority::Staff),
+            (2, Priority::Vip),
+        ] {
+            let mut order = Order::new(id, 1, 1, 1, 1).unwrap();
+            order.priority = priority;
+            queue.push(order, QueuePolicy::Block, &clock)?;
+        }
+        queue.close()?;
+        for id in [1, 2, 0] {
+            assert_eq!(queue.pop(&clock)?.map(|order| order.id), Some(id));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_full_reject() -> Result<(), CustomError> {
+        let queue = OrderQueue::new(1, 1000);
+        let clock = Clock::real();
+        queue.push(
+            Order::new(0, 1, 1, 1, 1).unwrap(),
+            QueuePolicy::Reject,
+            &clock,
+        )?;
+        assert_eq!(
+            queue.push(
+                Order::new(1, 1, 1, 1, 1).unwrap(),
+                QueuePolicy::Reject,
+                &clock
+            ),
+            Err(CustomError::QueueFull)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_full_blocks_until_pop() -> Result<(), CustomError> {
+        let clock = Clock::simulated();
+        let _participant = clock.participate();
+        let queue = std::sync::Arc::new(OrderQueue::new(1, 1000));
+        queue.push(
+            Order::new(0, 1, 1, 1, 1).unwrap(),
+            QueuePolicy::Block,
+            &clock,
+        )?;
+
+        let queue_clone = queue.clone();
+        let clock_clone = clock.clone();
+        let consumer = clock.spawn_named("consumidor".to_string(), move || {
+            clock_clone.sleep(std::time::Duration::from_millis(500));
+            queue_clone
+                .pop(&clock_clone)
+                .map(|order| order.map(|order| order.id))
+        });
+        queue.push(
+            Order::new(1, 1, 1, 1, 1).unwrap(),
+            QueuePolicy::Block,
+            &clock,
+        )?;
+        assert!(clock.now() >= 500);
+        assert_eq!(consumer.join().unwrap(), Ok(Some(0)));
+        Ok(())
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::helpers::error::CustomError;
+use crate::structs::coffee_maker::CoffeeMaker;
+use crate::structs::order::{OrderFormat, OrderStatus};
+use crate::structs::order_queue::OrderQueue;
+
+/// Línea con la que un cliente apaga el servidor.
+const SHUTDOWN_COMMAND: &str = "APAGAR";
+
+/// Respuesta que se le envía a un cliente, una por línea.
+/// Las de un pedido llevan su identificador y el que le dio el cliente, si lo indicó.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Reply {
+    /// El pedido fue recibido con este identificador.
+    Received(u32, Option<String>),
+    /// La línea no es un pedido válido.
+    Invalid(String),
+    /// El pedido terminó con este estado.
+    Finished(u32, Option<String>, OrderStatus),
+}
+
+impl Reply {
+    /// Línea que se envía al cliente, sin el salto de línea.
+    /// El identificador del cliente va al final, así puede tener espacios.
+    fn to_line(&self) -> String {
+        let line = match self {
+            Reply::Received(id, _) => format!("PEDIDO {}", id),
+            Reply::Invalid(e) => format!("ERROR {}", e),
+            Reply::Finished(id, _, OrderStatus::Completed) => format!("COMPLETADO {}", id),
+            Reply::Finished(id, _, OrderStatus::Rejected(e)) => {
+                format!("RECHAZADO {} {:?}", id, e)
+            }
+            Reply::Finished(id, _, OrderStatus::PartiallyServed(e)) => {
+                format!("PARCIAL {} {:?}", id, e)
+            }
+            Reply::Finished(id, _, status) => format!("ESTADO {} {:?}", id, status),
+        };
+        match self {
+            Reply::Received(_, Some(reference)) | Reply::Finished(_, Some(reference), _) => {
+                format!("{} {}", line, reference)
+            }
+            _ => line,
+        }
+    }
+}
+
+/// Estado compartido por todas las conexiones.
+struct ServerState {
+    /// Identificador del próximo pedido.
+    next_id: u32,
+    /// Flag que indica que algún cliente apagó el servidor.
+    stopping: bool,
+    /// Conexiones que se siguen leyendo, por número de cliente, para dejar de leerlas al apagar el servidor.
+    /// Cada cliente saca la suya cuando cierra la conexión.
+    connections: HashMap<usize, TcpStream>,
+}
+
+/// Hilos de un cliente: el que lee sus pedidos, que devuelve la cantidad de líneas inválidas, y el que le responde.
+type ClientThreads = (JoinHandle<u32>, JoinHandle<()>);
+
+/// Abre el puerto en la dirección indicada, por ejemplo `127.0.0.1:7878`.
+/// Si no se puede abrir devuelve [`CustomError::CantStartServer`]
+pub fn bind(address: &str) -> Result<TcpListener, CustomError> {
+    match TcpListener::bind(address) {
+        Ok(listener) => {
+            info!("Esperando pedidos en {}.", address);
+            Ok(listener)
+        }
+        Err(e) => {
+            error!("No se pudo abrir el servidor en {:?}: {:?}", address, e);
+            Err(CustomError::CantStartServer)
+        }
+    }
+}
+
+/// Acepta conexiones y recibe pedidos de todas ellas hasta que algún cliente envíe `APAGAR`.
+///
+/// Cada línea que envía un cliente es un pedido con el mismo formato que el archivo de pedidos, CSV o JSON.
+/// Por cada pedido se le responde `PEDIDO <id>`, o `ERROR <motivo>` si la línea es inválida,
+/// y cuando termina `COMPLETADO <id>`, `RECHAZADO <id> <error>` o `PARCIAL <id> <error>`.
+/// Si el pedido JSON indica su `id`, se agrega al final de cada respuesta del pedido: `PEDIDO 3 A12`.
+/// Al apagar deja de leer todas las conexiones y espera a que terminen los pedidos ya recibidos para
+/// enviarles su estado final.
+/// Devuelve la cantidad de líneas inválidas.
+/// Si algún lock está envenenado devuelve [`CustomError::PoisonedLock`]
+pub fn accept_orders(
+    listener: TcpListener,
+    coffee_maker: CoffeeMaker,
+    queue: Arc<OrderQueue>,
+) -> Result<u32, CustomError> {
+    let address = match listener.local_addr() {
+        Ok(address) => address,
+        Err(e) => {
+            error!("No se pudo obtener la dirección del servidor: {:?}", e);
+            return Err(CustomError::CantStartServer);
+        }
+    };
+    let state = Arc::new(Mutex::new(ServerState {
+        next_id: 0,
+        stopping: false,
+        connections: HashMap::new(),
+    }));
+    let mut clients: Vec<ClientThreads> = Vec::new();
+    let mut invalid_records = 0;
+    for (number, stream) in listener.incoming().enumerate() {
+        if state.lock()?.stopping {
+            break;
+        }
+        join_finished(&mut clients, &mut invalid_records);
+        match stream {
+            Ok(stream) => match start_client(
+                number,
+                stream,
+                address,
+                coffee_maker.clone(),
+                queue.clone(),
+                state.clone(),
+            ) {
+                Ok(client) => clients.push(client),
+                Err(e) => warn!("No se pudo atender al cliente {:?}: {:?}", number, e),
+            },
+            Err(e) => warn!("No se pudo aceptar una conexión: {:?}", e),
+        }
+    }
+
+    for connection in state.lock()?.connections.values() {
+        let _ = connection.shutdown(Shutdown::Read);
+    }
+    for client in clients {
+        invalid_records += join_client(client);
+    }
+    info!("Servidor apagado.");
+    Ok(invalid_records)
+}
+
+/// Une los hilos de los clientes que ya terminaron y suma sus líneas inválidas,
+/// así no se acumulan mientras el servidor siga abierto.
+fn join_finished(clients: &mut Vec<ClientThreads>, invalid_records: &mut u32) {
+    let (finished, running) = clients
+        .drain(..)
+        .partition(|(reader, writer)| reader.is_finished() && writer.is_finished());
+    *clients = running;
+    for client in finished {
+        *invalid_records += join_client(client);
+    }
+}
+
+/// Espera a que terminen los hilos del cliente y devuelve la cantidad de líneas inválidas que envió.
+fn join_client((reader, writer): ClientThreads) -> u32 {
+    let invalid_records = reader.join().unwrap_or_else(|_| {
+        error!("No se pudo unir el hilo de un cliente.");
+        0
+    });
+    if writer.join().is_err() {
+        error!("No se pudo unir el hilo de respuestas de un cliente.");
+    }
+    invalid_records
+}
+
+/// Crea los hilos que leen los pedidos del cliente y le envían las respuestas.
+fn start_client(
+    number: usize,
+    stream: TcpStream,
+    address: SocketAddr,
+    coffee_maker: CoffeeMaker,
+    queue: Arc<OrderQueue>,
+    state: Arc<Mutex<ServerState>>,
+) -> Result<ClientThreads, CustomError> {
+    let (replies, replies_receiver) = mpsc::channel();
+    let (writer_stream, connection) = match (stream.try_clone(), stream.try_clone()) {
+        (Ok(writer_stream), Ok(connection)) => (writer_stream, connection),
+        _ => return Err(CustomError::CantStartServer),
+    };
+    state.lock()?.connections.insert(number, connection);
+
+    let writer = thread::Builder::new()
+        .name(format!("respuestas-{}", number))
+        .spawn(move || write_replies(writer_stream, replies_receiver))
+        .map_err(|_| CustomError::CantStartServer)?;
+    let reader = thread::Builder::new()
+        .name(format!("cliente-{}", number))
+        .spawn(move || read_orders(number, stream, replies, address, coffee_maker, queue, state))
+        .map_err(|_| CustomError::CantStartServer)?;
+    Ok((reader, writer))
+}
+
+/// Recibe los pedidos del cliente `number`, una línea por pedido, hasta que cierre la conexión o apague el servidor.
+/// Al terminar saca su conexión del estado del servidor.
+/// Devuelve la cantidad de líneas inválidas.
+fn read_orders(
+    number: usize,
+    stream: TcpStream,
+    replies: Sender<Reply>,
+    address: SocketAddr,
+    coffee_maker: CoffeeMaker,
+    queue: Arc<OrderQueue>,
+    state: Arc<Mutex<ServerState>>,
+) -> u32 {
+    let mut invalid_records = 0;
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("No se pudo leer del cliente: {:?}", e);
+                break;
+            }
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.eq_ignore_ascii_case(SHUTDOWN_COMMAND) {
+            stop(&state, address);
+            break;
+        }
+        let id = match state.lock() {
+            Ok(mut state) => {
+                state.next_id += 1;
+                state.next_id - 1
+            }
+            Err(e) => {
+                error!("No se pudo asignar un identificador al pedido: {:?}", e);
+                break;
+            }
+        };
+        match coffee_maker.take_order(line, id, OrderFormat::from_line(line)) {
+            Ok(order) => {
+                let finished = replies.clone();
+                let reference = order.reference.clone();
+                let finished_reference = order.reference.clone();
+                let received = coffee_maker
+                    .on_order_finished(
+                        id,
+                        Box::new(move |status| {
+                            let _ = finished.send(Reply::Finished(id, finished_reference, status));
+                        }),
+                    )
+                    .and_then(|_| {
+                        let _ = replies.send(Reply::Received(id, reference));
+                        coffee_maker.receive_order(order, &queue)
+                    });
+                if let Err(e) = received {
+                    error!("No se pudo recibir el pedido {:?}: {:?}", id, e);
+                    break;
+                }
+            }
+            Err(e) => {
+                warn!("Pedido inválido: {}", e);
+                invalid_records += 1;
+                let _ = replies.send(Reply::Invalid(e));
+            }
+        }
+    }
+    match state.lock() {
+        Ok(mut state) => {
+            state.connections.remove(&number);
+        }
+        Err(e) => error!("No se pudo cerrar la conexión del cliente: {:?}", e),
+    }
+    invalid_records
+}
+
+/// Envía las respuestas al cliente hasta que no queden pedidos suyos sin terminar.
+fn write_replies(mut stream: TcpStream, replies: Receiver<Reply>) {
+    for reply in replies {
+        if let Err(e) = writeln!(stream, "{}", reply.to_line()) {
+            warn!("No se pudo responder al cliente: {:?}", e);
+            break;
+        }
+    }
+}
+
+/// Marca al servidor como apagado y se conecta a sí mismo para que deje de esperar conexiones.
+fn stop(state: &Mutex<ServerState>, address: SocketAddr) {
+    match state.lock() {
+        Ok(mut state) => state.stopping = true,
+        Err(e) => error!("No se pudo apagar el servidor: {:?}", e),
+    }
+    if let Err(e) = TcpStream::connect(address) {
+        error!("No se pudo despertar al servidor para apagarlo: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::clock::Clock;
+    use crate::helpers::config::Config;
+    use crate::structs::menu::Menu;
+
+    #[test]
+    fn test_serve_orders() {
+        let config = Config {
+            take_order_time: 0,
+            serve_coffee_time: 10,
+            serve_hot_water_time: 10,
+            serve_cocoa_time: 10,
+            serve_milk_foam_time: 10,
+            time_to_stats: 10,
+            ..Config::default()
+        };
+        let coffee_maker = CoffeeMaker::new(config, Clock::real(), Menu::default());
+        let listener = bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || coffee_maker.serve(listener));
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .write_all(b"1,1,1,1\nte\n150,0,0,0\n{\"id\":\"A12\",\"coffee\":1}\nAPAGAR\n")
+            .unwrap();
+        let replies: Vec<String> = BufReader::new(client)
+            .lines()
+            .map(|line| line.unwrap())
+            .collect();
+
+        let position = |reply: &str| replies.iter().position(|line| line.starts_with(reply));
+        assert_eq!(replies.len(), 7, "Respuestas: {:?}", replies);
+        assert!(position("PEDIDO 0") < position("COMPLETADO 0"));
+        assert!(position("ERROR").is_some());
+        assert!(position("PEDIDO 2") < position("RECHAZADO 2 OrderExceedsCapacity"));
+        assert!(replies.contains(&"PEDIDO 3 A12".to_string()));
+        assert!(replies.contains(&"COMPLETADO 3 A12".to_string()));
+
+        let report = server.join().unwrap().unwrap();
+        assert_eq!(report.completed(), 2);
+        assert_eq!(report.rejected(), 1);
+        assert_eq!(report.invalid_records, 1);
+    }
+
+    #[test]
+    fn test_read_orders_removes_closed_connection() {
+        let listener = bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = TcpStream::connect(address).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let state = Arc::new(Mutex::new(ServerState {
+            next_id: 0,
+            stopping: false,
+            connections: HashMap::from([(4, stream.try_clone().unwrap())]),
+        }));
+        drop(client);
+
+        let (replies, _) = mpsc::channel();
+        let coffee_maker = CoffeeMaker::new(Config::default(), Clock::real(), Menu::default());
+        let queue = Arc::new(OrderQueue::new(1, 0));
+        let invalid = read_orders(
+            4,
+            stream,
+            replies,
+            address,
+            coffee_maker,
+            queue,
+            state.clone(),
+        );
+        assert_eq!(invalid, 0);
+        assert!(state.lock().unwrap().connections.is_empty());
+    }
+
+    #[test]
+    fn test_join_finished_clients() {
+        let finished = (thread::spawn(|| 2), thread::spawn(|| ()));
+        let (release, released) = mpsc::channel::<()>();
+        let running = (
+            thread::spawn(move || {
+                let _ = released.recv();
+                1
+            }),
+            thread::spawn(|| ()),
+        );
+        while !(finished.0.is_finished() && finished.1.is_finished()) {
+            thread::yield_now();
+        }
+        let mut clients = vec![finished, running];
+        let mut invalid_records = 0;
+        join_finished(&mut clients, &mut invalid_records);
+        assert_eq!(invalid_records, 2);
+        assert_eq!(clients.len(), 1);
+
+        release.send(()).unwrap();
+        assert_eq!(join_client(clients.remove(0)), 1);
+    }
+
+    #[test]
+    fn test_reply_lines() {
+        assert_eq!(Reply::Received(3, None).to_line(), "PEDIDO 3");
+        assert_eq!(
+            Reply::Received(3, Some("A12".to_string())).to_line(),
+            "PEDIDO 3 A12"
+        );
+        assert_eq!(
+            Reply::Invalid("Cantidad inválida".to_string()).to_line(),
+            "ERROR Cantidad inválida"
+        );
+        assert_eq!(
+            Reply::Finished(3, None, OrderStatus::Completed).to_line(),
+            "COMPLETADO 3"
+        );
+        assert_eq!(
+            Reply::Finished(
+                4,
+                Some("Mesa 2".to_string()),
+                OrderStatus::Rejected(CustomError::InsufficientIngredients)
+            )
+            .to_line(),
+            "RECHAZADO 4 InsufficientIngredients Mesa 2"
+        );
+    }
+}
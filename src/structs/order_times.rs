@@ -0,0 +1,83 @@
+/// Contenedores, en el orden de [`OrderTimes::ingredients`]: clave para las estadísticas en JSON y CSV, y nombre.
+pub const CONTAINERS: [(&str, &str); 4] = [
+    ("coffee", "café"),
+    ("water", "agua caliente"),
+    ("cocoa", "cacao"),
+    ("foam", "espuma de leche"),
+];
+
+/// Momentos, en milisegundos del reloj, en que se sirvió un ingrediente de un pedido.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IngredientTimes {
+    /// Primera vez que el pedido intentó tomar un dispensador del contenedor.
+    pub arrived: Option<u64>,
+    /// Momento en que consiguió el dispensador.
+    pub dispensed: Option<u64>,
+    /// Momento en que terminó de servirse y liberó el dispensador.
+    pub served: Option<u64>,
+}
+
+impl IngredientTimes {
+    /// Anota la llegada al contenedor, si es la primera.
+    pub fn arrive(&mut self, now: u64) {
+        self.arrived.get_or_insert(now);
+    }
+
+    /// Lo que esperó el pedido por un dispensador del contenedor, si ya lo consiguió.
+    pub fn wait(&self) -> Option<u64> {
+        Some(self.dispensed?.saturating_sub(self.arrived?))
+    }
+}
+
+/// Línea de tiempo de un pedido desde que lo toma un barista. El momento en que se recibió es [`crate::structs::order::Order::received_at`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OrderTimes {
+    /// Momento en que un barista tomó el pedido de la cola.
+    pub started: Option<u64>,
+    pub ground_coffee: IngredientTimes,
+    pub hot_water: IngredientTimes,
+    pub cocoa: IngredientTimes,
+    pub milk_foam: IngredientTimes,
+    /// Momento en que se completó el pedido. `None` si no se pudo completar.
+    pub completed: Option<u64>,
+}
+
+impl OrderTimes {
+    /// Los tiempos de cada ingrediente, en el orden de [`CONTAINERS`].
+    pub fn ingredients(&self) -> [IngredientTimes; 4] {
+        [
+            self.ground_coffee,
+            self.hot_water,
+            self.cocoa,
+            self.milk_foam,
+        ]
+    }
+
+    /// Momento en que se empezó a servir el primer ingrediente.
+    pub fn first_ingredient(&self) -> Option<u64> {
+        self.ingredients()
+            .iter()
+            .filter_map(|times| times.dispensed)
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order_times() {
+        let mut times = OrderTimes::default();
+        assert_eq!(times.first_ingredient(), None);
+        times.milk_foam.arrive(100);
+        times.milk_foam.arrive(300);
+        times.milk_foam.dispensed = Some(400);
+        times.ground_coffee.arrive(100);
+        times.ground_coffee.dispensed = Some(150);
+        assert_eq!(times.milk_foam.wait(), Some(300));
+        assert_eq!(times.ground_coffee.wait(), Some(50));
+        assert_eq!(times.cocoa.wait(), None);
+        assert_eq!(times.first_ingredient(), Some(150));
+    }
+}
@@ -0,0 +1,80 @@
+/// Clase de prioridad de un pedido. Los pedidos de mayor prioridad toman primero los dispensadores
+/// y los baristas, ver [`crate::structs::dispenser_queue::DispenserQueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    /// Clientes comunes. Es la prioridad por defecto.
+    #[default]
+    Regular,
+    /// Clientes VIP.
+    Vip,
+    /// Pedidos del personal de la cafetería.
+    Staff,
+}
+
+impl Priority {
+    /// Todas las clases, de menor a mayor prioridad.
+    pub const ALL: [Priority; 3] = [Priority::Regular, Priority::Vip, Priority::Staff];
+
+    /// Interpreta el nombre de una clase (`regular`, `vip` o `staff`).
+    pub fn parse(name: &str) -> Option<Priority> {
+        match name.to_lowercase().as_str() {
+            "regular" => Some(Priority::Regular),
+            "vip" => Some(Priority::Vip),
+            "staff" => Some(Priority::Staff),
+            _ => None,
+        }
+    }
+
+    /// Nombre de la clase, como se escribe en los pedidos.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Priority::Regular => "regular",
+            Priority::Vip => "vip",
+            Priority::Staff => "staff",
+        }
+    }
+
+    /// Cantidad de clases por encima de [`Priority::Regular`].
+    pub fn rank(&self) -> u64 {
+        match self {
+            Priority::Regular => 0,
+            Priority::Vip => 1,
+            Priority::Staff => 2,
+        }
+    }
+
+    /// Urgencia de un pedido de esta clase recibido en `received_at`, en el momento `now`.
+    /// Cada [`crate::helpers::config::Config::aging_time`] milisegundos de espera equivalen a subir una clase,
+    /// así los pedidos de menor prioridad no esperan indefinidamente.
+    pub fn urgency(&self, received_at: u64, now: u64, aging_time: u64) -> u64 {
+        self.rank() * aging_time + now.saturating_sub(received_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_priority() {
+        assert_eq!(Priority::parse("VIP"), Some(Priority::Vip));
+        assert_eq!(Priority::parse("staff"), Some(Priority::Staff));
+        assert_eq!(Priority::parse("regular"), Some(Priority::Regular));
+        assert_eq!(Priority::parse("urgente"), None);
+        for priority in Priority::ALL {
+            assert_eq!(Priority::parse(priority.name()), Some(priority));
+        }
+    }
+
+    #[test]
+    fn test_urgency_ages() {
+        assert!(Priority::Staff.urgency(0, 0, 1000) > Priority::Vip.urgency(0, 0, 1000));
+        assert!(Priority::Vip.urgency(0, 0, 1000) > Priority::Regular.urgency(0, 0, 1000));
+        assert!(
+            Priority::Regular.urgency(0, 2500, 1000) > Priority::Staff.urgency(2500, 2500, 1000)
+        );
+        assert!(
+            Priority::Regular.urgency(0, 2500, 1000) < Priority::Staff.urgency(1500, 2500, 1000)
+        );
+    }
+}
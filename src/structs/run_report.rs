@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+
+use crate::structs::latency_summary::LatencySummary;
+use crate::structs::order_times::CONTAINERS;
+use crate::structs::priority::Priority;
+use crate::structs::statistics_values::WaitTimes;
+
+/// Resultado de procesar un archivo de pedidos con [`crate::structs::coffee_maker::CoffeeMaker::take_orders`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunReport {
+    /// Cantidad de pedidos completados.
+    pub orders_served: u32,
+    /// Cantidad de pedidos rechazados sin servir ningún ingrediente.
+    pub orders_rejected: u32,
+    /// Cantidad de pedidos que se sirvieron en parte.
+    pub orders_partially_served: u32,
+    /// Cantidad de líneas que no se pudieron interpretar como pedido.
+    pub invalid_records: u32,
+    /// Tiempos de espera de los pedidos preparados, por clase de prioridad.
+    pub waits: BTreeMap<Priority, WaitTimes>,
+    /// Latencia de punta a punta, desde la recepción, de los pedidos completados.
+    pub latency: LatencySummary,
+    /// Espera por un dispensador de cada contenedor, con la clave de [`CONTAINERS`].
+    pub container_waits: [(&'static str, LatencySummary); 4],
+}
+
+impl RunReport {
+    /// Cantidad de pedidos completados.
+    pub fn completed(&self) -> usize {
+        self.orders_served as usize
+    }
+
+    /// Cantidad de pedidos rechazados sin servir ningún ingrediente.
+    pub fn rejected(&self) -> usize {
+        self.orders_rejected as usize
+    }
+
+    /// Cantidad de pedidos que se sirvieron en parte.
+    pub fn partially_served(&self) -> usize {
+        self.orders_partially_served as usize
+    }
+
+    /// Imprime el resumen de la corrida. El motivo de cada pedido que no se completó ya se informó
+    /// con [`crate::structs::machine_event::MachineEvent::OrderRejected`] al rechazarlo.
+    pub fn log_summary(&self) {
+        info!(
+            "Pedidos completados: {:?}. Rechazados: {:?}. Servidos parcialmente: {:?}. Líneas inválidas: {:?}.",
+            self.completed(),
+            self.rejected(),
+            self.partially_served(),
+            self.invalid_records
+        );
+        for (priority, waits) in &self.waits {
+            info!(
+                "Pedidos {}: {:?}. Espera promedio: {:?} ms. Espera máxima: {:?} ms.",
+                priority.name(),
+                waits.orders,
+                waits.average(),
+                waits.max
+            );
+        }
+        info!(
+            "Latencia de los pedidos completados: {}.",
+            self.latency.describe()
+        );
+        for ((_, waits), (_, name)) in self.container_waits.iter().zip(CONTAINERS) {
+            info!("Espera por dispensador de {}: {}.", name, waits.describe());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_report_counts() {
+        let report = RunReport {
+            orders_served: 2,
+            orders_rejected: 1,
+            orders_partially_served: 1,
+            invalid_records: 1,
+            waits: BTreeMap::new(),
+            latency: LatencySummary::default(),
+            container_waits: [("", LatencySummary::default()); 4],
+        };
+        assert_eq!(report.completed(), 2);
+        assert_eq!(report.rejected(), 1);
+        assert_eq!(report.partially_served(), 1);
+    }
+}
@@ -1,7 +1,45 @@
+use std::collections::BTreeMap;
+
+use crate::structs::latency_summary::{LatencyHistogram, LatencySamples, LatencySummary};
+use crate::structs::order::OrderStatus;
+use crate::structs::order_times::{OrderTimes, CONTAINERS};
+use crate::structs::priority::Priority;
+
+/// Tiempos de espera de los pedidos de una clase de prioridad, en milisegundos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WaitTimes {
+    /// Cantidad de pedidos preparados.
+    pub orders: u32,
+    /// Suma de lo que esperaron todos los pedidos.
+    pub total: u64,
+    /// Mayor espera de un pedido.
+    pub max: u64,
+}
+
+impl WaitTimes {
+    /// Espera promedio, o 0 si no se preparó ningún pedido.
+    pub fn average(&self) -> u64 {
+        if self.orders == 0 {
+            return 0;
+        }
+        self.total / self.orders as u64
+    }
+}
+
 /// Estructura utilizada únicamente para las estadísticas.
 pub struct StatisticsValues {
     /// Contador de las ordenes que ya fueron completadas.
     pub orders_served: u32,
+    /// Contador de las ordenes rechazadas sin servir ningún ingrediente.
+    pub orders_rejected: u32,
+    /// Contador de las ordenes que se sirvieron en parte y no se pudieron terminar.
+    pub orders_partially_served: u32,
+    /// Tiempos de espera de los pedidos preparados, por clase de prioridad.
+    pub waits: BTreeMap<Priority, WaitTimes>,
+    /// Milisegundos desde que se recibió hasta que se completó cada pedido completado.
+    pub latencies: LatencySamples,
+    /// Milisegundos que esperó cada ingrediente servido por un dispensador, por contenedor en el orden de [`CONTAINERS`].
+    pub container_waits: [LatencySamples; 4],
     /// Flag utilizado para apagar el hilo que imprime las estadísticas.
     pub shutdown: bool,
 }
@@ -10,19 +48,136 @@ impl StatisticsValues {
     pub fn new() -> StatisticsValues {
         StatisticsValues {
             orders_served: 0,
+            orders_rejected: 0,
+            orders_partially_served: 0,
+            waits: BTreeMap::new(),
+            latencies: LatencySamples::default(),
+            container_waits: Default::default(),
             shutdown: false,
         }
     }
+
+    /// Si el estado del pedido es final, suma uno al contador correspondiente.
+    /// El estado de cada pedido no se guarda: se avisa con [`crate::structs::coffee_maker::CoffeeMaker::on_order_finished`].
+    pub fn set_status(&mut self, status: OrderStatus) {
+        match status {
+            OrderStatus::Completed => self.orders_served += 1,
+            OrderStatus::Rejected(_) => self.orders_rejected += 1,
+            OrderStatus::PartiallyServed(_) => self.orders_partially_served += 1,
+            OrderStatus::Queued | OrderStatus::InProgress => {}
+        }
+    }
+
+    /// Registra lo que esperó un pedido preparado de la clase indicada, sin contar el tiempo que se estuvo sirviendo.
+    pub fn record_wait(&mut self, priority: Priority, waited: u64) {
+        let waits = self.waits.entry(priority).or_default();
+        waits.orders += 1;
+        waits.total += waited;
+        waits.max = waits.max.max(waited);
+    }
+
+    /// Registra la línea de tiempo de un pedido preparado que se recibió en `received_at`.
+    pub fn record_times(&mut self, received_at: u64, times: &OrderTimes) {
+        if let Some(completed) = times.completed {
+            self.latencies.record(completed.saturating_sub(received_at));
+        }
+        for (waits, ingredient) in self.container_waits.iter_mut().zip(times.ingredients()) {
+            if let Some(wait) = ingredient.wait() {
+                waits.record(wait);
+            }
+        }
+    }
+
+    /// Resumen de la latencia de punta a punta de los pedidos completados.
+    pub fn latency(&self) -> LatencySummary {
+        self.latencies.summary()
+    }
+
+    /// Histograma de la latencia de punta a punta de los pedidos completados.
+    pub fn latency_histogram(&self) -> LatencyHistogram {
+        self.latencies.histogram()
+    }
+
+    /// Histograma de la espera por un dispensador de cada contenedor, en el orden de [`CONTAINERS`].
+    pub fn container_wait_histograms(&self) -> [LatencyHistogram; 4] {
+        [0, 1, 2, 3].map(|index| self.container_waits[index].histogram())
+    }
+
+    /// Resumen de la espera por un dispensador de cada contenedor, con la clave del contenedor.
+    pub fn container_waits(&self) -> [(&'static str, LatencySummary); 4] {
+        let mut summaries = [("", LatencySummary::default()); 4];
+        for (index, (key, _)) in CONTAINERS.iter().enumerate() {
+            summaries[index] = (key, self.container_waits[index].summary());
+        }
+        summaries
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::helpers::error::CustomError;
 
     #[test]
     fn test_new_statistics_values() {
         let statistics_values = StatisticsValues::new();
         assert_eq!(statistics_values.orders_served, 0);
+        assert_eq!(statistics_values.orders_rejected, 0);
+        assert_eq!(statistics_values.orders_partially_served, 0);
+        assert!(statistics_values.waits.is_empty());
         assert_eq!(statistics_values.shutdown, false);
     }
+
+    #[test]
+    fn test_set_status() {
+        let mut statistics_values = StatisticsValues::new();
+        statistics_values.set_status(OrderStatus::Queued);
+        statistics_values.set_status(OrderStatus::Queued);
+        statistics_values.set_status(OrderStatus::InProgress);
+        statistics_values.set_status(OrderStatus::Completed);
+        statistics_values.set_status(OrderStatus::PartiallyServed(
+            CustomError::InsufficientIngredients,
+        ));
+        assert_eq!(statistics_values.orders_served, 1);
+        assert_eq!(statistics_values.orders_rejected, 0);
+        assert_eq!(statistics_values.orders_partially_served, 1);
+    }
+
+    #[test]
+    fn test_record_wait() {
+        let mut statistics_values = StatisticsValues::new();
+        statistics_values.record_wait(Priority::Vip, 100);
+        statistics_values.record_wait(Priority::Vip, 300);
+        statistics_values.record_wait(Priority::Regular, 50);
+        let vip = statistics_values.waits[&Priority::Vip];
+        assert_eq!(vip.orders, 2);
+        assert_eq!(vip.average(), 200);
+        assert_eq!(vip.max, 300);
+        assert_eq!(statistics_values.waits[&Priority::Regular].average(), 50);
+        assert!(!statistics_values.waits.contains_key(&Priority::Staff));
+        assert_eq!(WaitTimes::default().average(), 0);
+    }
+
+    #[test]
+    fn test_record_times() {
+        let mut statistics_values = StatisticsValues::new();
+        let mut times = OrderTimes::default();
+        times.ground_coffee.arrive(100);
+        times.ground_coffee.dispensed = Some(300);
+        times.milk_foam.arrive(100);
+        times.milk_foam.dispensed = Some(100);
+        times.completed = Some(1100);
+        statistics_values.record_times(100, &times);
+        times.completed = None;
+        statistics_values.record_times(0, &times);
+
+        assert_eq!(statistics_values.latency().count, 1);
+        assert_eq!(statistics_values.latency().max, 1000);
+        let waits = statistics_values.container_waits();
+        assert_eq!(waits[0].0, "coffee");
+        assert_eq!(waits[0].1.count, 2);
+        assert_eq!(waits[0].1.mean, 200);
+        assert_eq!(waits[1].1.count, 0);
+        assert_eq!(waits[3].1.max, 0);
+    }
 }
@@ -0,0 +1,206 @@
+use std::io::{self, Write};
+
+use crate::helpers::config::StatsFormat;
+use crate::structs::order_times::CONTAINERS;
+use crate::structs::stats_snapshot::StatsSnapshot;
+
+/// Forma de imprimir las estadísticas que recolecta el hilo de estadísticas de la cafetera.
+/// Se elige una por [`StatsFormat`] con [`for_format`] o se registra otra con
+/// [`crate::structs::coffee_maker::CoffeeMaker::set_stats_renderer`].
+pub trait StatsRenderer: Send {
+    /// Imprime una toma de estadísticas. Se llama cada [`crate::helpers::config::Config::time_to_stats`] milisegundos.
+    fn render(&mut self, snapshot: &StatsSnapshot) -> io::Result<()>;
+}
+
+/// Devuelve el renderizador del formato indicado, que escribe en `output`.
+pub fn for_format(format: StatsFormat, output: Box<dyn Write + Send>) -> Box<dyn StatsRenderer> {
+    match format {
+        StatsFormat::Text => Box::new(TextRenderer { output }),
+        StatsFormat::Json => Box::new(JsonLinesRenderer { output }),
+        StatsFormat::Csv => Box::new(CsvRenderer {
+            output,
+            header_written: false,
+        }),
+    }
+}
+
+/// Bloque de texto legible.
+pub struct TextRenderer<W: Write + Send> {
+    output: W,
+}
+
+impl<W: Write + Send> StatsRenderer for TextRenderer<W> {
+    fn render(&mut self, s: &StatsSnapshot) -> io::Result<()> {
+        writeln!(
+            self.output,
+            r#"
+                Estadísticas ({:?} ms):
+                    Ordenes completas: {:?}
+                    Ordenes rechazadas: {:?}
+                    Ordenes servidas parcialmente: {:?}
+                    Café:
+                        Granos consumidos: {:?}
+                        Café molido consumido: {:?}
+                        Disponibilidad de granos: {:?}
+                        Disponibilidad de café molido: {:?}
+                        Rellenados: {:?}
+                    Agua caliente:
+                        Consumida: {:?}
+                        Disponibilidad: {:?}
+                        Rellenados: {:?}
+                    Cacao:
+                        Consumido: {:?}
+                        Disponibilidad: {:?}
+                    Leche:
+                        Leche fría consumida: {:?}
+                        Espuma de leche consumida: {:?}
+                        Leche fría disponible: {:?}
+                        Espuma de leche disponible: {:?}
+                        Rellenados: {:?}
+                    Espera por prioridad:
+{}
+                    Latencia de pedidos completados:
+                        {}
+                    Espera por dispensador:
+{}
+            "#,
+            s.time,
+            s.orders_served,
+            s.orders_rejected,
+            s.orders_partially_served,
+            s.grains_used,
+            s.coffee_used,
+            s.grains,
+            s.coffee,
+            s.coffee_refills,
+            s.water_used,
+            s.water,
+            s.water_refills,
+            s.cocoa_used,
+            s.cocoa,
+            s.cold_milk_used,
+            s.foam_used,
+            s.cold_milk,
+            s.foam,
+            s.foam_refills,
+            s.waits
+                .iter()
+                .map(|(priority, wait)| format!(
+                    "                        {}: {:?} pedidos, promedio {:?} ms, máxima {:?} ms",
+                    priority.name(),
+                    wait.orders,
+                    wait.average(),
+                    wait.max
+                ))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            s.latency.describe(),
+            s.container_waits
+                .iter()
+                .zip(CONTAINERS)
+                .map(|((_, waits), (_, name))| format!(
+                    "                        {}: {}",
+                    name,
+                    waits.describe()
+                ))
+                .collect::<Vec<String>>()
+                .join("\n")
+        )?;
+        self.output.flush()
+    }
+}
+
+/// Un objeto JSON por toma, en una línea.
+pub struct JsonLinesRenderer<W: Write + Send> {
+    output: W,
+}
+
+impl<W: Write + Send> StatsRenderer for JsonLinesRenderer<W> {
+    fn render(&mut self, snapshot: &StatsSnapshot) -> io::Result<()> {
+        let fields: Vec<String> = snapshot
+            .fields()
+            .iter()
+            .map(|(name, value)| format!("\"{}\":{}", name, value))
+            .collect();
+        writeln!(self.output, "{{{}}}", fields.join(","))?;
+        self.output.flush()
+    }
+}
+
+/// Serie de tiempo en CSV: una fila por toma, con el encabezado antes de la primera.
+pub struct CsvRenderer<W: Write + Send> {
+    output: W,
+    header_written: bool,
+}
+
+impl<W: Write + Send> StatsRenderer for CsvRenderer<W> {
+    fn render(&mut self, snapshot: &StatsSnapshot) -> io::Result<()> {
+        let fields = snapshot.fields();
+        if !self.header_written {
+            let header: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+            writeln!(self.output, "{}", header.join(","))?;
+            self.header_written = true;
+        }
+        let row: Vec<String> = fields.iter().map(|(_, value)| value.to_string()).collect();
+        writeln!(self.output, "{}", row.join(","))?;
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Salida que guarda lo escrito para poder revisarlo después de entregar el renderizador.
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn render_twice(format: StatsFormat) -> String {
+        let output = SharedOutput::default();
+        let mut renderer = for_format(format, Box::new(output.clone()));
+        let mut snapshot = StatsSnapshot {
+            time: 100,
+            orders_served: 2,
+            ..StatsSnapshot::default()
+        };
+        renderer.render(&snapshot).unwrap();
+        snapshot.time = 200;
+        snapshot.orders_served = 3;
+        renderer.render(&snapshot).unwrap();
+        let written = output.0.lock().unwrap().clone();
+        String::from_utf8(written).unwrap()
+    }
+
+    #[test]
+    fn test_json_lines_renderer() {
+        let written = render_twice(StatsFormat::Json);
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("{\"time_ms\":100,\"orders_served\":2,"));
+        assert!(lines[1].starts_with("{\"time_ms\":200,\"orders_served\":3,"));
+        assert!(lines[1].ends_with("\"wait_p99_foam\":0}"));
+    }
+
+    #[test]
+    fn test_csv_renderer_writes_header_once() {
+        let written = render_twice(StatsFormat::Csv);
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("time_ms,orders_served,orders_rejected,"));
+        assert!(lines[1].starts_with("100,2,0,"));
+        assert!(lines[2].starts_with("200,3,0,"));
+        assert_eq!(lines[0].split(',').count(), lines[2].split(',').count());
+    }
+}
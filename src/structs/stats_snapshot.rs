@@ -0,0 +1,310 @@
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::helpers::config::Config;
+use crate::helpers::error::CustomError;
+use crate::structs::cocoa_container::CocoaContainer;
+use crate::structs::coffee_container::CoffeeContainer;
+use crate::structs::hot_water_container::HotWaterContainer;
+use crate::structs::latency_summary::{LatencyHistogram, LatencySummary};
+use crate::structs::milk_container::MilkContainer;
+use crate::structs::order_times::CONTAINERS;
+use crate::structs::priority::Priority;
+use crate::structs::statistics_values::{StatisticsValues, WaitTimes};
+
+/// Estadísticas de la cafetera en un momento dado, como las recolecta el hilo de estadísticas.
+/// Se imprimen con un [`crate::structs::stats_renderer::StatsRenderer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StatsSnapshot {
+    /// Momento en que se tomaron, en milisegundos del reloj.
+    pub time: u64,
+    pub orders_served: u32,
+    pub orders_rejected: u32,
+    pub orders_partially_served: u32,
+    pub grains_used: u32,
+    pub coffee_used: u32,
+    pub grains: u32,
+    pub coffee: u32,
+    pub water_used: u32,
+    pub water: u32,
+    pub cocoa_used: u32,
+    pub cocoa: u32,
+    pub cold_milk_used: u32,
+    pub foam_used: u32,
+    pub cold_milk: u32,
+    pub foam: u32,
+    /// Cantidad de veces que se rellenó cada contenedor.
+    pub coffee_refills: u32,
+    pub water_refills: u32,
+    pub foam_refills: u32,
+    /// Cantidad de agua que se calentó de la red al rellenar.
+    pub water_heated: u32,
+    /// Esperas de los pedidos preparados, por clase en el orden de [`Priority::ALL`].
+    pub waits: [(Priority, WaitTimes); 3],
+    /// Latencia de punta a punta de los pedidos completados.
+    pub latency: LatencySummary,
+    /// Espera por un dispensador de cada contenedor, con la clave de [`CONTAINERS`].
+    pub container_waits: [(&'static str, LatencySummary); 4],
+    /// Histograma de la latencia de los pedidos completados.
+    pub latency_histogram: LatencyHistogram,
+    /// Histograma de la espera por un dispensador de cada contenedor, en el orden de [`CONTAINERS`].
+    pub container_wait_histograms: [LatencyHistogram; 4],
+}
+
+impl StatsSnapshot {
+    /// Recolecta las estadísticas de los contenedores y de los pedidos en el momento `time`.
+    /// Toma todos los locks a la vez antes de leer, así la toma es consistente: ningún contenedor ni contador cambia
+    /// mientras se lee. Los locks se toman en el orden café, agua caliente, cacao, leche y estadísticas; el resto de la
+    /// cafetera, cuando toma más de uno, respeta el mismo orden, así no se generan deadlocks.
+    /// Bajo los locks solo se copian los contadores, el stock y las muestras de duraciones; los resúmenes de latencia,
+    /// que ordenan las muestras, se calculan después de soltarlos para no frenar a los baristas.
+    /// Si algún lock está envenenado devuelve [`CustomError::PoisonedLock`]
+    pub fn collect(
+        coffee_container: &Arc<(Mutex<CoffeeContainer>, Condvar)>,
+        hot_water_container: &Arc<(Mutex<HotWaterContainer>, Condvar)>,
+        cocoa_container: &Arc<(Mutex<CocoaContainer>, Condvar)>,
+        milk_container: &Arc<(Mutex<MilkContainer>, Condvar)>,
+        statistics_values: &Arc<Mutex<StatisticsValues>>,
+        time: u64,
+    ) -> Result<StatsSnapshot, CustomError> {
+        let coffee_lock = coffee_container.0.lock()?;
+        let h_w_lock = hot_water_container.0.lock()?;
+        let cocoa_lock = cocoa_container.0.lock()?;
+        let milk_lock = milk_container.0.lock()?;
+        let statistics_values = statistics_values.lock()?;
+
+        let mut snapshot = StatsSnapshot {
+            time,
+            ..StatsSnapshot::default()
+        };
+        snapshot.grains_used = coffee_lock.coffee_grains_used;
+        snapshot.coffee_used = coffee_lock.ground_coffee_used;
+        snapshot.grains = coffee_lock.coffee_grains_container;
+        snapshot.coffee = coffee_lock.ground_coffee_container;
+        snapshot.coffee_refills = coffee_lock.refills;
+
+        snapshot.water_used = h_w_lock.used;
+        snapshot.water = h_w_lock.hot_water;
+        snapshot.water_refills = h_w_lock.refills;
+        snapshot.water_heated = h_w_lock.heated;
+
+        snapshot.cocoa_used = cocoa_lock.used;
+        snapshot.cocoa = cocoa_lock.cocoa;
+
+        snapshot.cold_milk_used = milk_lock.cold_milk_used;
+        snapshot.foam_used = milk_lock.milk_foam_used;
+        snapshot.cold_milk = milk_lock.cold_milk_container;
+        snapshot.foam = milk_lock.milk_foam_container;
+        snapshot.foam_refills = milk_lock.refills;
+
+        snapshot.orders_served = statistics_values.orders_served;
+        snapshot.orders_rejected = statistics_values.orders_rejected;
+        snapshot.orders_partially_served = statistics_values.orders_partially_served;
+        snapshot.waits = Priority::ALL.map(|priority| {
+            (
+                priority,
+                statistics_values
+                    .waits
+                    .get(&priority)
+                    .copied()
+                    .unwrap_or_default(),
+            )
+        });
+        snapshot.latency_histogram = statistics_values.latency_histogram();
+        snapshot.container_wait_histograms = statistics_values.container_wait_histograms();
+        let latencies = statistics_values.latencies.clone();
+        let container_waits = statistics_values.container_waits.clone();
+        drop(statistics_values);
+        drop(milk_lock);
+        drop(cocoa_lock);
+        drop(h_w_lock);
+        drop(coffee_lock);
+
+        snapshot.latency = latencies.summary();
+        for (index, (key, _)) in CONTAINERS.iter().enumerate() {
+            snapshot.container_waits[index] = (key, container_waits[index].summary());
+        }
+        Ok(snapshot)
+    }
+
+    /// Verifica que en cada contenedor lo consumido más lo disponible sea lo que había al empezar más lo que se
+    /// rellenó: los granos y la leche fría no se rellenan, el café molido se rellena con los granos que se muelen,
+    /// la espuma con la leche fría y el agua caliente con la que se calienta de la red.
+    /// Devuelve una descripción de cada invariante que no se cumple.
+    pub fn invariant_violations(&self, config: &Config) -> Vec<String> {
+        let invariants = [
+            (
+                "granos de café",
+                self.grains_used + self.grains,
+                config.coffee_grains_capacity,
+            ),
+            (
+                "café molido",
+                self.coffee_used + self.coffee,
+                config.ground_coffee_capacity + self.grains_used,
+            ),
+            (
+                "agua caliente",
+                self.water_used + self.water,
+                config.hot_water_capacity + self.water_heated,
+            ),
+            ("cacao", self.cocoa_used + self.cocoa, config.cocoa_capacity),
+            (
+                "leche fría",
+                self.cold_milk_used + self.cold_milk,
+                config.cold_milk_capacity,
+            ),
+            (
+                "espuma de leche",
+                self.foam_used + self.foam,
+                config.milk_foam_capacity + self.cold_milk_used,
+            ),
+        ];
+        invariants
+            .iter()
+            .filter(|(_, total, expected)| total != expected)
+            .map(|(name, total, expected)| {
+                format!(
+                    "{}: consumido más disponible es {:?} pero debería ser {:?}",
+                    name, total, expected
+                )
+            })
+            .collect()
+    }
+
+    /// Todos los valores con su nombre, en el orden de las columnas del CSV.
+    /// Los nombres son las claves del JSON, por ejemplo `orders_served`, `wait_avg_vip` o `latency_p95`.
+    pub fn fields(&self) -> Vec<(String, u64)> {
+        let mut fields: Vec<(String, u64)> = [
+            ("time_ms", self.time),
+            ("orders_served", self.orders_served.into()),
+            ("orders_rejected", self.orders_rejected.into()),
+            (
+                "orders_partially_served",
+                self.orders_partially_served.into(),
+            ),
+            ("grains_used", self.grains_used.into()),
+            ("coffee_used", self.coffee_used.into()),
+            ("grains", self.grains.into()),
+            ("coffee", self.coffee.into()),
+            ("water_used", self.water_used.into()),
+            ("water", self.water.into()),
+            ("cocoa_used", self.cocoa_used.into()),
+            ("cocoa", self.cocoa.into()),
+            ("cold_milk_used", self.cold_milk_used.into()),
+            ("foam_used", self.foam_used.into()),
+            ("cold_milk", self.cold_milk.into()),
+            ("foam", self.foam.into()),
+            ("coffee_refills", self.coffee_refills.into()),
+            ("water_refills", self.water_refills.into()),
+            ("foam_refills", self.foam_refills.into()),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+        for (priority, wait) in &self.waits {
+            fields.push((format!("wait_avg_{}", priority.name()), wait.average()));
+            fields.push((format!("wait_max_{}", priority.name()), wait.max));
+        }
+        let percentiles = |summary: &LatencySummary| {
+            [
+                ("mean", summary.mean),
+                ("p50", summary.p50),
+                ("p95", summary.p95),
+                ("p99", summary.p99),
+            ]
+        };
+        for (name, value) in percentiles(&self.latency) {
+            fields.push((format!("latency_{}", name), value));
+        }
+        for ((_, waits), (container, _)) in self.container_waits.iter().zip(CONTAINERS) {
+            for (name, value) in percentiles(waits) {
+                fields.push((format!("wait_{}_{}", name, container), value));
+            }
+        }
+        fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::order::OrderStatus;
+
+    #[test]
+    fn test_collect_snapshot() {
+        let config = Config::default();
+        let coffee_container =
+            Arc::new((Mutex::new(CoffeeContainer::new(&config)), Condvar::new()));
+        let hot_water_container =
+            Arc::new((Mutex::new(HotWaterContainer::new(&config)), Condvar::new()));
+        let cocoa_container = Arc::new((Mutex::new(CocoaContainer::new(&config)), Condvar::new()));
+        let milk_container = Arc::new((Mutex::new(MilkContainer::new(&config)), Condvar::new()));
+        let statistics_values = Arc::new(Mutex::new(StatisticsValues::new()));
+        cocoa_container.0.lock().unwrap().used = 20;
+        statistics_values
+            .lock()
+            .unwrap()
+            .set_status(OrderStatus::Completed);
+        statistics_values
+            .lock()
+            .unwrap()
+            .record_wait(Priority::Vip, 150);
+
+        let snapshot = StatsSnapshot::collect(
+            &coffee_container,
+            &hot_water_container,
+            &cocoa_container,
+            &milk_container,
+            &statistics_values,
+            500,
+        )
+        .unwrap();
+        assert_eq!(snapshot.time, 500);
+        assert_eq!(snapshot.orders_served, 1);
+        assert_eq!(snapshot.cocoa_used, 20);
+        assert_eq!(snapshot.cocoa, config.cocoa_capacity);
+        assert_eq!(
+            snapshot.waits[1],
+            (
+                Priority::Vip,
+                WaitTimes {
+                    orders: 1,
+                    total: 150,
+                    max: 150
+                }
+            )
+        );
+
+        let fields = snapshot.fields();
+        assert_eq!(fields[0], ("time_ms".to_string(), 500));
+        assert!(fields.contains(&("wait_max_vip".to_string(), 150)));
+        assert!(fields.contains(&("wait_p99_foam".to_string(), 0)));
+        assert_eq!(fields.len(), 19 + 2 * 3 + 4 + 4 * 4);
+    }
+
+    #[test]
+    fn test_invariant_violations() {
+        let config = Config::default();
+        let mut snapshot = StatsSnapshot {
+            grains: config.coffee_grains_capacity - 30,
+            grains_used: 30,
+            coffee: config.ground_coffee_capacity - 10,
+            coffee_used: 40,
+            water: config.hot_water_capacity,
+            water_used: 25,
+            water_heated: 25,
+            cocoa: config.cocoa_capacity,
+            cold_milk: config.cold_milk_capacity,
+            foam: config.milk_foam_capacity,
+            ..StatsSnapshot::default()
+        };
+        assert!(snapshot.invariant_violations(&config).is_empty());
+
+        snapshot.cocoa_used = 5;
+        snapshot.water_heated = 0;
+        let violations = snapshot.invariant_violations(&config);
+        assert_eq!(violations.len(), 2);
+        assert!(violations[0].starts_with("agua caliente"));
+        assert!(violations[1].starts_with("cacao"));
+    }
+}
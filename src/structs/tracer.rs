@@ -0,0 +1,235 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::helpers::clock::Clock;
+use crate::helpers::json;
+use crate::helpers::logger;
+
+/// Actividad de un hilo entre dos momentos del reloj, en milisegundos.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceSpan {
+    /// Qué hizo el hilo, por ejemplo `servir café molido`.
+    pub name: &'static str,
+    /// Tipo de actividad: `order`, `take`, `lock`, `wait`, `serve` o `refill`.
+    pub category: &'static str,
+    /// Nombre del hilo.
+    pub thread: String,
+    /// Pedido que estaba procesando el hilo, si procesaba uno (ver [`logger::order_scope`]).
+    pub order: Option<u32>,
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Registra la actividad de los hilos de la cafetera para verla como una línea de tiempo.
+/// No registra nada hasta que se llama a [`Tracer::start`]. Los clones comparten lo registrado.
+#[derive(Clone, Default)]
+pub struct Tracer {
+    recording: Arc<AtomicBool>,
+    spans: Arc<Mutex<Vec<TraceSpan>>>,
+}
+
+/// Actividad en curso, ver [`Tracer::span`]. Al descartarse se registra con el momento en que terminó.
+pub struct Span<'a> {
+    tracer: &'a Tracer,
+    clock: &'a Clock,
+    category: &'static str,
+    name: &'static str,
+    start: Option<u64>,
+}
+
+impl Drop for Span<'_> {
+    fn drop(&mut self) {
+        if let Some(start) = self.start {
+            self.tracer
+                .record(self.category, self.name, start, self.clock.now());
+        }
+    }
+}
+
+impl Tracer {
+    /// Empieza a registrar la actividad.
+    pub fn start(&self) {
+        self.recording.store(true, Ordering::SeqCst);
+    }
+
+    /// Indica si se está registrando la actividad.
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::Relaxed)
+    }
+
+    /// Empieza una actividad del hilo actual, que termina cuando se descarta el [`Span`] devuelto.
+    pub fn span<'a>(
+        &'a self,
+        clock: &'a Clock,
+        category: &'static str,
+        name: &'static str,
+    ) -> Span<'a> {
+        Span {
+            tracer: self,
+            clock,
+            category,
+            name,
+            start: self.is_recording().then(|| clock.now()),
+        }
+    }
+
+    /// Registra lo que tarda `f` como una actividad del hilo actual y devuelve su resultado.
+    pub fn measure<T>(
+        &self,
+        clock: &Clock,
+        category: &'static str,
+        name: &'static str,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        let _span = self.span(clock, category, name);
+        f()
+    }
+
+    /// Registra una actividad del hilo actual entre `start` y `end`.
+    /// Las actividades que no duran nada, como tomar un lock libre, no se registran.
+    fn record(&self, category: &'static str, name: &'static str, start: u64, end: u64) {
+        if end <= start {
+            return;
+        }
+        let span = TraceSpan {
+            name,
+            category,
+            thread: logger::thread_name(),
+            order: logger::current_order(),
+            start,
+            end,
+        };
+        if let Ok(mut spans) = self.spans.lock() {
+            spans.push(span);
+        }
+    }
+
+    /// Las actividades registradas, ordenadas por inicio. Las que empiezan a la vez quedan primero las más largas,
+    /// que son las que contienen a las otras.
+    pub fn spans(&self) -> Vec<TraceSpan> {
+        let mut spans = match self.spans.lock() {
+            Ok(spans) => spans.clone(),
+            Err(_) => Vec::new(),
+        };
+        spans.sort_by_key(|span| (span.start, Reverse(span.end)));
+        spans
+    }
+
+    /// Escribe las actividades en el formato Trace Event de Chrome, para abrirlas con `chrome://tracing` o Perfetto:
+    /// un evento completo (`"ph":"X"`) por actividad, con los tiempos en microsegundos, y el nombre de cada hilo.
+    pub fn write_chrome_trace(&self, mut output: impl Write) -> io::Result<()> {
+        let spans = self.spans();
+        let mut threads: HashMap<&str, usize> = HashMap::new();
+        let mut events = Vec::new();
+        for span in &spans {
+            let next_id = threads.len() + 1;
+            let tid = *threads.entry(span.thread.as_str()).or_insert_with(|| {
+                events.push(format!(
+                    "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":{}}}}}",
+                    next_id,
+                    json::quote(&span.thread)
+                ));
+                next_id
+            });
+            let args = match span.order {
+                Some(order) => format!(",\"args\":{{\"pedido\":{}}}", order),
+                None => String::new(),
+            };
+            events.push(format!(
+                "{{\"name\":{},\"cat\":{},\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":{}{}}}",
+                json::quote(span.name),
+                json::quote(span.category),
+                span.start * 1000,
+                (span.end - span.start) * 1000,
+                tid,
+                args
+            ));
+        }
+        writeln!(
+            output,
+            "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n{}\n]}}",
+            events.join(",\n")
+        )?;
+        output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_tracer_records_only_while_recording() {
+        let clock = Clock::simulated();
+        let _participant = clock.participate();
+        let tracer = Tracer::default();
+        tracer.measure(&clock, "serve", "servir cacao", || {
+            clock.sleep(Duration::from_millis(100))
+        });
+        assert!(tracer.spans().is_empty());
+
+        tracer.start();
+        let _scope = logger::order_scope(4);
+        {
+            let _span = tracer.span(&clock, "order", "preparar pedido");
+            tracer.measure(&clock, "lock", "lock cacao", || ());
+            clock.sleep(Duration::from_millis(50));
+            tracer.measure(&clock, "serve", "servir cacao", || {
+                clock.sleep(Duration::from_millis(250))
+            });
+        }
+        let spans = tracer.spans();
+        assert_eq!(spans.len(), 2);
+        assert_eq!((spans[0].start, spans[0].end), (100, 400));
+        assert_eq!(spans[0].name, "preparar pedido");
+        assert_eq!(spans[1].name, "servir cacao");
+        assert_eq!((spans[1].start, spans[1].end), (150, 400));
+        assert_eq!(spans[1].order, Some(4));
+        assert_eq!(spans[1].thread, logger::thread_name());
+    }
+
+    #[test]
+    fn test_write_chrome_trace() {
+        let tracer = Tracer::default();
+        tracer.spans.lock().unwrap().extend([
+            TraceSpan {
+                name: "moler café",
+                category: "refill",
+                thread: "moler-cafe".to_string(),
+                order: None,
+                start: 20,
+                end: 70,
+            },
+            TraceSpan {
+                name: "servir café molido",
+                category: "serve",
+                thread: "barista-0".to_string(),
+                order: Some(1),
+                start: 10,
+                end: 15,
+            },
+        ]);
+        let mut output = Vec::new();
+        tracer.write_chrome_trace(&mut output).unwrap();
+        let lines: Vec<String> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "{\"displayTimeUnit\":\"ms\",\"traceEvents\":[",
+                "{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":1,\"args\":{\"name\":\"barista-0\"}},",
+                "{\"name\":\"servir café molido\",\"cat\":\"serve\",\"ph\":\"X\",\"ts\":10000,\"dur\":5000,\"pid\":1,\"tid\":1,\"args\":{\"pedido\":1}},",
+                "{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":2,\"args\":{\"name\":\"moler-cafe\"}},",
+                "{\"name\":\"moler café\",\"cat\":\"refill\",\"ph\":\"X\",\"ts\":20000,\"dur\":50000,\"pid\":1,\"tid\":2}",
+                "]}",
+            ]
+        );
+    }
+}